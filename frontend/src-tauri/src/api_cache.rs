@@ -0,0 +1,349 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+  },
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+// 缓存格式版本：与目录下的 VERSION 文件不一致时整个目录作废（旧版本的条目可能含凭据头或归属不明）
+const CACHE_FORMAT: &str = "2";
+
+// 单条缓存上限：超过的响应照常转发，但不落盘，避免大列表/导出把缓存目录撑爆
+const MAX_ENTRY_BYTES: u64 = 16 * 1024 * 1024;
+
+// 全部账户合计的上限：超出后按最近访问时间淘汰到 90%
+const MAX_TOTAL_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedMeta {
+  pub url: String,
+  pub status: u16,
+  pub headers: Vec<(String, String)>,
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+  pub stored_at: u64,
+  pub size: u64,
+}
+
+pub struct CachedEntry {
+  pub meta: CachedMeta,
+  pub body_path: PathBuf,
+}
+
+/// /api 响应的磁盘缓存：按账户分子目录，目录内按上游完整 URL（含 path 与 query）寻址，
+/// 网关在线时用 ETag/Last-Modified 做条件请求，上游不可达时回放旧响应。
+pub struct ApiCache {
+  dir: PathBuf,
+  seq: AtomicU64,
+  budget: Arc<Budget>,
+}
+
+/// 缓存目录的总占用（估算值：写入时累加，淘汰时重新统计）。
+struct Budget {
+  dir: PathBuf,
+  used: AtomicU64,
+  evicting: AtomicBool,
+}
+
+pub fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+impl ApiCache {
+  pub fn new(dir: PathBuf) -> Self {
+    let version_path = dir.join("VERSION");
+    if std::fs::read_to_string(&version_path).ok().as_deref() != Some(CACHE_FORMAT) {
+      let _ = std::fs::remove_dir_all(&dir);
+      if std::fs::create_dir_all(&dir).is_ok() {
+        let _ = std::fs::write(&version_path, CACHE_FORMAT);
+      }
+    }
+    let used = scan_entries(&dir).iter().map(|e| e.size).sum();
+    Self {
+      budget: Arc::new(Budget {
+        dir: dir.clone(),
+        used: AtomicU64::new(used),
+        evicting: AtomicBool::new(false),
+      }),
+      dir,
+      seq: AtomicU64::new(0),
+    }
+  }
+
+  /// 账户标识（服务器 + 用户名的哈希）：切换账户或退出后不会把别人的响应回放出来。
+  pub fn account_scope(backend: &str, account: Option<&str>) -> String {
+    let raw = format!("{}|{}", backend.trim_end_matches('/'), account.unwrap_or("").trim());
+    Sha256::digest(raw.as_bytes())
+      .iter()
+      .take(16)
+      .map(|b| format!("{:02x}", b))
+      .collect()
+  }
+
+  pub fn key_for(url: &str) -> String {
+    let digest = Sha256::digest(url.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+  }
+
+  fn scope_dir(&self, scope: &str) -> PathBuf {
+    self.dir.join(scope)
+  }
+
+  pub async fn load(&self, scope: &str, key: &str) -> Option<CachedEntry> {
+    let dir = self.scope_dir(scope);
+    let raw = tokio::fs::read(dir.join(format!("{key}.json"))).await.ok()?;
+    let meta = serde_json::from_slice::<CachedMeta>(&raw).ok()?;
+    let body_path = dir.join(format!("{key}.body"));
+    let len = tokio::fs::metadata(&body_path).await.ok()?.len();
+    // meta 与 body 分两步落盘；长度对不上说明写到一半，按未命中处理
+    if len != meta.size {
+      return None;
+    }
+    // 用 body 的修改时间记录最近访问，淘汰时据此排序
+    if let Ok(file) = std::fs::File::options().append(true).open(&body_path) {
+      let _ = file.set_modified(SystemTime::now());
+    }
+    Some(CachedEntry { meta, body_path })
+  }
+
+  /// 把上游 body 原样转发给调用方，同时写入缓存。
+  /// 写盘在独立任务里完成：即使 WebView 中途放弃请求，缓存也能写完整。
+  pub fn tee<S>(
+    &self,
+    scope: &str,
+    key: String,
+    mut meta: CachedMeta,
+    upstream: S,
+  ) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static
+  where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + Unpin + 'static,
+  {
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(16);
+    let dir = self.scope_dir(scope);
+    let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = dir.join(format!("{key}.{seq}.tmp"));
+    let budget = self.budget.clone();
+
+    tokio::spawn(async move {
+      let mut upstream = upstream;
+      let mut file = match tokio::fs::create_dir_all(&dir).await {
+        Ok(()) => tokio::fs::File::create(&tmp_path).await.ok(),
+        Err(_) => None,
+      };
+      let mut written: u64 = 0;
+      let mut client_gone = false;
+
+      while let Some(chunk) = upstream.next().await {
+        match chunk {
+          Ok(bytes) => {
+            if let Some(f) = file.as_mut() {
+              written += bytes.len() as u64;
+              if written > MAX_ENTRY_BYTES || f.write_all(&bytes).await.is_err() {
+                file = None;
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+              }
+            }
+
+            if !client_gone && tx.send(Ok(bytes)).await.is_err() {
+              client_gone = true;
+            }
+
+            if client_gone && file.is_none() {
+              return;
+            }
+          }
+          Err(e) => {
+            drop(file);
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            if !client_gone {
+              let _ = tx.send(Err(std::io::Error::other(e))).await;
+            }
+            return;
+          }
+        }
+      }
+
+      if let Some(mut f) = file {
+        if f.flush().await.is_err() {
+          let _ = tokio::fs::remove_file(&tmp_path).await;
+          return;
+        }
+        drop(f);
+        meta.size = written;
+        match finalize_entry(&dir, &key, seq, &tmp_path, &meta).await {
+          Ok(stored) => budget.add(stored),
+          Err(e) => {
+            log::warn!("[api-cache] store {} failed: {}", meta.url, e);
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+          }
+        }
+      }
+    });
+
+    futures_util::stream::unfold(rx, |mut rx| async move {
+      rx.recv().await.map(|item| (item, rx))
+    })
+  }
+
+  /// 退出登录时删除该账户的全部缓存。
+  pub fn purge_scope(&self, scope: &str) {
+    let dir = self.scope_dir(scope);
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+      if e.kind() != std::io::ErrorKind::NotFound {
+        log::warn!("[api-cache] purge {} failed: {}", dir.display(), e);
+      }
+    }
+    self.budget.recount();
+  }
+}
+
+impl Budget {
+  /// 记一笔新写入；超出总上限时在后台淘汰（同一时间只跑一个）。
+  fn add(self: &Arc<Self>, bytes: u64) {
+    let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    if used <= MAX_TOTAL_BYTES || self.evicting.swap(true, Ordering::AcqRel) {
+      return;
+    }
+    let budget = self.clone();
+    tokio::task::spawn_blocking(move || {
+      budget.evict(MAX_TOTAL_BYTES / 10 * 9);
+      budget.evicting.store(false, Ordering::Release);
+    });
+  }
+
+  fn recount(&self) {
+    let used = scan_entries(&self.dir).iter().map(|e| e.size).sum();
+    self.used.store(used, Ordering::Relaxed);
+  }
+
+  /// 从最久未访问的条目开始删除，直到总占用不超过 `target`。
+  fn evict(&self, target: u64) {
+    let mut entries = scan_entries(&self.dir);
+    let mut used: u64 = entries.iter().map(|e| e.size).sum();
+    entries.sort_by_key(|e| e.accessed);
+    for entry in entries {
+      if used <= target {
+        break;
+      }
+      let _ = std::fs::remove_file(entry.body.with_extension("json"));
+      let _ = std::fs::remove_file(&entry.body);
+      used = used.saturating_sub(entry.size);
+    }
+    self.used.store(used, Ordering::Relaxed);
+  }
+}
+
+struct StoredEntry {
+  body: PathBuf,
+  size: u64,
+  accessed: SystemTime,
+}
+
+/// 列出各账户子目录下的已完成条目（body + meta 的合计大小）。
+fn scan_entries(dir: &Path) -> Vec<StoredEntry> {
+  let mut out = Vec::new();
+  let Ok(scopes) = std::fs::read_dir(dir) else {
+    return out;
+  };
+  for scope in scopes.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())) {
+    let Ok(files) = std::fs::read_dir(scope.path()) else {
+      continue;
+    };
+    for file in files.flatten() {
+      let body = file.path();
+      if body.extension().and_then(|e| e.to_str()) != Some("body") {
+        continue;
+      }
+      let Ok(meta) = file.metadata() else {
+        continue;
+      };
+      let meta_size = std::fs::metadata(body.with_extension("json")).map(|m| m.len()).unwrap_or(0);
+      out.push(StoredEntry {
+        size: meta.len() + meta_size,
+        accessed: meta.modified().unwrap_or(UNIX_EPOCH),
+        body,
+      });
+    }
+  }
+  out
+}
+
+async fn finalize_entry(
+  dir: &Path,
+  key: &str,
+  seq: u64,
+  tmp_body: &Path,
+  meta: &CachedMeta,
+) -> Result<u64, String> {
+  let raw = serde_json::to_vec(meta).map_err(|e| format!("serialize meta failed: {e}"))?;
+  let stored = meta.size + raw.len() as u64;
+  let body_path = dir.join(format!("{key}.body"));
+  let meta_path = dir.join(format!("{key}.json"));
+  // 同一个 key 可能有多个写入者并发：临时文件名带上各自的序号，互不覆盖
+  let meta_tmp = dir.join(format!("{key}.{seq}.json.tmp"));
+
+  tokio::fs::rename(tmp_body, &body_path)
+    .await
+    .map_err(|e| format!("rename body failed: {e}"))?;
+  tokio::fs::write(&meta_tmp, raw)
+    .await
+    .map_err(|e| format!("write meta failed: {e}"))?;
+  tokio::fs::rename(&meta_tmp, &meta_path)
+    .await
+    .map_err(|e| format!("rename meta failed: {e}"))?;
+  Ok(stored)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  fn write_entry(scope_dir: &Path, key: &str, size: usize, accessed: SystemTime) {
+    let body = scope_dir.join(format!("{key}.body"));
+    std::fs::write(&body, vec![0u8; size]).unwrap();
+    std::fs::write(scope_dir.join(format!("{key}.json")), b"{}").unwrap();
+    std::fs::File::options().append(true).open(&body).unwrap().set_modified(accessed).unwrap();
+  }
+
+  #[test]
+  fn evict_removes_least_recently_used_first() {
+    let dir = std::env::temp_dir().join(format!("pdh-api-cache-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let scope = dir.join("scope");
+    std::fs::create_dir_all(&scope).unwrap();
+
+    let now = SystemTime::now();
+    write_entry(&scope, "old", 1000, now - Duration::from_secs(300));
+    write_entry(&scope, "mid", 1000, now - Duration::from_secs(200));
+    write_entry(&scope, "new", 1000, now - Duration::from_secs(100));
+
+    let budget = Budget {
+      dir: dir.clone(),
+      used: AtomicU64::new(0),
+      evicting: AtomicBool::new(false),
+    };
+    budget.recount();
+    assert_eq!(budget.used.load(Ordering::Relaxed), 3 * 1002);
+
+    budget.evict(2500);
+    assert!(!scope.join("old.body").exists() && !scope.join("old.json").exists());
+    assert!(scope.join("mid.body").exists());
+    assert!(scope.join("new.body").exists());
+    assert_eq!(budget.used.load(Ordering::Relaxed), 2 * 1002);
+
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,870 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs,
+  io::Read,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+  },
+};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::api_cache::now_secs;
+
+pub const DEFAULT_CAP_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+// 后台补齐每次请求的区间大小；太大拖慢暂停/切换，太小请求数过多
+const FILL_CHUNK_BYTES: u64 = 8 * 1024 * 1024;
+
+// 边写边登记已落盘区间，让正在播放的视频尽早从本地命中
+const RECORD_EVERY_BYTES: u64 = 1024 * 1024;
+
+// 同时进行的后台补齐上限：大量附件被顺序读到一半时不至于占满带宽
+const MAX_CONCURRENT_FILLS: usize = 2;
+
+/// 按字节区间向上游取数据；None 表示取完整文件。
+/// 由网关提供：每次调用都读取最新的 backend/token，避免长时间补齐时 token 过期。
+pub type Filler = Arc<dyn Fn(Option<(u64, u64)>) -> reqwest::RequestBuilder + Send + Sync>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredHeaders {
+  pub content_type: Option<String>,
+  pub content_disposition: Option<String>,
+  pub cache_control: Option<String>,
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+  // 所属账户（服务器 + 用户名的哈希，见 GatewayConfig::cache_scope）；旧版本的条目为空
+  #[serde(default)]
+  pub scope: String,
+  pub attachment_id: String,
+  pub variant: String,
+  pub total_size: u64,
+  pub headers: StoredHeaders,
+  // 已落盘的字节区间（左闭右开，升序且已合并）
+  pub ranges: Vec<(u64, u64)>,
+  // 完整后按内容 sha256 存入 blobs/；相同内容的附件共享同一份文件
+  pub blob: Option<String>,
+  pub last_access: u64,
+  // 本次运行内的条目代号：条目被重置或删除后再建会换新代号，旧写入者记下的区间一律作废
+  #[serde(skip)]
+  pub generation: u64,
+}
+
+impl CacheEntry {
+  pub fn is_complete(&self) -> bool {
+    self.blob.is_some()
+  }
+
+  pub fn covers(&self, start: u64, end: u64) -> bool {
+    self.ranges.iter().any(|(a, b)| *a <= start && end <= *b)
+  }
+
+
+  fn first_missing(&self) -> Option<(u64, u64)> {
+    let mut cursor = 0u64;
+    for (a, b) in self.ranges.iter() {
+      if *a > cursor {
+        return Some((cursor, *a));
+      }
+      cursor = cursor.max(*b);
+    }
+    if cursor < self.total_size {
+      Some((cursor, self.total_size))
+    } else {
+      None
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheIndex {
+  version: u32,
+  cap_bytes: u64,
+  entries: HashMap<String, CacheEntry>,
+}
+
+impl Default for CacheIndex {
+  fn default() -> Self {
+    Self {
+      version: 1,
+      cap_bytes: DEFAULT_CAP_BYTES,
+      entries: HashMap::new(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentCacheInfo {
+  pub dir: String,
+  pub cap_bytes: u64,
+  pub used_bytes: u64,
+  pub entries: usize,
+  pub complete_entries: usize,
+  pub filling: usize,
+}
+
+pub enum RangeRequest {
+  Full,
+  // 左闭右开
+  Partial(u64, u64),
+  Unsatisfiable,
+}
+
+/// 只处理单区间；多区间或格式不对时按 RFC 7233 忽略 Range，返回完整内容
+pub fn parse_range(header: Option<&str>, total: u64) -> RangeRequest {
+  let raw = match header {
+    Some(v) => v.trim(),
+    None => return RangeRequest::Full,
+  };
+  let spec = match raw.strip_prefix("bytes=") {
+    Some(s) if !s.contains(',') => s.trim(),
+    _ => return RangeRequest::Full,
+  };
+  let (start_raw, end_raw) = match spec.split_once('-') {
+    Some(v) => v,
+    None => return RangeRequest::Full,
+  };
+
+  if start_raw.is_empty() {
+    // bytes=-N：最后 N 个字节
+    let n = match end_raw.parse::<u64>() {
+      Ok(n) => n,
+      Err(_) => return RangeRequest::Full,
+    };
+    if n == 0 || total == 0 {
+      return RangeRequest::Unsatisfiable;
+    }
+    return RangeRequest::Partial(total.saturating_sub(n), total);
+  }
+
+  let start = match start_raw.parse::<u64>() {
+    Ok(n) => n,
+    Err(_) => return RangeRequest::Full,
+  };
+  if start >= total {
+    return RangeRequest::Unsatisfiable;
+  }
+  let end = if end_raw.is_empty() {
+    total
+  } else {
+    match end_raw.parse::<u64>() {
+      Ok(n) if n >= start => (n + 1).min(total),
+      _ => return RangeRequest::Full,
+    }
+  };
+  RangeRequest::Partial(start, end)
+}
+
+/// 解析 `Content-Range: bytes a-b/total`，返回 (a, total)
+pub fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+  let rest = value.trim().strip_prefix("bytes ")?;
+  let (range, total) = rest.split_once('/')?;
+  let (start, _end) = range.split_once('-')?;
+  Some((start.trim().parse().ok()?, total.trim().parse().ok()?))
+}
+
+fn merge_range(ranges: &mut Vec<(u64, u64)>, start: u64, end: u64) {
+  if end <= start {
+    return;
+  }
+  ranges.push((start, end));
+  ranges.sort_by_key(|r| r.0);
+  let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+  for (a, b) in ranges.drain(..) {
+    if let Some(last) = merged.last_mut() {
+      if a <= last.1 {
+        last.1 = last.1.max(b);
+        continue;
+      }
+    }
+    merged.push((a, b));
+  }
+  *ranges = merged;
+}
+
+fn hash_file(path: &Path) -> Result<String, String> {
+  let mut file = fs::File::open(path).map_err(|e| format!("open partial failed: {e}"))?;
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0u8; 256 * 1024];
+  loop {
+    let n = file
+      .read(&mut buf)
+      .map_err(|e| format!("read partial failed: {e}"))?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buf[..n]);
+  }
+  Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 附件/缩略图的本地磁盘缓存：按内容寻址、按最近访问时间淘汰、支持部分缓存。
+pub struct AttachmentCache {
+  root: PathBuf,
+  index: Mutex<CacheIndex>,
+  filling: Mutex<HashSet<String>>,
+  next_generation: AtomicU64,
+}
+
+impl AttachmentCache {
+  pub fn open(root: PathBuf) -> Self {
+    let index = fs::read_to_string(root.join("index.json"))
+      .ok()
+      .and_then(|raw| serde_json::from_str::<CacheIndex>(&raw).ok())
+      .unwrap_or_default();
+
+    let cache = Self {
+      root,
+      index: Mutex::new(index),
+      filling: Mutex::new(HashSet::new()),
+      next_generation: AtomicU64::new(1),
+    };
+    // 旧版本的条目不知道属于哪个账户：丢弃
+    cache.remove_where(|entry| entry.scope.is_empty());
+    cache
+  }
+
+  /// scope 为账户范围（见 GatewayConfig::cache_scope），不同账户的同一附件互不共用条目。
+  pub fn key_for(scope: &str, attachment_id: &str, variant: &str) -> String {
+    let raw = format!("{}|{}|{}", scope, attachment_id.trim(), variant);
+    Sha256::digest(raw.as_bytes())
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect()
+  }
+
+  fn partial_path(&self, key: &str) -> PathBuf {
+    self.root.join("partial").join(key)
+  }
+
+  fn blob_path(&self, hash: &str) -> PathBuf {
+    self.root.join("blobs").join(hash)
+  }
+
+  pub fn data_path(&self, key: &str, entry: &CacheEntry) -> PathBuf {
+    match &entry.blob {
+      Some(hash) => self.blob_path(hash),
+      None => self.partial_path(key),
+    }
+  }
+
+  fn save_index(&self, index: &CacheIndex) {
+    let result = (|| -> Result<(), String> {
+      fs::create_dir_all(&self.root).map_err(|e| format!("create cache dir failed: {e}"))?;
+      let raw = serde_json::to_vec(index).map_err(|e| format!("serialize index failed: {e}"))?;
+      let tmp = self.root.join("index.json.tmp");
+      fs::write(&tmp, raw).map_err(|e| format!("write index failed: {e}"))?;
+      fs::rename(&tmp, self.root.join("index.json")).map_err(|e| format!("rename index failed: {e}"))
+    })();
+    if let Err(e) = result {
+      log::warn!("[attachment-cache] {}", e);
+    }
+  }
+
+  /// 读取并刷新访问时间（LRU）；不属于该账户或完整条目的 blob 丢失时视为未命中
+  pub fn lookup(&self, key: &str, scope: &str) -> Option<CacheEntry> {
+    let mut index = self.index.lock().ok()?;
+    let entry = index.entries.get_mut(key)?;
+    if entry.scope != scope {
+      return None;
+    }
+    if let Some(hash) = &entry.blob {
+      if !self.blob_path(hash).is_file() {
+        index.entries.remove(key);
+        return None;
+      }
+    }
+    entry.last_access = now_secs();
+    Some(entry.clone())
+  }
+
+  /// 准备写入一段上游数据；返回写入者要带上的条目代号，None 表示不缓存（超出容量等）。
+  /// 上游内容变化（ETag/大小不一致）时丢弃旧的部分缓存。
+  /// 新条目按完整大小预留空间，不够时先淘汰旧条目，保证写入过程中不会突破容量。
+  pub fn begin_write(
+    &self,
+    key: &str,
+    scope: &str,
+    attachment_id: &str,
+    variant: &str,
+    total_size: u64,
+    headers: StoredHeaders,
+  ) -> Option<u64> {
+    let busy = self.filling.lock().map(|s| s.clone()).unwrap_or_default();
+    let mut index = self.index.lock().ok()?;
+    if total_size == 0 || total_size > index.cap_bytes {
+      return None;
+    }
+
+    let stale = match index.entries.get(key) {
+      Some(existing) => {
+        existing.total_size != total_size
+          || (existing.headers.etag.is_some()
+            && headers.etag.is_some()
+            && existing.headers.etag != headers.etag)
+      }
+      None => false,
+    };
+
+    if stale {
+      index.entries.remove(key);
+      let _ = fs::remove_file(self.partial_path(key));
+    }
+
+    if !index.entries.contains_key(key) {
+      let cap = index.cap_bytes;
+      self.evict_locked(&mut index, &busy, cap.saturating_sub(total_size));
+      if Self::used_bytes(&index) + total_size > cap {
+        self.save_index(&index);
+        return None;
+      }
+    }
+
+    let entry = index.entries.entry(key.to_string()).or_insert_with(|| CacheEntry {
+      scope: scope.to_string(),
+      attachment_id: attachment_id.to_string(),
+      variant: variant.to_string(),
+      total_size,
+      headers: StoredHeaders::default(),
+      ranges: Vec::new(),
+      blob: None,
+      last_access: now_secs(),
+      generation: 0,
+    });
+    // 从索引文件读入的条目也在第一次写入时领取代号
+    if entry.generation == 0 {
+      entry.generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+    }
+    entry.headers = headers;
+    entry.last_access = now_secs();
+    (!entry.is_complete()).then_some(entry.generation)
+  }
+
+  /// 记录已落盘的区间；条目已被重置或删除（代号不符）时忽略，旧写入者写的是已删除的文件。
+  fn record_range(&self, key: &str, generation: u64, start: u64, end: u64) {
+    if let Ok(mut index) = self.index.lock() {
+      if let Some(entry) = index.entries.get_mut(key) {
+        if entry.generation == generation && entry.blob.is_none() {
+          merge_range(&mut entry.ranges, start, end.min(entry.total_size));
+        }
+      }
+    }
+  }
+
+  async fn open_partial(&self, key: &str, offset: u64) -> Option<tokio::fs::File> {
+    let path = self.partial_path(key);
+    if let Some(parent) = path.parent() {
+      tokio::fs::create_dir_all(parent).await.ok()?;
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(&path)
+      .await
+      .ok()?;
+    file.seek(std::io::SeekFrom::Start(offset)).await.ok()?;
+    Some(file)
+  }
+
+  /// 边转发边写入部分缓存文件（从 offset 开始）。客户端断开就停止写入。
+  /// 只有顺序读取（从已缓存的开头接着读且读完）才交给后台补齐剩余部分；
+  /// 视频拖动产生的零散区间只缓存读到的部分，不会触发整文件下载。
+  pub fn tee<S>(
+    self: &Arc<Self>,
+    key: String,
+    generation: u64,
+    offset: u64,
+    upstream: S,
+    filler: Option<Filler>,
+  ) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send + 'static
+  where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + Unpin + 'static,
+  {
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(16);
+    let cache = self.clone();
+
+    tokio::spawn(async move {
+      let mut upstream = upstream;
+      let mut file = cache.open_partial(&key, offset).await;
+      let mut pos = offset;
+      let mut recorded = offset;
+      let mut finished = true;
+
+      while let Some(chunk) = upstream.next().await {
+        match chunk {
+          Ok(bytes) => {
+            if let Some(f) = file.as_mut() {
+              if f.write_all(&bytes).await.is_err() {
+                file = None;
+              } else {
+                pos += bytes.len() as u64;
+                if pos - recorded >= RECORD_EVERY_BYTES && f.flush().await.is_ok() {
+                  cache.record_range(&key, generation, recorded, pos);
+                  recorded = pos;
+                }
+              }
+            }
+
+            if tx.send(Ok(bytes)).await.is_err() {
+              finished = false;
+              break;
+            }
+          }
+          Err(e) => {
+            let _ = tx.send(Err(std::io::Error::other(e))).await;
+            finished = false;
+            break;
+          }
+        }
+      }
+
+      if let Some(mut f) = file {
+        if f.flush().await.is_ok() && pos > recorded {
+          cache.record_range(&key, generation, recorded, pos);
+        }
+      }
+
+      cache.after_write(&key, generation, offset, finished, filler).await;
+    });
+
+    futures_util::stream::unfold(rx, |mut rx| async move {
+      rx.recv().await.map(|item| (item, rx))
+    })
+  }
+
+  async fn after_write(
+    self: &Arc<Self>,
+    key: &str,
+    generation: u64,
+    offset: u64,
+    finished: bool,
+    filler: Option<Filler>,
+  ) {
+    let (complete, sequential, filling) = {
+      let (complete, sequential) = match self.index.lock() {
+        Ok(index) => match index.entries.get(key) {
+          Some(e) if e.generation == generation => (
+            Some(e.blob.is_none() && e.first_missing().is_none()),
+            finished && e.ranges.first().is_some_and(|(a, b)| *a == 0 && *b > offset),
+          ),
+          // 条目已删除或已被新写入者接管
+          _ => (None, false),
+        },
+        Err(_) => (None, false),
+      };
+      let filling = self
+        .filling
+        .lock()
+        .map(|set| set.contains(key))
+        .unwrap_or(false);
+      (complete, sequential, filling)
+    };
+
+    match complete {
+      // 后台补齐任务会在结束时自行收尾，这里不抢
+      _ if filling => self.flush_index(),
+      Some(true) => {
+        if let Err(e) = self.finalize(key, generation).await {
+          log::warn!("[attachment-cache] finalize failed: {}", e);
+        }
+      }
+      Some(false) => {
+        self.flush_index();
+        if let (Some(filler), true) = (filler, sequential) {
+          self.spawn_fill(key.to_string(), generation, filler);
+        }
+      }
+      None => {}
+    }
+  }
+
+  fn spawn_fill(self: &Arc<Self>, key: String, generation: u64, filler: Filler) {
+    match self.filling.lock() {
+      Ok(mut set) => {
+        if set.len() >= MAX_CONCURRENT_FILLS || !set.insert(key.clone()) {
+          return;
+        }
+      }
+      Err(_) => return,
+    }
+
+    let cache = self.clone();
+    tokio::spawn(async move {
+      if let Err(e) = cache.fill(&key, generation, &filler).await {
+        log::warn!("[attachment-cache] background fill stopped: {}", e);
+        cache.flush_index();
+      }
+      if let Ok(mut set) = cache.filling.lock() {
+        set.remove(&key);
+      }
+    });
+  }
+
+  async fn fill(&self, key: &str, generation: u64, filler: &Filler) -> Result<(), String> {
+    loop {
+      let missing = {
+        let index = self.index.lock().map_err(|_| "cache index poisoned".to_string())?;
+        match index.entries.get(key) {
+          Some(entry) if entry.generation == generation && entry.blob.is_none() => entry.first_missing(),
+          _ => return Ok(()),
+        }
+      };
+
+      let (start, end) = match missing {
+        Some((a, b)) => (a, b.min(a + FILL_CHUNK_BYTES)),
+        None => break,
+      };
+
+      let resp = filler(Some((start, end - 1)))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+      // 服务端不支持 Range 时会回完整文件（200），从 0 开始写即可
+      let offset = match resp.status().as_u16() {
+        206 => resp
+          .headers()
+          .get(reqwest::header::CONTENT_RANGE)
+          .and_then(|v| v.to_str().ok())
+          .and_then(parse_content_range)
+          .map(|(a, _total)| a)
+          .ok_or_else(|| "missing content-range".to_string())?,
+        200 => 0,
+        code => return Err(format!("upstream returned {code}")),
+      };
+
+      let mut file = self
+        .open_partial(key, offset)
+        .await
+        .ok_or_else(|| "open partial failed".to_string())?;
+      let mut stream = resp.bytes_stream();
+      let mut pos = offset;
+      let mut recorded = offset;
+      while let Some(chunk) = stream.next().await {
+        let bytes = chunk.map_err(|e| e.to_string())?;
+        file
+          .write_all(&bytes)
+          .await
+          .map_err(|e| format!("write partial failed: {e}"))?;
+        pos += bytes.len() as u64;
+        if pos - recorded >= RECORD_EVERY_BYTES {
+          file.flush().await.map_err(|e| format!("flush partial failed: {e}"))?;
+          self.record_range(key, generation, recorded, pos);
+          recorded = pos;
+        }
+      }
+      file.flush().await.map_err(|e| format!("flush partial failed: {e}"))?;
+      self.record_range(key, generation, recorded, pos);
+
+      if pos == offset {
+        return Err("upstream returned empty body".to_string());
+      }
+    }
+
+    self.finalize(key, generation).await
+  }
+
+  /// 部分缓存已覆盖完整文件：计算内容哈希并移入 blobs/
+  async fn finalize(&self, key: &str, generation: u64) -> Result<(), String> {
+    let partial = self.partial_path(key);
+    let to_hash = partial.clone();
+    let hash = tokio::task::spawn_blocking(move || hash_file(&to_hash))
+      .await
+      .map_err(|e| format!("hash join failed: {e}"))??;
+
+    // 移动与改索引都在锁内完成：哈希期间条目被重置或删除（代号不符）时，
+    // 同名部分文件已归新写入者所有，不能动它
+    {
+      let mut index = self.index.lock().map_err(|_| "cache index poisoned".to_string())?;
+      match index.entries.get_mut(key) {
+        Some(entry) if entry.generation == generation && entry.blob.is_none() => {
+          let blob = self.blob_path(&hash);
+          if let Some(parent) = blob.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("create blobs dir failed: {e}"))?;
+          }
+          if blob.is_file() {
+            let _ = fs::remove_file(&partial);
+          } else {
+            fs::rename(&partial, &blob).map_err(|e| format!("move blob failed: {e}"))?;
+          }
+          entry.ranges = vec![(0, entry.total_size)];
+          entry.blob = Some(hash);
+        }
+        _ => return Ok(()),
+      }
+    }
+    self.enforce_cap();
+    Ok(())
+  }
+
+  /// 占用空间：完整条目按 blob 去重计算；部分缓存按完整大小预留（稀疏写入、后台补齐都不会超出）。
+  fn used_bytes(index: &CacheIndex) -> u64 {
+    let mut blobs: HashSet<&str> = HashSet::new();
+    let mut total = 0u64;
+    for entry in index.entries.values() {
+      match &entry.blob {
+        Some(hash) => {
+          if blobs.insert(hash.as_str()) {
+            total += entry.total_size;
+          }
+        }
+        None => total += entry.total_size,
+      }
+    }
+    total
+  }
+
+  /// 删除条目及其文件；blob 仍被其他条目引用时保留。
+  fn remove_entry_locked(&self, index: &mut CacheIndex, key: &str) {
+    if let Some(entry) = index.entries.remove(key) {
+      match entry.blob {
+        Some(hash) => {
+          let shared = index.entries.values().any(|e| e.blob.as_deref() == Some(hash.as_str()));
+          if !shared {
+            let _ = fs::remove_file(self.blob_path(&hash));
+          }
+        }
+        None => {
+          let _ = fs::remove_file(self.partial_path(key));
+        }
+      }
+    }
+  }
+
+  /// 按最近访问时间从旧到新淘汰，直到占用不超过 target；正在补齐的条目跳过
+  fn evict_locked(&self, index: &mut CacheIndex, busy: &HashSet<String>, target: u64) {
+    let mut order: Vec<(String, u64)> = index
+      .entries
+      .iter()
+      .filter(|(k, _)| !busy.contains(*k))
+      .map(|(k, e)| (k.clone(), e.last_access))
+      .collect();
+    order.sort_by_key(|(_, t)| *t);
+
+    for (key, _) in order {
+      if Self::used_bytes(index) <= target {
+        break;
+      }
+      self.remove_entry_locked(index, &key);
+    }
+  }
+
+  fn enforce_cap(&self) {
+    let busy = self.filling.lock().map(|s| s.clone()).unwrap_or_default();
+    let mut index = match self.index.lock() {
+      Ok(v) => v,
+      Err(_) => return,
+    };
+    let cap = index.cap_bytes;
+    self.evict_locked(&mut index, &busy, cap);
+    self.save_index(&index);
+  }
+
+  fn remove_where(&self, pred: impl Fn(&CacheEntry) -> bool) {
+    let mut index = match self.index.lock() {
+      Ok(v) => v,
+      Err(_) => return,
+    };
+    let keys: Vec<String> = index
+      .entries
+      .iter()
+      .filter(|(_, e)| pred(e))
+      .map(|(k, _)| k.clone())
+      .collect();
+    if keys.is_empty() {
+      return;
+    }
+    for key in keys {
+      self.remove_entry_locked(&mut index, &key);
+    }
+    self.save_index(&index);
+  }
+
+  fn flush_index(&self) {
+    if let Ok(index) = self.index.lock() {
+      self.save_index(&index);
+    }
+  }
+
+  pub fn info(&self) -> AttachmentCacheInfo {
+    let filling = self.filling.lock().map(|s| s.len()).unwrap_or(0);
+    match self.index.lock() {
+      Ok(index) => AttachmentCacheInfo {
+        dir: self.root.to_string_lossy().to_string(),
+        cap_bytes: index.cap_bytes,
+        used_bytes: Self::used_bytes(&index),
+        entries: index.entries.len(),
+        complete_entries: index.entries.values().filter(|e| e.is_complete()).count(),
+        filling,
+      },
+      Err(_) => AttachmentCacheInfo {
+        dir: self.root.to_string_lossy().to_string(),
+        cap_bytes: 0,
+        used_bytes: 0,
+        entries: 0,
+        complete_entries: 0,
+        filling,
+      },
+    }
+  }
+
+  pub fn set_cap(&self, cap_bytes: u64) {
+    if let Ok(mut index) = self.index.lock() {
+      index.cap_bytes = cap_bytes;
+    }
+    self.enforce_cap();
+  }
+
+  /// 删除某个账户的全部条目（退出登录时调用）。
+  pub fn purge_scope(&self, scope: &str) {
+    self.remove_where(|entry| entry.scope == scope);
+  }
+
+  /// 清空全部缓存；正在写入的流会在下一次登记时发现条目已不存在并自然放弃
+  pub fn purge(&self) -> Result<(), String> {
+    if let Ok(mut index) = self.index.lock() {
+      index.entries.clear();
+      self.save_index(&index);
+    }
+    for sub in ["blobs", "partial"] {
+      let dir = self.root.join(sub);
+      if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("remove {sub} failed: {e}"))?;
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(total_size: u64, ranges: Vec<(u64, u64)>) -> CacheEntry {
+    CacheEntry {
+      scope: "scope".to_string(),
+      attachment_id: "id".to_string(),
+      variant: String::new(),
+      total_size,
+      headers: StoredHeaders::default(),
+      ranges,
+      blob: None,
+      last_access: 0,
+      generation: 0,
+    }
+  }
+
+  #[test]
+  fn parse_range_single_ranges() {
+    assert!(matches!(parse_range(None, 1000), RangeRequest::Full));
+    assert!(matches!(parse_range(Some("bytes=0-99"), 1000), RangeRequest::Partial(0, 100)));
+    assert!(matches!(parse_range(Some(" bytes=500- "), 1000), RangeRequest::Partial(500, 1000)));
+    // 结束位置超出文件时截断到末尾
+    assert!(matches!(parse_range(Some("bytes=900-5000"), 1000), RangeRequest::Partial(900, 1000)));
+  }
+
+  #[test]
+  fn parse_range_suffix() {
+    assert!(matches!(parse_range(Some("bytes=-100"), 1000), RangeRequest::Partial(900, 1000)));
+    assert!(matches!(parse_range(Some("bytes=-5000"), 1000), RangeRequest::Partial(0, 1000)));
+    assert!(matches!(parse_range(Some("bytes=-0"), 1000), RangeRequest::Unsatisfiable));
+    assert!(matches!(parse_range(Some("bytes=-10"), 0), RangeRequest::Unsatisfiable));
+  }
+
+  #[test]
+  fn parse_range_unsatisfiable_or_ignored() {
+    assert!(matches!(parse_range(Some("bytes=1000-"), 1000), RangeRequest::Unsatisfiable));
+    // 多区间、非 bytes 单位、格式错误：忽略 Range
+    assert!(matches!(parse_range(Some("bytes=0-1,5-6"), 1000), RangeRequest::Full));
+    assert!(matches!(parse_range(Some("items=0-1"), 1000), RangeRequest::Full));
+    assert!(matches!(parse_range(Some("bytes=5-2"), 1000), RangeRequest::Full));
+    assert!(matches!(parse_range(Some("bytes=abc"), 1000), RangeRequest::Full));
+    assert!(matches!(parse_range(Some("bytes=x-10"), 1000), RangeRequest::Full));
+  }
+
+  #[test]
+  fn parse_content_range_values() {
+    assert_eq!(parse_content_range("bytes 0-99/1000"), Some((0, 1000)));
+    assert_eq!(parse_content_range(" bytes 200-299/300 "), Some((200, 300)));
+    assert_eq!(parse_content_range("bytes 0-99/*"), None);
+    assert_eq!(parse_content_range("bytes */1000"), None);
+    assert_eq!(parse_content_range("0-99/1000"), None);
+  }
+
+  #[test]
+  fn merge_range_keeps_sorted_disjoint_ranges() {
+    let mut ranges = Vec::new();
+    merge_range(&mut ranges, 10, 20);
+    merge_range(&mut ranges, 0, 5);
+    assert_eq!(ranges, vec![(0, 5), (10, 20)]);
+
+    // 相邻区间合并
+    merge_range(&mut ranges, 5, 10);
+    assert_eq!(ranges, vec![(0, 20)]);
+
+    merge_range(&mut ranges, 30, 40);
+    merge_range(&mut ranges, 15, 35);
+    assert_eq!(ranges, vec![(0, 40)]);
+
+    // 空区间不记录
+    merge_range(&mut ranges, 50, 50);
+    merge_range(&mut ranges, 60, 55);
+    assert_eq!(ranges, vec![(0, 40)]);
+  }
+
+  #[test]
+  fn first_missing_finds_gaps() {
+    assert_eq!(entry(100, vec![]).first_missing(), Some((0, 100)));
+    assert_eq!(entry(100, vec![(0, 40), (60, 100)]).first_missing(), Some((40, 60)));
+    assert_eq!(entry(100, vec![(20, 100)]).first_missing(), Some((0, 20)));
+    assert_eq!(entry(100, vec![(0, 70)]).first_missing(), Some((70, 100)));
+    assert_eq!(entry(100, vec![(0, 100)]).first_missing(), None);
+  }
+
+  #[test]
+  fn covers_requires_a_single_range() {
+    let e = entry(100, vec![(0, 40), (60, 100)]);
+    assert!(e.covers(0, 40));
+    assert!(e.covers(70, 100));
+    assert!(!e.covers(30, 70));
+  }
+
+  #[test]
+  fn record_range_ignores_replaced_writers() {
+    let root = std::env::temp_dir().join(format!("pdh-attachment-cache-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let cache = AttachmentCache::open(root.clone());
+    let key = AttachmentCache::key_for("scope", "id", "");
+    let headers = |etag: &str| StoredHeaders {
+      etag: Some(etag.to_string()),
+      ..StoredHeaders::default()
+    };
+
+    let old = cache.begin_write(&key, "scope", "id", "", 100, headers("a")).unwrap();
+    cache.record_range(&key, old, 0, 50);
+    // 上游内容变了：条目被重置，旧写入者之后记的区间不能算到新内容头上
+    let new = cache.begin_write(&key, "scope", "id", "", 100, headers("b")).unwrap();
+    assert_ne!(old, new);
+    cache.record_range(&key, old, 50, 100);
+    cache.record_range(&key, new, 0, 10);
+
+    let ranges = cache.index.lock().unwrap().entries[&key].ranges.clone();
+    assert_eq!(ranges, vec![(0, 10)]);
+    let _ = fs::remove_dir_all(&root);
+  }
+}
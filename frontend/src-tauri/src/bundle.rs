@@ -0,0 +1,260 @@
+use std::{
+  fs,
+  io::Write as _,
+  path::Path,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{write::DeflateEncoder, Compression, Crc};
+
+use crate::error::{PdhError, PdhResult};
+use crate::logging;
+
+/// 最小的 zip 写入器：只支持 deflate、无 zip64。超出经典 zip 的上限（单个大小/偏移 4GB、
+/// 65535 个条目、文件名 64KB）时返回错误，而不是写出损坏的包。
+struct ZipWriter {
+  buf: Vec<u8>,
+  central: Vec<u8>,
+  entries: u16,
+  dos_time: u16,
+  dos_date: u16,
+}
+
+impl ZipWriter {
+  fn new() -> Self {
+    let (dos_date, dos_time) = dos_datetime(SystemTime::now());
+    Self {
+      buf: Vec::new(),
+      central: Vec::new(),
+      entries: 0,
+      dos_time,
+      dos_date,
+    }
+  }
+
+  fn add(&mut self, name: &str, data: &[u8]) -> PdhResult<()> {
+    let mut crc = Crc::new();
+    crc.update(data);
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    let compressed = encoder.finish()?;
+
+    if self.entries == u16::MAX {
+      return Err(PdhError::internal("diagnostics bundle has too many entries"));
+    }
+    let offset = zip_u32(self.buf.len())?;
+    let compressed_len = zip_u32(compressed.len())?;
+    let data_len = zip_u32(data.len())?;
+    let name = name.as_bytes();
+    let name_len = u16::try_from(name.len()).map_err(|_| PdhError::internal("zip entry name too long"))?;
+
+    // local file header；flag 0x0800 表示文件名为 UTF-8
+    self.buf.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+    self.buf.extend_from_slice(&20u16.to_le_bytes());
+    self.buf.extend_from_slice(&0x0800u16.to_le_bytes());
+    self.buf.extend_from_slice(&8u16.to_le_bytes());
+    self.buf.extend_from_slice(&self.dos_time.to_le_bytes());
+    self.buf.extend_from_slice(&self.dos_date.to_le_bytes());
+    self.buf.extend_from_slice(&crc.sum().to_le_bytes());
+    self.buf.extend_from_slice(&compressed_len.to_le_bytes());
+    self.buf.extend_from_slice(&data_len.to_le_bytes());
+    self.buf.extend_from_slice(&name_len.to_le_bytes());
+    self.buf.extend_from_slice(&0u16.to_le_bytes());
+    self.buf.extend_from_slice(name);
+    self.buf.extend_from_slice(&compressed);
+
+    // central directory entry
+    self.central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+    self.central.extend_from_slice(&20u16.to_le_bytes());
+    self.central.extend_from_slice(&20u16.to_le_bytes());
+    self.central.extend_from_slice(&0x0800u16.to_le_bytes());
+    self.central.extend_from_slice(&8u16.to_le_bytes());
+    self.central.extend_from_slice(&self.dos_time.to_le_bytes());
+    self.central.extend_from_slice(&self.dos_date.to_le_bytes());
+    self.central.extend_from_slice(&crc.sum().to_le_bytes());
+    self.central.extend_from_slice(&compressed_len.to_le_bytes());
+    self.central.extend_from_slice(&data_len.to_le_bytes());
+    self.central.extend_from_slice(&name_len.to_le_bytes());
+    // extra / comment / disk / internal attr
+    self.central.extend_from_slice(&[0u8; 8]);
+    // external attr
+    self.central.extend_from_slice(&0u32.to_le_bytes());
+    self.central.extend_from_slice(&offset.to_le_bytes());
+    self.central.extend_from_slice(name);
+
+    self.entries += 1;
+    Ok(())
+  }
+
+  fn finish(mut self) -> PdhResult<Vec<u8>> {
+    let central_offset = zip_u32(self.buf.len())?;
+    let central_size = zip_u32(self.central.len())?;
+    self.buf.extend_from_slice(&self.central);
+    self.buf.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    self.buf.extend_from_slice(&[0u8; 4]);
+    self.buf.extend_from_slice(&self.entries.to_le_bytes());
+    self.buf.extend_from_slice(&self.entries.to_le_bytes());
+    self.buf.extend_from_slice(&central_size.to_le_bytes());
+    self.buf.extend_from_slice(&central_offset.to_le_bytes());
+    self.buf.extend_from_slice(&0u16.to_le_bytes());
+    Ok(self.buf)
+  }
+}
+
+fn zip_u32(len: usize) -> PdhResult<u32> {
+  u32::try_from(len).map_err(|_| PdhError::internal("diagnostics bundle too large (zip64 not supported)"))
+}
+
+/// zip 使用 MS-DOS 时间格式（UTC，2 秒精度）。
+fn dos_datetime(time: SystemTime) -> (u16, u16) {
+  let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+  let days = (secs / 86_400) as i64;
+  let rem = secs % 86_400;
+
+  // days since 1970-01-01 -> civil date（Howard Hinnant 的算法）
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z - era * 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  let year = year.clamp(1980, 2107);
+  let date = (((year - 1980) as u16) << 9) | ((month as u16) << 5) | day as u16;
+  let time = (((rem / 3600) as u16) << 11) | (((rem % 3600 / 60) as u16) << 5) | ((rem % 60 / 2) as u16);
+  (date, time)
+}
+
+pub struct BundleInput {
+  pub logs_dir: Option<std::path::PathBuf>,
+  pub settings: serde_json::Value,
+  pub gateway: serde_json::Value,
+  pub version: serde_json::Value,
+}
+
+fn add_json(zip: &mut ZipWriter, name: &str, value: &serde_json::Value) -> PdhResult<()> {
+  let raw = serde_json::to_vec_pretty(value).map_err(|e| PdhError::internal(format!("serialize {name} failed: {e}")))?;
+  zip.add(name, &raw)
+}
+
+fn add_logs(zip: &mut ZipWriter, dir: &Path) -> PdhResult<()> {
+  let Ok(read_dir) = fs::read_dir(dir) else {
+    return Ok(());
+  };
+  let mut names: Vec<String> = read_dir
+    .filter_map(|e| e.ok())
+    .filter(|e| e.path().is_file())
+    .filter_map(|e| e.file_name().to_str().map(str::to_string))
+    .collect();
+  names.sort();
+
+  for name in names {
+    let folder = if name.starts_with(logging::CRASH_FILE_PREFIX) {
+      "crashes"
+    } else if name.ends_with(".log") {
+      "logs"
+    } else {
+      continue;
+    };
+    // 写入时已脱敏；旧版本留下的日志可能没有，这里再过一遍
+    let raw = fs::read(dir.join(&name))?;
+    let text = logging::redact(&String::from_utf8_lossy(&raw));
+    zip.add(&format!("{folder}/{name}"), text.as_bytes())?;
+  }
+  Ok(())
+}
+
+/// 打包诊断信息：日志与崩溃报告、脱敏后的设置、网关状态、版本信息。
+pub fn write_diagnostics_bundle(target: &Path, mut input: BundleInput) -> PdhResult<()> {
+  let mut zip = ZipWriter::new();
+
+  logging::redact_json(&mut input.settings);
+  logging::redact_json(&mut input.gateway);
+
+  add_json(&mut zip, "version.json", &input.version)?;
+  add_json(&mut zip, "settings.json", &input.settings)?;
+  add_json(&mut zip, "gateway.json", &input.gateway)?;
+  if let Some(dir) = &input.logs_dir {
+    add_logs(&mut zip, dir)?;
+  }
+
+  let bytes = zip.finish()?;
+  fs::write(target, bytes).map_err(|e| PdhError::io(format!("write diagnostics bundle failed: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Read as _;
+  use std::time::Duration;
+
+  fn u16_at(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+  }
+
+  fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+  }
+
+  #[test]
+  fn dos_datetime_encodes_utc_fields() {
+    // 2024-02-29 13:45:58 UTC
+    let (date, time) = dos_datetime(UNIX_EPOCH + Duration::from_secs(1_709_214_358));
+    assert_eq!(date, ((2024 - 1980) << 9) | (2 << 5) | 29);
+    assert_eq!(time, (13 << 11) | (45 << 5) | 29);
+  }
+
+  #[test]
+  fn dos_datetime_clamps_to_dos_epoch() {
+    // 1970 早于 DOS 纪元，年份截到 1980
+    let (date, time) = dos_datetime(UNIX_EPOCH);
+    assert_eq!(date, (1 << 5) | 1);
+    assert_eq!(time, 0);
+  }
+
+  #[test]
+  fn zip_layout_round_trips() {
+    let mut zip = ZipWriter::new();
+    zip.add("version.json", b"{\"version\":\"1.0.0\"}").unwrap();
+    zip.add("logs/日志.log", b"hello hello hello").unwrap();
+    let bytes = zip.finish().unwrap();
+
+    assert_eq!(u32_at(&bytes, 0), 0x0403_4b50);
+    let eocd = bytes.len() - 22;
+    assert_eq!(u32_at(&bytes, eocd), 0x0605_4b50);
+    assert_eq!(u16_at(&bytes, eocd + 8), 2);
+    assert_eq!(u16_at(&bytes, eocd + 10), 2);
+    let central_size = u32_at(&bytes, eocd + 12) as usize;
+    let central_offset = u32_at(&bytes, eocd + 16) as usize;
+    assert_eq!(central_offset + central_size, eocd);
+
+    // 第二个条目：从中央目录找到本地头，解压后与原文一致
+    let first_name_len = u16_at(&bytes, central_offset + 28) as usize;
+    let second = central_offset + 46 + first_name_len;
+    assert_eq!(u32_at(&bytes, second), 0x0201_4b50);
+    let name_len = u16_at(&bytes, second + 28) as usize;
+    assert_eq!(&bytes[second + 46..second + 46 + name_len], "logs/日志.log".as_bytes());
+    let compressed_len = u32_at(&bytes, second + 20) as usize;
+    let local = u32_at(&bytes, second + 42) as usize;
+    assert_eq!(u32_at(&bytes, local), 0x0403_4b50);
+    let data_start = local + 30 + u16_at(&bytes, local + 26) as usize;
+    let mut decoder = flate2::read::DeflateDecoder::new(&bytes[data_start..data_start + compressed_len]);
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out).unwrap();
+    assert_eq!(out, b"hello hello hello");
+
+    let mut crc = Crc::new();
+    crc.update(&out);
+    assert_eq!(u32_at(&bytes, second + 16), crc.sum());
+  }
+
+  #[test]
+  fn zip_rejects_too_many_entries() {
+    let mut zip = ZipWriter::new();
+    zip.entries = u16::MAX;
+    assert!(zip.add("one-more.txt", b"x").is_err());
+  }
+}
@@ -0,0 +1,309 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  future::Future,
+  sync::{Arc, Mutex, MutexGuard},
+};
+
+use axum::{
+  body::Body,
+  http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+  response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use tokio::sync::watch;
+
+use crate::inspector::UpstreamRequest;
+
+// 单次合并请求最多领先最慢的等待者这么多字节；超出时暂停读取上游（背压）
+const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+struct Head {
+  status: StatusCode,
+  headers: HeaderMap,
+  upstream: Option<UpstreamRequest>,
+}
+
+#[derive(Default)]
+struct FlightState {
+  head: Option<Head>,
+  // 尚未被所有等待者读走的分块；chunks[0] 的序号为 base
+  chunks: VecDeque<Bytes>,
+  base: usize,
+  buffered: usize,
+  // 每个等待者下一个要读的分块序号
+  cursors: HashMap<u64, usize>,
+  next_waiter: u64,
+  done: bool,
+  failed: bool,
+}
+
+impl FlightState {
+  /// 丢掉所有等待者都已读过的分块。
+  fn trim(&mut self) {
+    let min = self.cursors.values().copied().min().unwrap_or(self.base + self.chunks.len());
+    while self.base < min {
+      let Some(chunk) = self.chunks.pop_front() else {
+        break;
+      };
+      self.buffered -= chunk.len();
+      self.base += 1;
+    }
+  }
+}
+
+/// 一次正在进行的上游请求，结果按分块广播给所有等待者。
+struct Flight {
+  state: Mutex<FlightState>,
+  // 状态每次变化都递增版本号，等待方据此唤醒
+  version: watch::Sender<u64>,
+}
+
+impl Flight {
+  fn lock(&self) -> MutexGuard<'_, FlightState> {
+    match self.state.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+
+  fn notify(&self) {
+    self.version.send_modify(|v| *v = v.wrapping_add(1));
+  }
+
+  /// 还没有丢弃过任何分块时才能加入，保证新等待者拿到完整响应。
+  fn try_join(&self) -> Option<u64> {
+    let mut state = self.lock();
+    if state.base > 0 || state.failed {
+      return None;
+    }
+    let id = state.next_waiter;
+    state.next_waiter += 1;
+    state.cursors.insert(id, 0);
+    Some(id)
+  }
+
+  fn leave(&self, waiter: u64) {
+    let mut state = self.lock();
+    state.cursors.remove(&waiter);
+    state.trim();
+    drop(state);
+    self.notify();
+  }
+}
+
+/// 合并相同的并发 GET：同一个键只有一个上游请求在跑，它的流式响应分发给所有等待者。
+#[derive(Default)]
+pub struct RequestCoalescer {
+  flights: Mutex<HashMap<String, Arc<Flight>>>,
+}
+
+impl RequestCoalescer {
+  fn forget(&self, key: &str, flight: &Arc<Flight>) {
+    let mut flights = match self.flights.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    if flights.get(key).is_some_and(|f| Arc::ptr_eq(f, flight)) {
+      flights.remove(key);
+    }
+  }
+
+  /// 加入同键的进行中请求，或者由 `start` 发起一个新的。
+  /// `start` 在独立任务中运行：发起者的连接断开不会影响其他等待者。
+  pub async fn run<F, Fut>(self: &Arc<Self>, key: String, start: F) -> Response
+  where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Response> + Send + 'static,
+  {
+    let (flight, waiter, shared) = {
+      let mut flights = match self.flights.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+      };
+      let joined = flights
+        .get(&key)
+        .and_then(|flight| flight.try_join().map(|id| (flight.clone(), id)));
+      match joined {
+        Some((flight, id)) => (flight, id, true),
+        None => {
+          let flight = Arc::new(Flight {
+            state: Mutex::new(FlightState::default()),
+            version: watch::channel(0).0,
+          });
+          // 新建的 flight 一定可以加入
+          let id = flight.try_join().unwrap_or_default();
+          flights.insert(key.clone(), flight.clone());
+          (flight, id, false)
+        }
+      }
+    };
+
+    if !shared {
+      let coalescer = self.clone();
+      let pump_flight = flight.clone();
+      let fut = start();
+      tauri::async_runtime::spawn(crate::request_id::scope(
+        crate::request_id::current().unwrap_or_else(crate::request_id::generate),
+        async move {
+          pump(&pump_flight, fut).await;
+          coalescer.forget(&key, &pump_flight);
+        },
+      ));
+    }
+
+    respond(flight, waiter, shared).await
+  }
+}
+
+/// pump 无论以何种方式结束（包括处理函数 panic），都让等待者收到结束信号。
+struct Finish<'a>(&'a Flight);
+
+impl Drop for Finish<'_> {
+  fn drop(&mut self) {
+    let mut state = self.0.lock();
+    if !state.done {
+      state.failed = true;
+      state.done = true;
+    }
+    drop(state);
+    self.0.notify();
+  }
+}
+
+async fn pump<Fut: Future<Output = Response>>(flight: &Arc<Flight>, fut: Fut) {
+  let _finish = Finish(flight);
+  let mut rx = flight.version.subscribe();
+  let response = fut.await;
+  let (mut parts, body) = response.into_parts();
+  {
+    let mut state = flight.lock();
+    state.head = Some(Head {
+      status: parts.status,
+      headers: std::mem::take(&mut parts.headers),
+      upstream: parts.extensions.remove::<UpstreamRequest>(),
+    });
+  }
+  flight.notify();
+
+  let mut stream = body.into_data_stream();
+  loop {
+    // 背压：等最慢的等待者跟上；所有等待者都离开时放弃上游
+    loop {
+      {
+        let state = flight.lock();
+        if state.cursors.is_empty() {
+          return;
+        }
+        if state.buffered <= MAX_BUFFERED_BYTES {
+          break;
+        }
+      }
+      if rx.changed().await.is_err() {
+        return;
+      }
+    }
+
+    let next = stream.next().await;
+    let mut state = flight.lock();
+    match next {
+      Some(Ok(chunk)) => {
+        state.buffered += chunk.len();
+        state.chunks.push_back(chunk);
+      }
+      Some(Err(e)) => {
+        log::warn!("[gateway] {}shared response failed: {}", crate::request_id::log_prefix(), e);
+        state.failed = true;
+        state.done = true;
+      }
+      None => state.done = true,
+    }
+    let finished = state.done;
+    drop(state);
+    flight.notify();
+    if finished {
+      return;
+    }
+  }
+}
+
+/// 等待者离开（读完或连接断开）时释放游标。
+struct Cursor {
+  flight: Arc<Flight>,
+  waiter: u64,
+  rx: watch::Receiver<u64>,
+}
+
+impl Drop for Cursor {
+  fn drop(&mut self) {
+    self.flight.leave(self.waiter);
+  }
+}
+
+async fn respond(flight: Arc<Flight>, waiter: u64, shared: bool) -> Response {
+  let mut cursor = Cursor {
+    rx: flight.version.subscribe(),
+    flight,
+    waiter,
+  };
+
+  let head = loop {
+    {
+      let state = cursor.flight.lock();
+      if let Some(head) = &state.head {
+        break Some((head.status, head.headers.clone(), head.upstream.clone()));
+      }
+      if state.failed {
+        break None;
+      }
+    }
+    if cursor.rx.changed().await.is_err() {
+      break None;
+    }
+  };
+  let Some((status, headers, upstream)) = head else {
+    return crate::gateway_error::GatewayError::Internal("shared request aborted".to_string()).into_response();
+  };
+
+  // 上游中途失败时给出一个错误分块，让客户端知道响应不完整
+  let body = futures_util::stream::unfold(Some(cursor), |cursor| async move {
+    let mut cursor = cursor?;
+    loop {
+      {
+        let mut state = cursor.flight.lock();
+        let next = state.cursors.get(&cursor.waiter).copied().unwrap_or(state.base);
+        if next < state.base + state.chunks.len() {
+          let chunk = state.chunks[next - state.base].clone();
+          state.cursors.insert(cursor.waiter, next + 1);
+          state.trim();
+          drop(state);
+          cursor.flight.notify();
+          return Some((Ok(chunk), Some(cursor)));
+        }
+        if state.failed {
+          return Some((Err(std::io::Error::other("shared response failed")), None));
+        }
+        if state.done {
+          return None;
+        }
+      }
+      if cursor.rx.changed().await.is_err() {
+        return None;
+      }
+    }
+  });
+
+  let mut response = Response::new(Body::from_stream(body));
+  *response.status_mut() = status;
+  *response.headers_mut() = headers;
+  if let Some(upstream) = upstream {
+    response.extensions_mut().insert(upstream);
+  }
+  if shared {
+    response.headers_mut().insert(
+      HeaderName::from_static("x-pdh-coalesced"),
+      HeaderValue::from_static("shared"),
+    );
+  }
+  response
+}
@@ -0,0 +1,483 @@
+use std::{
+  net::SocketAddr,
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+
+use rustls::{
+  client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+  crypto::CryptoProvider,
+  pki_types::{CertificateDer, ServerName, UnixTime},
+  DigitallySignedStruct, SignatureScheme,
+};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+use crate::api_cache::now_secs;
+use crate::net::TlsSettings;
+
+const STEP_TIMEOUT: Duration = Duration::from_secs(8);
+// 时钟偏差超过这个值时 JWT 的签发/过期判断可能出错
+const CLOCK_SKEW_WARN_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+  Ok,
+  Warning,
+  Failed,
+  Skipped,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticStep {
+  pub name: &'static str,
+  pub status: StepStatus,
+  pub duration_ms: Option<u64>,
+  pub detail: serde_json::Value,
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateInfo {
+  pub subject: String,
+  pub issuer: String,
+  pub serial: String,
+  pub not_before: String,
+  pub not_after: String,
+  pub subject_alt_names: Vec<String>,
+  pub sha256: String,
+  // 可直接填入服务器配置的 SPKI pin
+  pub spki_pin: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticReport {
+  pub url: String,
+  pub generated_at: u64,
+  pub app_version: String,
+  pub ok: bool,
+  pub steps: Vec<DiagnosticStep>,
+  pub clock_skew_secs: Option<i64>,
+}
+
+impl DiagnosticReport {
+  fn push(&mut self, step: DiagnosticStep) {
+    if matches!(step.status, StepStatus::Failed) {
+      self.ok = false;
+    }
+    self.steps.push(step);
+  }
+
+  fn skip(&mut self, name: &'static str, reason: &str) {
+    self.push(DiagnosticStep {
+      name,
+      status: StepStatus::Skipped,
+      duration_ms: None,
+      detail: json!({ "reason": reason }),
+      error: None,
+    });
+  }
+}
+
+fn step(name: &'static str, started: Instant, result: Result<serde_json::Value, String>) -> DiagnosticStep {
+  let duration_ms = Some(started.elapsed().as_millis() as u64);
+  match result {
+    Ok(detail) => DiagnosticStep {
+      name,
+      status: StepStatus::Ok,
+      duration_ms,
+      detail,
+      error: None,
+    },
+    Err(e) => DiagnosticStep {
+      name,
+      status: StepStatus::Failed,
+      duration_ms,
+      detail: serde_json::Value::Null,
+      error: Some(e),
+    },
+  }
+}
+
+async fn with_timeout<T>(fut: impl std::future::Future<Output = Result<T, String>>) -> Result<T, String> {
+  tokio::time::timeout(STEP_TIMEOUT, fut)
+    .await
+    .map_err(|_| format!("timed out after {}s", STEP_TIMEOUT.as_secs()))?
+}
+
+/// 仅用于诊断：证书校验失败后再握手一次，把对方证书读出来给用户看；
+/// 这个连接不会发送任何数据。
+#[derive(Debug)]
+struct InspectOnlyVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for InspectOnlyVerifier {
+  fn verify_server_cert(
+    &self,
+    _end_entity: &CertificateDer<'_>,
+    _intermediates: &[CertificateDer<'_>],
+    _server_name: &ServerName<'_>,
+    _ocsp_response: &[u8],
+    _now: UnixTime,
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    Ok(ServerCertVerified::assertion())
+  }
+
+  fn verify_tls12_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self.0.signature_verification_algorithms.supported_schemes()
+  }
+}
+
+/// `tls` 为 None 时不校验证书（仅用于读取证书）；否则与网关使用同一套 TLS 设置。
+fn tls_config(tls: Option<&TlsOptions>) -> Result<rustls::ClientConfig, String> {
+  if let Some(opts) = tls {
+    return crate::net::rustls_config(&opts.settings, opts.client_key_pem.as_deref());
+  }
+
+  let provider = Arc::new(rustls::crypto::ring::default_provider());
+  let mut config = rustls::ClientConfig::builder_with_provider(provider.clone())
+    .with_safe_default_protocol_versions()
+    .map_err(|e| e.to_string())?
+    .dangerous()
+    .with_custom_certificate_verifier(Arc::new(InspectOnlyVerifier(provider)))
+    .with_no_client_auth();
+  config.alpn_protocols = vec![b"http/1.1".to_vec()];
+  Ok(config)
+}
+
+#[derive(Default)]
+pub struct TlsOptions {
+  pub settings: TlsSettings,
+  pub client_key_pem: Option<String>,
+}
+
+fn describe_certificate(der: &CertificateDer<'_>) -> CertificateInfo {
+  let sha256 = Sha256::digest(der.as_ref())
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect::<String>();
+
+  match X509Certificate::from_der(der.as_ref()) {
+    Ok((_, cert)) => {
+      let subject_alt_names = cert
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|ext| {
+          ext
+            .value
+            .general_names
+            .iter()
+            .map(|name| match name {
+              GeneralName::DNSName(dns) => dns.to_string(),
+              GeneralName::IPAddress(ip) => match ip.len() {
+                4 => std::net::Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]).to_string(),
+                16 => <[u8; 16]>::try_from(*ip)
+                  .map(|b| std::net::Ipv6Addr::from(b).to_string())
+                  .unwrap_or_default(),
+                _ => String::new(),
+              },
+              other => other.to_string(),
+            })
+            .filter(|s| !s.is_empty())
+            .collect()
+        })
+        .unwrap_or_default();
+
+      CertificateInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        serial: cert.raw_serial_as_string(),
+        not_before: cert.validity().not_before.to_string(),
+        not_after: cert.validity().not_after.to_string(),
+        subject_alt_names,
+        sha256,
+        spki_pin: crate::net::spki_pin(der),
+      }
+    }
+    Err(e) => CertificateInfo {
+      subject: format!("<unparsable: {e}>"),
+      issuer: String::new(),
+      serial: String::new(),
+      not_before: String::new(),
+      not_after: String::new(),
+      subject_alt_names: Vec::new(),
+      sha256,
+      spki_pin: None,
+    },
+  }
+}
+
+async fn tls_handshake(addr: SocketAddr, host: &str, tls: Option<&TlsOptions>) -> Result<serde_json::Value, String> {
+  let config = tls_config(tls)?;
+  let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+  let server_name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+
+  let tcp = tokio::net::TcpStream::connect(addr)
+    .await
+    .map_err(|e| format!("tcp connect failed: {e}"))?;
+  let stream = connector
+    .connect(server_name, tcp)
+    .await
+    .map_err(|e| e.to_string())?;
+
+  let (_, conn) = stream.get_ref();
+  let certificates: Vec<CertificateInfo> = conn
+    .peer_certificates()
+    .map(|certs| certs.iter().map(describe_certificate).collect())
+    .unwrap_or_default();
+
+  Ok(json!({
+    "protocol": conn.protocol_version().map(|v| format!("{:?}", v)),
+    "cipherSuite": conn.negotiated_cipher_suite().map(|s| format!("{:?}", s.suite())),
+    "alpn": conn.alpn_protocol().map(|p| String::from_utf8_lossy(p).to_string()),
+    "certificates": certificates,
+  }))
+}
+
+/// 逐步诊断一个后端地址：DNS → TCP → TLS → /health → /api/auth/me → 时钟偏差。
+/// 前一步失败时，依赖它的后续步骤记为 skipped。
+pub async fn diagnose(
+  app_version: String,
+  base_url: String,
+  bearer_token: Option<String>,
+  client: reqwest::Client,
+  tls: TlsOptions,
+) -> DiagnosticReport {
+  let mut report = DiagnosticReport {
+    url: base_url.clone(),
+    generated_at: now_secs(),
+    app_version,
+    ok: true,
+    steps: Vec::new(),
+    clock_skew_secs: None,
+  };
+
+  let parsed = reqwest::Url::parse(&base_url);
+  let (host, port, is_https) = match &parsed {
+    Ok(url) => (
+      url.host_str().unwrap_or("").trim_matches(|c| c == '[' || c == ']').to_string(),
+      url.port_or_known_default().unwrap_or(80),
+      url.scheme() == "https",
+    ),
+    Err(e) => {
+      report.push(DiagnosticStep {
+        name: "url",
+        status: StepStatus::Failed,
+        duration_ms: None,
+        detail: serde_json::Value::Null,
+        error: Some(e.to_string()),
+      });
+      return report;
+    }
+  };
+
+  // DNS
+  let started = Instant::now();
+  let resolved = with_timeout(async {
+    tokio::net::lookup_host((host.as_str(), port))
+      .await
+      .map(|addrs| addrs.collect::<Vec<SocketAddr>>())
+      .map_err(|e| e.to_string())
+  })
+  .await
+  .and_then(|addrs| {
+    if addrs.is_empty() {
+      Err("no addresses".to_string())
+    } else {
+      Ok(addrs)
+    }
+  });
+  let addrs = match resolved {
+    Ok(addrs) => {
+      report.push(step(
+        "dns",
+        started,
+        Ok(json!({ "host": host, "addresses": addrs.iter().map(|a| a.ip().to_string()).collect::<Vec<_>>() })),
+      ));
+      addrs
+    }
+    Err(e) => {
+      report.push(step("dns", started, Err(e)));
+      for name in ["tcp", "tls", "health", "auth", "clock"] {
+        report.skip(name, "dns failed");
+      }
+      return report;
+    }
+  };
+
+  // TCP：按解析顺序逐个尝试
+  let started = Instant::now();
+  let mut tcp_errors = Vec::new();
+  let mut connected = None;
+  for addr in &addrs {
+    match with_timeout(async {
+      tokio::net::TcpStream::connect(addr)
+        .await
+        .map_err(|e| e.to_string())
+    })
+    .await
+    {
+      Ok(_) => {
+        connected = Some(*addr);
+        break;
+      }
+      Err(e) => tcp_errors.push(format!("{addr}: {e}")),
+    }
+  }
+  let Some(addr) = connected else {
+    report.push(step("tcp", started, Err(tcp_errors.join("; "))));
+    for name in ["tls", "health", "auth", "clock"] {
+      report.skip(name, "tcp connect failed");
+    }
+    return report;
+  };
+  report.push(step(
+    "tcp",
+    started,
+    Ok(json!({ "address": addr.to_string(), "failedAddresses": tcp_errors })),
+  ));
+
+  // TLS
+  if is_https {
+    let started = Instant::now();
+    match with_timeout(tls_handshake(addr, &host, Some(&tls))).await {
+      Ok(detail) => report.push(step("tls", started, Ok(detail))),
+      Err(e) => {
+        // 校验失败（自签名、域名不符、过期等）时仍把证书读出来，方便定位
+        let peer = with_timeout(tls_handshake(addr, &host, None)).await.ok();
+        let mut failed = step("tls", started, Err(e));
+        failed.detail = json!({ "unverifiedPeer": peer });
+        report.push(failed);
+        for name in ["health", "auth", "clock"] {
+          report.skip(name, "tls handshake failed");
+        }
+        return report;
+      }
+    }
+  } else {
+    report.skip("tls", "plain http");
+  }
+
+  // /health（同时用响应的 Date 头估算时钟偏差）
+  let started = Instant::now();
+  let sent_at = SystemTime::now();
+  let health = client
+    .get(format!("{}/health", base_url))
+    .timeout(STEP_TIMEOUT)
+    .send()
+    .await;
+  let elapsed = started.elapsed();
+  let mut server_date = None;
+  match health {
+    Ok(resp) => {
+      let status = resp.status();
+      server_date = resp
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok());
+      let body = resp.text().await.unwrap_or_default();
+      let snippet: String = body.chars().take(300).collect();
+      let detail = json!({ "status": status.as_u16(), "body": snippet });
+      if status.is_success() {
+        report.push(step("health", started, Ok(detail)));
+      } else {
+        let mut failed = step("health", started, Err(format!("status {}", status.as_u16())));
+        failed.detail = detail;
+        report.push(failed);
+      }
+    }
+    Err(e) => report.push(step("health", started, Err(e.to_string()))),
+  }
+
+  // /api/auth/me
+  match bearer_token {
+    Some(token) => {
+      let started = Instant::now();
+      let result = client
+        .get(format!("{}/api/auth/me", base_url))
+        .bearer_auth(token)
+        .timeout(STEP_TIMEOUT)
+        .send()
+        .await;
+      match result {
+        Ok(resp) => {
+          let status = resp.status();
+          let body = resp.json::<serde_json::Value>().await.unwrap_or(serde_json::Value::Null);
+          let username = body
+            .get("data")
+            .and_then(|d| d.get("user"))
+            .and_then(|u| u.get("username"))
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+          let detail = json!({ "status": status.as_u16(), "username": username });
+          if status.is_success() {
+            report.push(step("auth", started, Ok(detail)));
+          } else {
+            let message = body
+              .get("message")
+              .and_then(|v| v.as_str())
+              .unwrap_or("request failed")
+              .to_string();
+            let mut failed = step("auth", started, Err(format!("status {}: {}", status.as_u16(), message)));
+            failed.detail = detail;
+            report.push(failed);
+          }
+        }
+        Err(e) => report.push(step("auth", started, Err(e.to_string()))),
+      }
+    }
+    None => report.skip("auth", "not signed in to this backend"),
+  }
+
+  // 时钟偏差：服务器 Date（秒级精度）对比请求中点的本地时间
+  match server_date {
+    Some(server) => {
+      let local_mid = sent_at + elapsed / 2;
+      let skew = match server.duration_since(local_mid) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+      };
+      report.clock_skew_secs = Some(skew);
+      let status = if skew.abs() > CLOCK_SKEW_WARN_SECS {
+        StepStatus::Warning
+      } else {
+        StepStatus::Ok
+      };
+      report.push(DiagnosticStep {
+        name: "clock",
+        status,
+        duration_ms: None,
+        detail: json!({ "skewSecs": skew }),
+        error: None,
+      });
+    }
+    None => report.skip("clock", "no Date header from /health"),
+  }
+
+  report
+}
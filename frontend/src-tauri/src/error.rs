@@ -0,0 +1,149 @@
+use serde::Serialize;
+
+use crate::gateway_error::GatewayError;
+
+/// 命令错误码：前端按它做本地化与分支处理，不要再匹配 message 文本。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+  InvalidArgument,
+  NotFound,
+  AlreadyExists,
+  Conflict,
+  // 还没有设置服务器地址
+  BackendNotConfigured,
+  // 当前服务器没有可用会话（没有 refresh token），需要登录
+  NotAuthenticated,
+  // refresh token 被服务端拒绝，需要重新登录
+  SessionExpired,
+  // 登录失败（用户名或密码错误等），message 为服务端返回的提示
+  AuthFailed,
+  Keyring,
+  Dns,
+  Network,
+  Tls,
+  Timeout,
+  // 服务端返回了错误状态码，details 中有 status 与 body
+  Upstream,
+  Io,
+  NotReady,
+  Internal,
+}
+
+/// 所有 Tauri 命令统一使用的错误类型，序列化为
+/// `{ "code": "...", "message": "...", "details": ..., "retryable": bool }`。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdhError {
+  pub code: ErrorCode,
+  pub message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub details: Option<serde_json::Value>,
+  pub retryable: bool,
+}
+
+pub type PdhResult<T> = Result<T, PdhError>;
+
+impl PdhError {
+  pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+    // 网络类错误默认可重试
+    let retryable = matches!(
+      code,
+      ErrorCode::Dns | ErrorCode::Network | ErrorCode::Timeout | ErrorCode::NotReady
+    );
+    Self {
+      code,
+      message: message.into(),
+      details: None,
+      retryable,
+    }
+  }
+
+  pub fn with_details(mut self, details: serde_json::Value) -> Self {
+    self.details = Some(details);
+    self
+  }
+
+  pub fn retryable(mut self, retryable: bool) -> Self {
+    self.retryable = retryable;
+    self
+  }
+
+  pub fn invalid_argument(message: impl Into<String>) -> Self {
+    Self::new(ErrorCode::InvalidArgument, message)
+  }
+
+  pub fn not_found(message: impl Into<String>) -> Self {
+    Self::new(ErrorCode::NotFound, message)
+  }
+
+  pub fn io(message: impl Into<String>) -> Self {
+    Self::new(ErrorCode::Io, message)
+  }
+
+  pub fn internal(message: impl Into<String>) -> Self {
+    Self::new(ErrorCode::Internal, message)
+  }
+
+  pub fn poisoned() -> Self {
+    Self::internal("gateway state poisoned")
+  }
+
+  pub fn backend_not_configured() -> Self {
+    Self::new(ErrorCode::BackendNotConfigured, "backend url not set")
+  }
+
+  /// 服务端返回了非 2xx：5xx / 429 可以稍后重试。
+  pub fn upstream(action: &str, status: u16, body: String) -> Self {
+    Self::new(ErrorCode::Upstream, format!("{action} failed ({status})"))
+      .with_details(serde_json::json!({ "status": status, "body": body }))
+      .retryable(status >= 500 || status == 429)
+  }
+}
+
+impl std::fmt::Display for PdhError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+impl std::error::Error for PdhError {}
+
+impl From<keyring::Error> for PdhError {
+  fn from(err: keyring::Error) -> Self {
+    Self::new(ErrorCode::Keyring, err.to_string())
+  }
+}
+
+impl From<std::io::Error> for PdhError {
+  fn from(err: std::io::Error) -> Self {
+    Self::io(err.to_string())
+  }
+}
+
+impl From<reqwest::Error> for PdhError {
+  fn from(err: reqwest::Error) -> Self {
+    // 复用网关对发送错误的归类（DNS / 连接 / TLS / 超时）
+    let code = match GatewayError::from_reqwest(&err) {
+      GatewayError::Dns(_) => ErrorCode::Dns,
+      GatewayError::Tls(_) => ErrorCode::Tls,
+      GatewayError::Timeout(_) => ErrorCode::Timeout,
+      _ if err.is_decode() => ErrorCode::Upstream,
+      _ => ErrorCode::Network,
+    };
+    // 只有没连上或超时值得重试；请求已送达后的失败（读 body、解码等）重试也未必安全
+    let retryable = err.is_connect() || err.is_timeout();
+    Self::new(code, crate::gateway_error::error_chain(&err)).retryable(retryable)
+  }
+}
+
+impl From<crate::session::RefreshError> for PdhError {
+  fn from(err: crate::session::RefreshError) -> Self {
+    use crate::session::RefreshError;
+    match err {
+      RefreshError::NoSession(m) => Self::new(ErrorCode::NotAuthenticated, m),
+      RefreshError::Rejected(m) => Self::new(ErrorCode::SessionExpired, m),
+      RefreshError::Transient(m) => Self::new(ErrorCode::Network, m),
+    }
+  }
+}
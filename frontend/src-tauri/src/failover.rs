@@ -0,0 +1,97 @@
+use std::sync::{Arc, RwLock};
+
+use serde_json::json;
+use tauri::Emitter;
+
+use crate::gateway::GatewayConfig;
+use crate::health::HealthSample;
+
+// 当前地址仍健康、且只比最快的慢这么多以内时不切换，避免来回抖动
+const SWITCH_MARGIN_MS: u64 = 50;
+
+fn pick_endpoint(samples: &[HealthSample], current: Option<&str>) -> Option<String> {
+  // 延迟相同取列表中靠前的（min_by_key 遇到相等返回第一个）
+  let best = samples
+    .iter()
+    .filter(|s| s.ok)
+    .min_by_key(|s| s.latency_ms.unwrap_or(u64::MAX))?;
+  let best_ms = best.latency_ms.unwrap_or(u64::MAX);
+
+  if let Some(cur) = current.and_then(|c| samples.iter().find(|s| s.endpoint == c && s.ok)) {
+    if cur.latency_ms.unwrap_or(u64::MAX) <= best_ms.saturating_add(SWITCH_MARGIN_MS) {
+      return Some(cur.endpoint.clone());
+    }
+  }
+
+  Some(best.endpoint.clone())
+}
+
+/// 按健康检查对全部访问地址的探测结果，把网关（以及上传任务）的流量切到最快的健康地址。
+/// 全部不可用或探测期间切换了服务器时保持原地址不动。返回之后使用的访问地址。
+pub fn apply(
+  app: &tauri::AppHandle,
+  config: &Arc<RwLock<GatewayConfig>>,
+  backend: &str,
+  samples: &[HealthSample],
+  current: Option<&str>,
+) -> Option<String> {
+  let Some(next) = pick_endpoint(samples, current) else {
+    return current.map(str::to_string);
+  };
+  let changed = match config.write() {
+    Ok(mut cfg) if cfg.backend_base_url.as_deref() == Some(backend) => {
+      let changed = cfg.active_endpoint.as_deref() != Some(next.as_str());
+      cfg.active_endpoint = Some(next.clone());
+      changed
+    }
+    _ => return current.map(str::to_string),
+  };
+
+  if changed {
+    log::info!("[failover] {} -> {}", backend, next);
+    let _ = app.emit(
+      "pdh-backend-endpoint",
+      json!({
+        "backend": backend,
+        "endpoint": next,
+        "previous": current,
+        "probes": samples,
+      }),
+    );
+  }
+  Some(next)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample(endpoint: &str, latency_ms: Option<u64>) -> HealthSample {
+    HealthSample {
+      at: 0,
+      endpoint: endpoint.to_string(),
+      ok: latency_ms.is_some(),
+      status: latency_ms.map(|_| 200),
+      latency_ms,
+      error: None,
+      tls_error: false,
+    }
+  }
+
+  #[test]
+  fn pick_endpoint_prefers_fastest_healthy() {
+    let samples = [sample("a", Some(300)), sample("b", Some(100)), sample("c", None)];
+    assert_eq!(pick_endpoint(&samples, None).as_deref(), Some("b"));
+    // 当前地址不可用时换到最快的健康地址
+    assert_eq!(pick_endpoint(&samples, Some("c")).as_deref(), Some("b"));
+    assert_eq!(pick_endpoint(&[sample("a", None)], Some("a")), None);
+  }
+
+  #[test]
+  fn pick_endpoint_keeps_current_within_margin() {
+    let samples = [sample("a", Some(140)), sample("b", Some(100))];
+    assert_eq!(pick_endpoint(&samples, Some("a")).as_deref(), Some("a"));
+    let samples = [sample("a", Some(200)), sample("b", Some(100))];
+    assert_eq!(pick_endpoint(&samples, Some("a")).as_deref(), Some("b"));
+  }
+}
@@ -6,25 +6,205 @@ use std::{
 use axum::{
   body::Body,
   extract::{Path, State},
-  http::{HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Uri},
-  response::Response,
+  http::{self, header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Uri},
+  middleware::{self, Next},
+  response::{IntoResponse, Response},
   routing::{any, get},
   Router,
 };
 use futures_util::StreamExt;
 use reqwest::Client;
-use tower_http::cors::{Any, CorsLayer};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::api_cache::{self, ApiCache, CachedEntry, CachedMeta};
+use crate::coalesce::RequestCoalescer;
+use crate::gateway_error::GatewayError;
+use crate::health::HealthMonitor;
+use crate::inspector::{self, GatewayInspector};
+use crate::metrics::{self, GatewayMetrics};
+use crate::net::HttpClient;
+use crate::request_id::{self, REQUEST_ID_HEADER};
+use crate::retry::{self, RetryPolicy};
+use crate::session::{self, SessionRefresher};
+use crate::attachment_cache::{
+  parse_content_range, parse_range, AttachmentCache, CacheEntry, Filler, RangeRequest, StoredHeaders,
+};
 
 #[derive(Clone, Default)]
 pub struct GatewayConfig {
+  // 服务器标识（配置里的主地址）：钥匙串与本地缓存都按它隔离
   pub backend_base_url: Option<String>,
   pub bearer_token: Option<String>,
+  // 当前会话的用户名：同一服务器可以保留多个账户的 refresh token，按它取用
+  pub account: Option<String>,
+  // 选中服务器时它最近使用的账户：升级前按服务器保存的旧 refresh token 只属于这个账户
+  pub legacy_owner: Option<String>,
+  // 同一服务器的多个访问地址（按优先级）；为空时只有 backend_base_url
+  pub endpoints: Vec<String>,
+  // 健康检查选出的当前地址；None 表示直接使用 backend_base_url
+  pub active_endpoint: Option<String>,
+}
+
+impl GatewayConfig {
+  /// 实际发请求用的地址。
+  pub fn upstream_base_url(&self) -> Option<String> {
+    self
+      .active_endpoint
+      .clone()
+      .or_else(|| self.backend_base_url.clone())
+  }
+
+  /// 本地缓存的隔离范围：有会话时按服务器 + 账户，没有会话时是该服务器的匿名范围。
+  pub fn cache_scope(&self) -> Option<String> {
+    let backend = self.backend_base_url.as_deref()?;
+    let account = self.bearer_token.as_ref().and(self.account.as_deref());
+    Some(ApiCache::account_scope(backend, account))
+  }
+}
+
+// pdh:// 自定义协议在 WebView 里的基础地址：Windows/Android 上由 WebView2/WebView
+// 映射为 http://<scheme>.localhost，其余平台直接使用 <scheme>://localhost
+pub const URI_SCHEME: &str = "pdh";
+#[cfg(any(windows, target_os = "android"))]
+pub const SCHEME_BASE_URL: &str = "http://pdh.localhost";
+#[cfg(not(any(windows, target_os = "android")))]
+pub const SCHEME_BASE_URL: &str = "pdh://localhost";
+
+// pdh:// 响应在内存里收齐的上限；更大的响应改走 TCP 网关
+const SCHEME_BODY_LIMIT: usize = 32 * 1024 * 1024;
+
+// 不走路径前缀时，调用方可以改用这个请求头携带网关密钥
+pub const GATEWAY_KEY_HEADER: &str = "x-pdh-gateway-key";
+
+// 后端滑动续期时在响应头里下发的新 access token：由网关接管，不转发给前端
+const SLIDING_TOKEN_HEADER: &str = "x-pdh-auth-token";
+
+// 允许访问网关的页面来源：打包后的 Tauri WebView（不同平台 scheme 不同）；
+// 开发模式额外放行 devUrl
+const ALLOWED_ORIGINS: &[&str] = &[
+  "tauri://localhost",
+  "http://tauri.localhost",
+  "https://tauri.localhost",
+  #[cfg(debug_assertions)]
+  "http://localhost:3000",
+];
+
+/// 每次启动随机生成的网关密钥（32 字节，hex 编码）。
+pub fn generate_gateway_key() -> String {
+  let bytes: [u8; 32] = rand::random();
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Clone)]
+struct GatewayGuard {
+  key: Arc<str>,
+  port: u16,
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn is_allowed_host(headers: &HeaderMap, port: u16) -> bool {
+  let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+    return false;
+  };
+  // DNS rebinding 时 Host 是攻击者的域名；这里只认本机地址 + 网关端口
+  let host = host.to_ascii_lowercase();
+  host == format!("127.0.0.1:{port}") || host == format!("localhost:{port}")
+}
+
+fn is_allowed_origin(origin: &HeaderValue) -> bool {
+  // 沙箱 iframe 的 Origin 为 "null"，同样拒绝
+  origin
+    .to_str()
+    .map(|o| ALLOWED_ORIGINS.contains(&o))
+    .unwrap_or(false)
+}
+
+fn forbidden(msg: &'static str) -> Response {
+  GatewayError::Forbidden(msg).into_response()
+}
+
+// 不带 Origin 的请求（<img>、<video> 等）交给密钥/协议本身把关
+fn origin_header_allowed(headers: &HeaderMap) -> bool {
+  headers.get(header::ORIGIN).map(is_allowed_origin).unwrap_or(true)
+}
+
+/// TCP 监听的所有请求都要经过：Host 必须是本机回环地址，带 Origin 时必须在白名单里。
+async fn check_host_and_origin(
+  State(guard): State<GatewayGuard>,
+  req: Request<Body>,
+  next: Next,
+) -> Response {
+  if !is_allowed_host(req.headers(), guard.port) {
+    return forbidden("host not allowed");
+  }
+  if !origin_header_allowed(req.headers()) {
+    return forbidden("origin not allowed");
+  }
+  next.run(req).await
+}
+
+/// pdh:// 请求只可能来自本应用的 WebView，不需要密钥与 Host；
+/// 但 WebView 里的沙箱 iframe 也能发起，仍然校验 Origin。
+async fn check_origin(req: Request<Body>, next: Next) -> Response {
+  if !origin_header_allowed(req.headers()) {
+    return forbidden("origin not allowed");
+  }
+  next.run(req).await
+}
+
+/// 根路径下的路由（没有密钥前缀）必须通过请求头提供网关密钥。
+async fn require_key_header(
+  State(guard): State<GatewayGuard>,
+  req: Request<Body>,
+  next: Next,
+) -> Response {
+  let ok = req
+    .headers()
+    .get(GATEWAY_KEY_HEADER)
+    .map(|v| constant_time_eq(v.as_bytes(), guard.key.as_bytes()))
+    .unwrap_or(false);
+  if !ok {
+    return forbidden("gateway key required");
+  }
+  next.run(req).await
 }
 
 #[derive(Clone)]
 struct AppState {
-  client: Client,
+  app: tauri::AppHandle,
+  http: Arc<HttpClient>,
   config: Arc<RwLock<GatewayConfig>>,
+  session: Arc<SessionRefresher>,
+  api_cache: Option<Arc<ApiCache>>,
+  attachment_cache: Option<Arc<AttachmentCache>>,
+  health: Arc<HealthMonitor>,
+  inspector: Arc<GatewayInspector>,
+  metrics: Arc<GatewayMetrics>,
+  coalescer: Arc<RequestCoalescer>,
+}
+
+impl AppState {
+  fn client(&self) -> Client {
+    self.http.get()
+  }
+}
+
+/// 连不上上游时让健康检查立即重新选择访问地址，并刷新连通状态。
+fn upstream_send_failed(state: &AppState, err: &reqwest::Error) -> GatewayError {
+  if err.is_connect() || err.is_timeout() {
+    state.health.wake();
+  }
+  GatewayError::from_reqwest(err)
 }
 
 fn is_hop_by_hop_header(name: &HeaderName) -> bool {
@@ -41,6 +221,20 @@ fn is_hop_by_hop_header(name: &HeaderName) -> bool {
   )
 }
 
+/// 允许写进 API 缓存的响应头：逐跳头、Cookie、续期 token 与认证相关的头不落盘。
+fn is_storable_response_header(name: &str) -> bool {
+  let Ok(hn) = HeaderName::from_bytes(name.as_bytes()) else {
+    return false;
+  };
+  if is_hop_by_hop_header(&hn) {
+    return false;
+  }
+  !matches!(
+    hn.as_str(),
+    "set-cookie" | "set-cookie2" | "authorization" | "www-authenticate" | SLIDING_TOKEN_HEADER
+  )
+}
+
 fn copy_request_headers(req_headers: &HeaderMap, target: &mut reqwest::header::HeaderMap) {
   for (name, value) in req_headers.iter() {
     if is_hop_by_hop_header(name) {
@@ -51,6 +245,11 @@ fn copy_request_headers(req_headers: &HeaderMap, target: &mut reqwest::header::H
       continue;
     }
 
+    // 网关密钥只在本机使用，不能转发给后端
+    if name.as_str().eq_ignore_ascii_case(GATEWAY_KEY_HEADER) {
+      continue;
+    }
+
     target.insert(name.clone(), value.clone());
   }
 }
@@ -65,8 +264,10 @@ fn copy_response_headers(src: &reqwest::header::HeaderMap, dst: &mut HeaderMap)
       continue;
     }
  
-    // 本地网关不需要后端的 Set-Cookie
-    if name.as_str().eq_ignore_ascii_case("set-cookie") {
+    // 本地网关不需要后端的 Set-Cookie；续期 token 只留在网关里
+    if name.as_str().eq_ignore_ascii_case("set-cookie")
+      || name.as_str().eq_ignore_ascii_case(SLIDING_TOKEN_HEADER)
+    {
       continue;
     }
 
@@ -88,134 +289,390 @@ fn copy_response_headers(src: &reqwest::header::HeaderMap, dst: &mut HeaderMap)
   }
 }
 
-async fn proxy_attachment(
-  State(state): State<AppState>,
-  Path(id): Path<String>,
-  uri: Uri,
-  method: Method,
-  headers: HeaderMap,
-) -> Result<Response, StatusCode> {
-  let (backend_base_url, bearer_token) = {
-    let cfg = state
-      .config
-      .read()
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    (cfg.backend_base_url.clone(), cfg.bearer_token.clone())
+/// 采用后端滑动续期下发的新 token（仅当请求用的是网关注入的 token，且期间没有切换服务器）。
+fn adopt_sliding_token(state: &AppState, backend: &str, headers: &reqwest::header::HeaderMap) {
+  let Some(next) = headers
+    .get(SLIDING_TOKEN_HEADER)
+    .and_then(|v| v.to_str().ok())
+    .map(str::trim)
+    .filter(|t| !t.is_empty())
+  else {
+    return;
   };
+  if let Ok(mut cfg) = state.config.write() {
+    if cfg.backend_base_url.as_deref() == Some(backend) {
+      cfg.bearer_token = Some(next.to_string());
+    }
+  }
+}
 
-  let backend_base_url = backend_base_url.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
-  if id.trim().is_empty() {
-    return Err(StatusCode::BAD_REQUEST);
+fn set_bearer(headers: &mut reqwest::header::HeaderMap, token: &str) {
+  let value = format!("Bearer {}", token.trim());
+  if let Ok(hv) = reqwest::header::HeaderValue::from_str(&value) {
+    headers.insert(reqwest::header::AUTHORIZATION, hv);
   }
+}
 
-  let mut url = format!("{}/api/attachments/{}", backend_base_url, id);
-  if let Some(q) = uri.query() {
-    if !q.trim().is_empty() {
-      url = format!("{url}?{q}");
-    }
+/// GET / HEAD / OPTIONS 总是可以重试；PUT 按设置决定（body 可重放时）。
+fn retry_policy(state: &AppState, method: &Method, headers: &HeaderMap) -> RetryPolicy {
+  let network = state.http.network();
+  let idempotent = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+    || (*method == Method::PUT && network.retry_put);
+  if idempotent && is_replayable_request(method, headers) {
+    RetryPolicy::from_settings(&network)
+  } else {
+    RetryPolicy::disabled()
   }
-  let mut req = state.client.request(method.clone(), url);
+}
 
-  let mut out_headers = reqwest::header::HeaderMap::new();
-  copy_request_headers(&headers, &mut out_headers);
+fn with_retry_count(mut response: Response, retries: u32) -> Response {
+  if retries > 0 {
+    response.headers_mut().insert(
+      HeaderName::from_static(retry::RETRY_COUNT_HEADER),
+      HeaderValue::from(retries),
+    );
+  }
+  response
+}
 
-  if !out_headers.contains_key(reqwest::header::AUTHORIZATION) {
-    if let Some(token) = bearer_token {
-      if !token.trim().is_empty() {
-        let value = format!("Bearer {}", token.trim());
-        if let Ok(hv) = reqwest::header::HeaderValue::from_str(&value) {
-          out_headers.insert(reqwest::header::AUTHORIZATION, hv);
-        }
+// 重放请求需要把 body 读进内存：只对无 body 的方法和小 body 生效，大上传仍然流式转发
+const MAX_REPLAY_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+fn is_replayable_request(method: &Method, headers: &HeaderMap) -> bool {
+  if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::DELETE) {
+    return true;
+  }
+  headers
+    .get(axum::http::header::CONTENT_LENGTH)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.trim().parse::<usize>().ok())
+    .map(|len| len <= MAX_REPLAY_BODY_BYTES)
+    .unwrap_or(false)
+}
+
+/// 上游对网关注入的 token 返回 401：刷新一次（并发请求共享同一次刷新）并返回新 token。
+/// 刷新彻底失败（refresh token 无效/不存在）时通知前端需要重新登录。
+async fn refresh_after_unauthorized(state: &AppState, used_token: &str) -> Option<String> {
+  match state
+    .session
+    .refresh(&state.http, &state.config, Some(used_token))
+    .await
+  {
+    Ok(refreshed) => {
+      if !refreshed.body.is_null() {
+        log::info!("[gateway] {}access token refreshed after 401", request_id::log_prefix());
+        session::emit_auth_state(
+          &state.app,
+          "refreshed",
+          serde_json::json!({ "expiresIn": refreshed.expires_in() }),
+        );
+      }
+      Some(refreshed.token)
+    }
+    Err(e) => {
+      log::warn!(
+        "[gateway] {}token refresh after 401 failed: {}",
+        request_id::log_prefix(),
+        e.message()
+      );
+      if e.is_final() {
+        session::emit_auth_state(
+          &state.app,
+          "expired",
+          serde_json::json!({ "reason": e.message() }),
+        );
       }
+      None
     }
   }
+}
 
-  req = req.headers(out_headers);
+fn stored_headers_from(headers: &reqwest::header::HeaderMap) -> StoredHeaders {
+  StoredHeaders {
+    content_type: header_string(headers, reqwest::header::CONTENT_TYPE),
+    content_disposition: header_string(headers, reqwest::header::CONTENT_DISPOSITION),
+    cache_control: header_string(headers, reqwest::header::CACHE_CONTROL),
+    etag: header_string(headers, reqwest::header::ETAG),
+    last_modified: header_string(headers, reqwest::header::LAST_MODIFIED),
+  }
+}
 
-  let resp = req.send().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
-  let status = resp.status();
-  let upstream_headers = resp.headers().clone();
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+  let bare = etag.trim_start_matches("W/");
+  if_none_match.split(',').map(|c| c.trim()).any(|c| {
+    c == "*" || c == etag || c.trim_start_matches("W/") == bare
+  })
+}
 
-  let mut response = if method == Method::HEAD {
-    Response::builder()
-      .status(status)
-      .body(Body::empty())
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-  } else {
-    let stream = resp.bytes_stream().map(|chunk| {
-      chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-    });
-    let body = Body::from_stream(stream);
+fn insert_stored_headers(dst: &mut HeaderMap, stored: &StoredHeaders) {
+  let pairs = [
+    ("content-type", &stored.content_type),
+    ("content-disposition", &stored.content_disposition),
+    ("cache-control", &stored.cache_control),
+    ("etag", &stored.etag),
+    ("last-modified", &stored.last_modified),
+  ];
+  for (name, value) in pairs {
+    if let Some(hv) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+      dst.insert(HeaderName::from_static(name), hv);
+    }
+  }
+}
 
-    Response::builder()
-      .status(status)
-      .body(body)
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+/// 尝试完全由本地缓存应答；缓存覆盖不了请求的区间时返回 None，交给上游
+async fn serve_cached_attachment(
+  cache: &AttachmentCache,
+  key: &str,
+  entry: &CacheEntry,
+  method: &Method,
+  headers: &HeaderMap,
+) -> Option<Response> {
+  let total = entry.total_size;
+
+  if entry.is_complete() {
+    let if_none_match = headers
+      .get(axum::http::header::IF_NONE_MATCH)
+      .and_then(|v| v.to_str().ok());
+    if let (Some(inm), Some(etag)) = (if_none_match, entry.headers.etag.as_deref()) {
+      if etag_matches(inm, etag) {
+        let mut response = Response::builder()
+          .status(StatusCode::NOT_MODIFIED)
+          .body(Body::empty())
+          .ok()?;
+        insert_stored_headers(response.headers_mut(), &entry.headers);
+        return Some(response);
+      }
+    }
+  }
+
+  let range_header = headers
+    .get(axum::http::header::RANGE)
+    .and_then(|v| v.to_str().ok());
+  let (status, start, end) = match parse_range(range_header, total) {
+    RangeRequest::Unsatisfiable => {
+      let mut response = Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .body(Body::empty())
+        .ok()?;
+      response.headers_mut().insert(
+        axum::http::header::CONTENT_RANGE,
+        HeaderValue::from_str(&format!("bytes */{total}")).ok()?,
+      );
+      return Some(response);
+    }
+    RangeRequest::Full if entry.is_complete() => (StatusCode::OK, 0, total),
+    RangeRequest::Partial(a, b) if entry.covers(a, b) => (StatusCode::PARTIAL_CONTENT, a, b),
+    _ => return None,
   };
 
-  copy_response_headers(&upstream_headers, response.headers_mut());
-  // 允许任意 origin 嵌入本机网关提供的附件资源（img/video/audio/object 等）
-  response.headers_mut().insert(
+  let body = if *method == Method::HEAD {
+    Body::empty()
+  } else {
+    let mut file = tokio::fs::File::open(cache.data_path(key, entry)).await.ok()?;
+    file.seek(std::io::SeekFrom::Start(start)).await.ok()?;
+    Body::from_stream(ReaderStream::new(file.take(end - start)))
+  };
+
+  let mut response = Response::builder().status(status).body(body).ok()?;
+  let out = response.headers_mut();
+  insert_stored_headers(out, &entry.headers);
+  out.insert(axum::http::header::CONTENT_LENGTH, HeaderValue::from(end - start));
+  out.insert(axum::http::header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+  if status == StatusCode::PARTIAL_CONTENT {
+    out.insert(
+      axum::http::header::CONTENT_RANGE,
+      HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end - 1, total)).ok()?,
+    );
+  }
+  out.insert(
+    HeaderName::from_static("x-pdh-cache"),
+    HeaderValue::from_static("hit"),
+  );
+  out.insert(
     HeaderName::from_static("cross-origin-resource-policy"),
     HeaderValue::from_static("cross-origin"),
   );
-  Ok(response)
+  Some(response)
 }
 
-async fn proxy_attachment_thumb(
-  State(state): State<AppState>,
-  Path(id): Path<String>,
+/// 后台补齐用的上游请求：backend 固定为写入时的那台（缓存 key 含 backend），token 每次取最新
+fn attachment_filler(state: &AppState, url: String) -> Filler {
+  let client = state.client();
+  let config = state.config.clone();
+  // 补齐请求沿用触发它的那次请求的 ID，后端日志里能对上
+  let request_id = request_id::current();
+  Arc::new(move |range| {
+    let token = config
+      .read()
+      .ok()
+      .and_then(|cfg| cfg.bearer_token.clone())
+      .unwrap_or_default();
+    let mut req = client.get(url.clone());
+    if !token.trim().is_empty() {
+      req = req.bearer_auth(token.trim());
+    }
+    if let Some((start, end)) = range {
+      req = req.header(reqwest::header::RANGE, format!("bytes={start}-{end}"));
+    }
+    if let Some(id) = &request_id {
+      req = req.header(REQUEST_ID_HEADER, id.as_str());
+    }
+    req
+  })
+}
+
+// 只缓存未压缩的 200/206；206 需要从 Content-Range 拿到写入位置与总大小
+fn attachment_write_plan(
+  status: reqwest::StatusCode,
+  headers: &reqwest::header::HeaderMap,
+) -> Option<(u64, u64)> {
+  let encoded = headers
+    .get(reqwest::header::CONTENT_ENCODING)
+    .and_then(|v| v.to_str().ok())
+    .map(|v| !v.trim().eq_ignore_ascii_case("identity"))
+    .unwrap_or(false);
+  if encoded {
+    return None;
+  }
+
+  match status.as_u16() {
+    200 => headers
+      .get(reqwest::header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.trim().parse::<u64>().ok())
+      .map(|total| (0, total)),
+    206 => headers
+      .get(reqwest::header::CONTENT_RANGE)
+      .and_then(|v| v.to_str().ok())
+      .and_then(parse_content_range),
+    _ => None,
+  }
+}
+
+async fn proxy_attachment_resource(
+  state: AppState,
+  id: String,
+  variant: &'static str,
   uri: Uri,
   method: Method,
   headers: HeaderMap,
-) -> Result<Response, StatusCode> {
-  let (backend_base_url, bearer_token) = {
+) -> Result<Response, GatewayError> {
+  let (backend_base_url, upstream_base_url, bearer_token, cache_scope) = {
     let cfg = state
       .config
       .read()
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    (cfg.backend_base_url.clone(), cfg.bearer_token.clone())
+      .map_err(|_| GatewayError::Internal("gateway state poisoned".to_string()))?;
+    (
+      cfg.backend_base_url.clone(),
+      cfg.upstream_base_url(),
+      cfg.bearer_token.clone(),
+      cfg.cache_scope(),
+    )
   };
 
-  let backend_base_url = backend_base_url.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+  let backend_base_url = backend_base_url.ok_or(GatewayError::BackendNotConfigured)?;
+  let upstream_base_url = upstream_base_url.ok_or(GatewayError::BackendNotConfigured)?;
   if id.trim().is_empty() {
-    return Err(StatusCode::BAD_REQUEST);
+    return Err(GatewayError::BadRequest("attachment id is empty"));
   }
 
-  let mut url = format!("{}/api/attachments/{}/thumb", backend_base_url, id);
+  let suffix = if variant == "thumb" { "/thumb" } else { "" };
+  let mut url = format!("{}/api/attachments/{}{}", upstream_base_url, id, suffix);
+  // 缩略图尺寸等参数走 query，缓存需要按 query 区分
+  let mut cache_variant = variant.to_string();
   if let Some(q) = uri.query() {
     if !q.trim().is_empty() {
       url = format!("{url}?{q}");
+      cache_variant = format!("{variant}?{q}");
     }
   }
 
-  let mut req = state.client.request(method.clone(), url);
+  // 本地缓存只在网关持有会话时使用，并按账户隔离：退出或切换账户后，旧账户的附件不会再从本地返回。
+  // 前端自带 Authorization 时无法确认身份，不走缓存
+  let has_session = bearer_token.as_deref().is_some_and(|t| !t.trim().is_empty());
+  let cache_scope = cache_scope
+    .filter(|_| has_session && !headers.contains_key(header::AUTHORIZATION))
+    .unwrap_or_default();
+  let cache = state.attachment_cache.clone().filter(|_| !cache_scope.is_empty());
+  let cache_key = cache
+    .as_ref()
+    .map(|_| AttachmentCache::key_for(&cache_scope, &id, &cache_variant));
+
+  if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+    if let Some(entry) = cache.lookup(key, &cache_scope) {
+      if let Some(response) = serve_cached_attachment(cache, key, &entry, &method, &headers).await {
+        return Ok(response);
+      }
+    }
+  }
 
   let mut out_headers = reqwest::header::HeaderMap::new();
   copy_request_headers(&headers, &mut out_headers);
 
-  if !out_headers.contains_key(reqwest::header::AUTHORIZATION) {
-    if let Some(token) = bearer_token {
-      if !token.trim().is_empty() {
-        let value = format!("Bearer {}", token.trim());
-        if let Ok(hv) = reqwest::header::HeaderValue::from_str(&value) {
-          out_headers.insert(reqwest::header::AUTHORIZATION, hv);
-        }
+  // 只有网关自己注入的 token 才由网关负责刷新；前端显式带的 Authorization 原样透传
+  let injected_token = if out_headers.contains_key(reqwest::header::AUTHORIZATION) {
+    None
+  } else {
+    bearer_token.filter(|t| !t.trim().is_empty())
+  };
+  if let Some(token) = &injected_token {
+    set_bearer(&mut out_headers, token);
+  }
+
+  let policy = retry_policy(&state, &method, &headers);
+  let build = |headers: &reqwest::header::HeaderMap| {
+    state.client().request(method.clone(), url.clone()).headers(headers.clone())
+  };
+  let (sent, mut retries) = retry::send(policy, || build(&out_headers)).await;
+  let mut resp = match sent {
+    Ok(resp) => resp,
+    Err(e) => return Ok(with_retry_count(upstream_send_failed(&state, &e).into_response(), retries)),
+  };
+
+  if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+    if let Some(used) = &injected_token {
+      if let Some(next) = refresh_after_unauthorized(&state, used).await {
+        set_bearer(&mut out_headers, &next);
+        let (sent, n) = retry::send(policy, || build(&out_headers)).await;
+        retries += n;
+        resp = match sent {
+          Ok(resp) => resp,
+          Err(e) => return Ok(with_retry_count(upstream_send_failed(&state, &e).into_response(), retries)),
+        };
       }
     }
   }
 
-  req = req.headers(out_headers);
-
-  let resp = req.send().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
   let status = resp.status();
   let upstream_headers = resp.headers().clone();
+  if injected_token.is_some() {
+    adopt_sliding_token(&state, &backend_base_url, &upstream_headers);
+  }
+
+  let write_to = match (cache, cache_key, method == Method::GET) {
+    (Some(cache), Some(key), true) => attachment_write_plan(status, &upstream_headers).and_then(
+      |(offset, total)| {
+        cache
+          .begin_write(&key, &cache_scope, &id, &cache_variant, total, stored_headers_from(&upstream_headers))
+          .map(|generation| (cache, key, generation, offset))
+      },
+    ),
+    _ => None,
+  };
 
   let mut response = if method == Method::HEAD {
     Response::builder()
       .status(status)
       .body(Body::empty())
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
+  } else if let Some((cache, key, generation, offset)) = write_to {
+    let filler = attachment_filler(&state, url);
+    let body = Body::from_stream(cache.tee(key, generation, offset, resp.bytes_stream(), Some(filler)));
+
+    Response::builder()
+      .status(status)
+      .body(body)
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
   } else {
     let stream = resp.bytes_stream().map(|chunk| {
       chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
@@ -225,34 +682,72 @@ async fn proxy_attachment_thumb(
     Response::builder()
       .status(status)
       .body(body)
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
   };
 
   copy_response_headers(&upstream_headers, response.headers_mut());
+  // 允许任意 origin 嵌入本机网关提供的附件资源（img/video/audio/object 等）
   response.headers_mut().insert(
     HeaderName::from_static("cross-origin-resource-policy"),
     HeaderValue::from_static("cross-origin"),
   );
-  Ok(response)
+  Ok(with_retry_count(response, retries))
+}
+
+async fn proxy_attachment(
+  State(state): State<AppState>,
+  Path(id): Path<String>,
+  uri: Uri,
+  method: Method,
+  headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+  proxy_attachment_resource(state, id, "file", uri, method, headers).await
+}
+
+async fn proxy_attachment_thumb(
+  State(state): State<AppState>,
+  Path(id): Path<String>,
+  uri: Uri,
+  method: Method,
+  headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+  // 同一缩略图常被多个窗口同时请求：合并成一次上游请求
+  let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("");
+  match coalesce_key(&state, &method, path_and_query, &headers)? {
+    Some(key) => {
+      let shared = state.clone();
+      Ok(
+        state
+          .coalescer
+          .run(key, move || async move {
+            proxy_attachment_resource(shared, id, "thumb", uri, method, headers)
+              .await
+              .into_response()
+          })
+          .await,
+      )
+    }
+    None => proxy_attachment_resource(state, id, "thumb", uri, method, headers).await,
+  }
 }
 
 async fn proxy_health(
   State(state): State<AppState>,
   method: Method,
   headers: HeaderMap,
-) -> Result<Response, StatusCode> {
-  let backend_base_url = {
+) -> Result<Response, GatewayError> {
+  let upstream_base_url = {
     let cfg = state
       .config
       .read()
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    cfg.backend_base_url.clone()
+      .map_err(|_| GatewayError::Internal("gateway state poisoned".to_string()))?;
+    cfg.upstream_base_url()
   };
 
-  let backend_base_url = backend_base_url.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
-  let url = format!("{}/health", backend_base_url);
+  let upstream_base_url = upstream_base_url.ok_or(GatewayError::BackendNotConfigured)?;
+  let url = format!("{}/health", upstream_base_url);
 
-  let mut req = state.client.request(method.clone(), url);
+  let mut req = state.client().request(method.clone(), url);
   let mut out_headers = reqwest::header::HeaderMap::new();
   copy_request_headers(&headers, &mut out_headers);
 
@@ -260,7 +755,7 @@ async fn proxy_health(
   out_headers.remove(reqwest::header::AUTHORIZATION);
   req = req.headers(out_headers);
 
-  let resp = req.send().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
+  let resp = req.send().await.map_err(|e| upstream_send_failed(&state, &e))?;
   let status = resp.status();
   let upstream_headers = resp.headers().clone();
 
@@ -268,7 +763,7 @@ async fn proxy_health(
     Response::builder()
       .status(status)
       .body(Body::empty())
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
   } else {
     let stream = resp.bytes_stream().map(|chunk| {
       chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
@@ -278,7 +773,7 @@ async fn proxy_health(
     Response::builder()
       .status(status)
       .body(body)
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
   };
 
   copy_response_headers(&upstream_headers, response.headers_mut());
@@ -289,20 +784,196 @@ async fn proxy_health(
   Ok(response)
 }
 
+fn is_auth_api_path(path: &str) -> bool {
+  path == "auth" || path.starts_with("auth/")
+}
+
+// 登录/刷新的响应体里带 token：只能走 pdh_auth_login / pdh_auth_refresh 命令，网关不代理
+fn is_credential_api_path(path: &str) -> bool {
+  matches!(path.trim_end_matches('/'), "auth/login" | "auth/refresh")
+}
+
+// 认证与 AI 接口不落盘：前者含凭据，后者是一次性的流式结果
+fn is_cacheable_api_path(path: &str) -> bool {
+  !(is_auth_api_path(path) || path.starts_with("ai/"))
+}
+
+fn is_cacheable_response(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap) -> bool {
+  if status != reqwest::StatusCode::OK {
+    return false;
+  }
+  let no_store = headers
+    .get(reqwest::header::CACHE_CONTROL)
+    .and_then(|v| v.to_str().ok())
+    .map(|v| v.to_ascii_lowercase().contains("no-store"))
+    .unwrap_or(false);
+  !no_store
+}
+
+// 上游挂了（反代返回 502/503/504）也视为不可达，允许回放缓存
+fn is_upstream_down_status(status: reqwest::StatusCode) -> bool {
+  matches!(status.as_u16(), 502..=504)
+}
+
+fn header_string(headers: &reqwest::header::HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+  headers
+    .get(name)
+    .and_then(|v| v.to_str().ok())
+    .map(|v| v.to_string())
+}
+
+async fn cached_response(
+  entry: CachedEntry,
+  method: &Method,
+  marker: &'static str,
+) -> Result<Response, GatewayError> {
+  let status = StatusCode::from_u16(entry.meta.status)
+    .map_err(|_| GatewayError::Internal(format!("invalid cached status {}", entry.meta.status)))?;
+
+  let body = if *method == Method::HEAD {
+    Body::empty()
+  } else {
+    let file = tokio::fs::File::open(&entry.body_path)
+      .await
+      .map_err(|e| GatewayError::Internal(format!("open cached body failed: {e}")))?;
+    Body::from_stream(ReaderStream::new(file))
+  };
+
+  let mut response = Response::builder()
+    .status(status)
+    .body(body)
+    .map_err(|e| GatewayError::Internal(e.to_string()))?;
+
+  let mut stored_headers = reqwest::header::HeaderMap::new();
+  for (name, value) in entry.meta.headers.iter() {
+    if let (Ok(hn), Ok(hv)) = (
+      reqwest::header::HeaderName::from_bytes(name.as_bytes()),
+      reqwest::header::HeaderValue::from_str(value),
+    ) {
+      stored_headers.append(hn, hv);
+    }
+  }
+  copy_response_headers(&stored_headers, response.headers_mut());
+
+  let age = api_cache::now_secs().saturating_sub(entry.meta.stored_at);
+  response
+    .headers_mut()
+    .insert(HeaderName::from_static("age"), HeaderValue::from(age));
+  // 让前端能区分“实时数据”和“离线回放”（stale）/“304 复用”（revalidated）
+  response.headers_mut().insert(
+    HeaderName::from_static("x-pdh-cache"),
+    HeaderValue::from_static(marker),
+  );
+  response.headers_mut().insert(
+    HeaderName::from_static("cross-origin-resource-policy"),
+    HeaderValue::from_static("cross-origin"),
+  );
+  Ok(response)
+}
+
+/// 合并请求的键：上游地址 + 路径、认证身份，以及会影响响应内容的请求头。
+fn coalesce_key(
+  state: &AppState,
+  method: &Method,
+  path_and_query: &str,
+  headers: &HeaderMap,
+) -> Result<Option<String>, GatewayError> {
+  let (upstream, token) = {
+    let cfg = state
+      .config
+      .read()
+      .map_err(|_| GatewayError::Internal("gateway state poisoned".to_string()))?;
+    (cfg.upstream_base_url(), cfg.bearer_token.clone())
+  };
+  let Some(upstream) = upstream else {
+    return Ok(None);
+  };
+  Ok(request_coalesce_key(&upstream, token.as_deref(), method, path_and_query, headers))
+}
+
+/// 只合并安全的 GET；带 Range / 条件头的请求各自的响应不同，不合并。
+fn request_coalesce_key(
+  upstream: &str,
+  token: Option<&str>,
+  method: &Method,
+  path_and_query: &str,
+  headers: &HeaderMap,
+) -> Option<String> {
+  if *method != Method::GET {
+    return None;
+  }
+  let varies = [
+    header::RANGE,
+    header::IF_RANGE,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+  ];
+  if varies.iter().any(|name| headers.contains_key(name)) {
+    return None;
+  }
+
+  // 前端自带的 Authorization 会原样透传，此时按它区分身份；只保存哈希
+  let identity = headers
+    .get(header::AUTHORIZATION)
+    .and_then(|v| v.to_str().ok())
+    .or(token)
+    .unwrap_or_default();
+  let identity: String = Sha256::digest(identity.trim().as_bytes())
+    .iter()
+    .take(16)
+    .map(|b| format!("{:02x}", b))
+    .collect();
+
+  let mut key = format!("{upstream}{path_and_query}|{identity}");
+  for name in [header::ACCEPT, header::ACCEPT_ENCODING, header::ACCEPT_LANGUAGE] {
+    let value = headers.get(&name).and_then(|v| v.to_str().ok()).unwrap_or("");
+    key.push('|');
+    key.push_str(value);
+  }
+  Some(key)
+}
+
 async fn proxy_api(
   State(state): State<AppState>,
   Path(path): Path<String>,
   req: Request<Body>,
-) -> Result<Response, StatusCode> {
-  let (backend_base_url, bearer_token) = {
+) -> Result<Response, GatewayError> {
+  let path_and_query = req.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("");
+  match coalesce_key(&state, req.method(), path_and_query, req.headers())? {
+    Some(key) => {
+      let shared = state.clone();
+      Ok(
+        state
+          .coalescer
+          .run(key, move || async move { forward_api(shared, path, req).await.into_response() })
+          .await,
+      )
+    }
+    None => forward_api(state, path, req).await,
+  }
+}
+
+async fn forward_api(
+  state: AppState,
+  path: String,
+  req: Request<Body>,
+) -> Result<Response, GatewayError> {
+  let (backend_base_url, upstream_base_url, bearer_token, cache_scope) = {
     let cfg = state
       .config
       .read()
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    (cfg.backend_base_url.clone(), cfg.bearer_token.clone())
+      .map_err(|_| GatewayError::Internal("gateway state poisoned".to_string()))?;
+    (
+      cfg.backend_base_url.clone(),
+      cfg.upstream_base_url(),
+      cfg.bearer_token.clone(),
+      cfg.cache_scope(),
+    )
   };
 
-  let backend_base_url = backend_base_url.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+  let backend_base_url = backend_base_url.ok_or(GatewayError::BackendNotConfigured)?;
+  let upstream_base_url = upstream_base_url.ok_or(GatewayError::BackendNotConfigured)?;
+  let cache_scope = cache_scope.unwrap_or_default();
 
   let (parts, body) = req.into_parts();
   let method = parts.method;
@@ -310,26 +981,65 @@ async fn proxy_api(
   let query = parts.uri.query().unwrap_or("");
 
   let trimmed = path.trim_start_matches('/');
-  let mut url = format!("{}/api/{}", backend_base_url, trimmed);
+  if is_credential_api_path(trimmed) {
+    return Err(GatewayError::Forbidden("use the desktop auth commands instead"));
+  }
+  let mut path_and_query = format!("/api/{}", trimmed);
   if !query.is_empty() {
-    url.push('?');
-    url.push_str(query);
+    path_and_query.push('?');
+    path_and_query.push_str(query);
   }
+  let url = format!("{}{}", upstream_base_url, path_and_query);
+  // 缓存按服务器标识寻址：切换访问地址后仍能命中同一份离线副本；不同账户放在各自的子目录
+  let cache_url = format!("{}{}", backend_base_url, path_and_query);
+
+  let cache = match (&state.api_cache, method == Method::GET && is_cacheable_api_path(trimmed)) {
+    (Some(cache), true) => Some(cache.clone()),
+    _ => None,
+  };
+  let cache_key = cache.as_ref().map(|_| ApiCache::key_for(&cache_url));
+  let cached = match (&cache, &cache_key) {
+    (Some(cache), Some(key)) => cache.load(&cache_scope, key).await,
+    _ => None,
+  };
 
   let mut out_headers = reqwest::header::HeaderMap::new();
   copy_request_headers(&headers, &mut out_headers);
 
-  // 统一从网关状态注入 token（前端不需要/也不应该每次都带 Authorization）
-  out_headers.remove(reqwest::header::AUTHORIZATION);
-  if let Some(token) = bearer_token {
-    if !token.trim().is_empty() {
-      let value = format!("Bearer {}", token.trim());
-      if let Ok(hv) = reqwest::header::HeaderValue::from_str(&value) {
-        out_headers.insert(reqwest::header::AUTHORIZATION, hv);
+  // 有缓存且前端没有自带条件头时，由网关发起条件请求；304 时直接复用本地副本
+  let mut injected_conditional = false;
+  if let Some(entry) = &cached {
+    let has_own_conditional = out_headers.contains_key(reqwest::header::IF_NONE_MATCH)
+      || out_headers.contains_key(reqwest::header::IF_MODIFIED_SINCE);
+    if !has_own_conditional {
+      if let Some(hv) = entry
+        .meta
+        .etag
+        .as_deref()
+        .and_then(|v| reqwest::header::HeaderValue::from_str(v).ok())
+      {
+        out_headers.insert(reqwest::header::IF_NONE_MATCH, hv);
+        injected_conditional = true;
+      }
+      if let Some(hv) = entry
+        .meta
+        .last_modified
+        .as_deref()
+        .and_then(|v| reqwest::header::HeaderValue::from_str(v).ok())
+      {
+        out_headers.insert(reqwest::header::IF_MODIFIED_SINCE, hv);
+        injected_conditional = true;
       }
     }
   }
 
+  // 统一从网关状态注入 token（前端不需要/也不应该每次都带 Authorization）
+  out_headers.remove(reqwest::header::AUTHORIZATION);
+  let injected_token = bearer_token.filter(|t| !t.trim().is_empty());
+  if let Some(token) = &injected_token {
+    set_bearer(&mut out_headers, token);
+  }
+
   // 给后端一个明确的客户端标识（不走浏览器 CORS 了，这个头不会再坑你）
   if !out_headers.contains_key("x-pdh-client") {
     let hv = reqwest::header::HeaderValue::from_static("tauri");
@@ -339,28 +1049,133 @@ async fn proxy_api(
     );
   }
 
-  let stream = body.into_data_stream().map(|chunk| {
-    chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-  });
-  let body = reqwest::Body::wrap_stream(stream);
+  // 401 后需要用新 token 重放、连接失败需要重试：可重放的请求先把 body 收进内存；
+  // 登录/刷新接口的 401 本身就是结果，不重放
+  let replay_on_401 = injected_token.is_some() && !is_auth_api_path(trimmed);
+  let policy = retry_policy(&state, &method, &headers);
+  let (replay_body, first_body) = if (replay_on_401 || policy.max_retries > 0)
+    && is_replayable_request(&method, &headers)
+  {
+    let bytes = axum::body::to_bytes(body, MAX_REPLAY_BODY_BYTES)
+      .await
+      .map_err(|_| GatewayError::PayloadTooLarge)?;
+    (Some(bytes.clone()), reqwest::Body::from(bytes))
+  } else {
+    let stream = body.into_data_stream().map(|chunk| {
+      chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    });
+    (None, reqwest::Body::wrap_stream(stream))
+  };
+
+  let inspected = state
+    .inspector
+    .is_enabled()
+    .then(|| inspector::UpstreamRequest::new(&url, &out_headers));
 
-  let resp = state
-    .client
-    .request(method.clone(), url)
-    .headers(out_headers)
-    .body(body)
-    .send()
-    .await
-    .map_err(|_| StatusCode::BAD_GATEWAY)?;
+  // 总超时只给 body 已收进内存的请求；流式上传与 GET（响应可能是下载流）只受连接/读取超时限制，
+  // 否则大文件下载会在总超时处被截断
+  let limited = replay_body.is_some() && method != Method::GET;
+  let build = |headers: reqwest::header::HeaderMap, body: reqwest::Body| {
+    let req = state.client().request(method.clone(), url.clone()).headers(headers).body(body);
+    if limited {
+      state.http.with_request_timeout(req)
+    } else {
+      req
+    }
+  };
+
+  let (mut sent, mut retries) = match &replay_body {
+    Some(bytes) => {
+      retry::send(policy, || build(out_headers.clone(), reqwest::Body::from(bytes.clone()))).await
+    }
+    None => (build(out_headers.clone(), first_body).send().await, 0),
+  };
+
+  if let (Ok(resp), Some(bytes), Some(used), true) = (&sent, &replay_body, &injected_token, replay_on_401) {
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+      if let Some(next) = refresh_after_unauthorized(&state, used).await {
+        set_bearer(&mut out_headers, &next);
+        let (again, n) =
+          retry::send(policy, || build(out_headers.clone(), reqwest::Body::from(bytes.clone()))).await;
+        sent = again;
+        retries += n;
+      }
+    }
+  }
+
+  let resp = match sent {
+    Ok(resp) => resp,
+    Err(e) => {
+      let status = upstream_send_failed(&state, &e);
+      if let Some(entry) = cached {
+        log::warn!(
+          "[gateway] {}upstream unreachable, serving cached {}: {}",
+          request_id::log_prefix(),
+          url,
+          e
+        );
+        return cached_response(entry, &method, "stale").await.map(|r| with_retry_count(r, retries));
+      }
+      return Ok(with_retry_count(status.into_response(), retries));
+    }
+  };
 
   let status = resp.status();
   let upstream_headers = resp.headers().clone();
+  if injected_token.is_some() {
+    adopt_sliding_token(&state, &backend_base_url, &upstream_headers);
+  }
+
+  if let Some(entry) = cached {
+    if status == reqwest::StatusCode::NOT_MODIFIED && injected_conditional {
+      return cached_response(entry, &method, "revalidated").await;
+    }
+    if is_upstream_down_status(status) {
+      log::warn!(
+        "[gateway] {}upstream returned {}, serving cached {}",
+        request_id::log_prefix(),
+        status.as_u16(),
+        url
+      );
+      return cached_response(entry, &method, "stale").await;
+    }
+  }
+
+  let store_to = match (cache, cache_key) {
+    (Some(cache), Some(key)) if is_cacheable_response(status, &upstream_headers) => Some((cache, key)),
+    _ => None,
+  };
+
+  let mut response = if let Some((cache, key)) = store_to {
+    let meta = CachedMeta {
+      url: cache_url.clone(),
+      status: status.as_u16(),
+      headers: upstream_headers
+        .iter()
+        .filter(|(name, _)| is_storable_response_header(name.as_str()))
+        .filter_map(|(name, value)| {
+          value
+            .to_str()
+            .ok()
+            .map(|v| (name.as_str().to_string(), v.to_string()))
+        })
+        .collect(),
+      etag: header_string(&upstream_headers, reqwest::header::ETAG),
+      last_modified: header_string(&upstream_headers, reqwest::header::LAST_MODIFIED),
+      stored_at: api_cache::now_secs(),
+      size: 0,
+    };
+    let body = Body::from_stream(cache.tee(&cache_scope, key, meta, resp.bytes_stream()));
 
-  let mut response = if method == Method::HEAD {
+    Response::builder()
+      .status(status)
+      .body(body)
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
+  } else if method == Method::HEAD {
     Response::builder()
       .status(status)
       .body(Body::empty())
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
   } else {
     let stream = resp.bytes_stream().map(|chunk| {
       chunk.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
@@ -370,7 +1185,7 @@ async fn proxy_api(
     Response::builder()
       .status(status)
       .body(body)
-      .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+      .map_err(|e| GatewayError::Internal(e.to_string()))?
   };
 
   copy_response_headers(&upstream_headers, response.headers_mut());
@@ -378,46 +1193,293 @@ async fn proxy_api(
     HeaderName::from_static("cross-origin-resource-policy"),
     HeaderValue::from_static("cross-origin"),
   );
-  Ok(response)
+  if let Some(record) = inspected {
+    response.extensions_mut().insert(record);
+  }
+  Ok(with_retry_count(response, retries))
 }
 
-pub async fn start_gateway(
-  config: Arc<RwLock<GatewayConfig>>,
-) -> Result<(SocketAddr, tokio::task::JoinHandle<()>), String> {
-  let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+async fn serve_wallpaper(
+  State(state): State<AppState>,
+  Path(name): Path<String>,
+  method: Method,
+) -> Result<Response, GatewayError> {
+  let path = crate::local_data::wallpaper_file_path(&state.app, &name)
+    .map_err(|_| GatewayError::NotFound("wallpaper not found"))?;
+  let file = tokio::fs::File::open(&path)
     .await
-    .map_err(|e| format!("bind gateway failed: {e}"))?;
-
-  let addr = listener
-    .local_addr()
-    .map_err(|e| format!("get local addr failed: {e}"))?;
+    .map_err(|_| GatewayError::NotFound("wallpaper not found"))?;
+  let len = file
+    .metadata()
+    .await
+    .map_err(|e| GatewayError::Internal(e.to_string()))?
+    .len();
+  let mime = mime_guess::from_path(&path).first_or_octet_stream();
 
-  let state = AppState {
-    client: Client::new(),
-    config,
+  let body = if method == Method::HEAD {
+    Body::empty()
+  } else {
+    Body::from_stream(ReaderStream::new(file))
   };
 
-  let cors = CorsLayer::new()
-    .allow_origin(Any)
+  Response::builder()
+    .status(StatusCode::OK)
+    .header(header::CONTENT_TYPE, mime.as_ref())
+    .header(header::CONTENT_LENGTH, len)
+    // 同名文件可能被替换，每次都让 WebView 重新验证
+    .header(header::CACHE_CONTROL, "no-cache")
+    .header("cross-origin-resource-policy", "cross-origin")
+    .body(body)
+    .map_err(|e| GatewayError::Internal(e.to_string()))
+}
+
+fn cors_layer() -> CorsLayer {
+  CorsLayer::new()
+    .allow_origin(AllowOrigin::predicate(|origin, _| is_allowed_origin(origin)))
     .allow_methods(Any)
     .allow_headers(Any)
-    .expose_headers(Any);
+    .expose_headers(Any)
+}
+
+async fn inspect(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+  inspector::inspect_exchange(state.inspector.clone(), state.app.clone(), req, next).await
+}
+
+async fn track_metrics(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+  metrics::track(state.metrics.clone(), req, next).await
+}
+
+async fn serve_metrics(State(state): State<AppState>) -> Response {
+  let mut response = Response::new(Body::from(state.metrics.render_prometheus()));
+  response.headers_mut().insert(
+    header::CONTENT_TYPE,
+    HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+  );
+  response
+}
 
-  let app = Router::new()
+fn gateway_routes(state: &AppState) -> Router<AppState> {
+  Router::new()
     .route(
       "/attachments/:id/thumb",
       get(proxy_attachment_thumb).head(proxy_attachment_thumb),
     )
     .route("/attachments/:id", get(proxy_attachment).head(proxy_attachment))
+    .route("/wallpapers/:name", get(serve_wallpaper).head(serve_wallpaper))
     .route("/health", get(proxy_health).head(proxy_health))
     .route("/api/*path", any(proxy_api))
-    .layer(cors)
-    .with_state(state);
+    .route("/metrics", get(serve_metrics))
+    .layer(middleware::from_fn_with_state(state.clone(), inspect))
+    .layer(middleware::from_fn_with_state(state.clone(), track_metrics))
+    // 最外层：检查器与错误日志都能看到同一个请求 ID
+    .layer(middleware::from_fn(request_id::propagate))
+}
+
+/// 本地网关：同一套路由既挂在 pdh:// 自定义协议上，也可以额外监听 127.0.0.1 的随机端口。
+#[derive(Clone)]
+pub struct Gateway {
+  state: AppState,
+  scheme: Router,
+}
+
+/// 网关与 Tauri 命令共享的运行时状态。
+pub struct GatewayDeps {
+  pub config: Arc<RwLock<GatewayConfig>>,
+  pub http: Arc<HttpClient>,
+  pub session: Arc<SessionRefresher>,
+  pub health: Arc<HealthMonitor>,
+  pub inspector: Arc<GatewayInspector>,
+  pub metrics: Arc<GatewayMetrics>,
+  pub api_cache: Option<Arc<ApiCache>>,
+  pub attachment_cache: Option<Arc<AttachmentCache>>,
+}
 
-  let handle = tokio::spawn(async move {
-    let server = axum::serve(listener, app);
-    let _ = server.await;
-  });
+impl Gateway {
+  pub fn new(app: tauri::AppHandle, deps: GatewayDeps) -> Self {
+    let state = AppState {
+      app,
+      http: deps.http,
+      config: deps.config,
+      session: deps.session,
+      api_cache: deps.api_cache,
+      attachment_cache: deps.attachment_cache,
+      health: deps.health,
+      inspector: deps.inspector,
+      metrics: deps.metrics,
+      coalescer: Arc::new(RequestCoalescer::default()),
+    };
 
-  Ok((addr, handle))
+    let scheme = gateway_routes(&state)
+      .layer(middleware::from_fn(check_origin))
+      .layer(cors_layer())
+      .with_state(state.clone());
+
+    Self { state, scheme }
+  }
+
+  /// 处理一次 pdh:// 请求。自定义协议的 responder 只能一次性交付完整 body，
+  /// 所以响应会先在内存里收齐，但最多 SCHEME_BODY_LIMIT；更大的响应重定向到 TCP 网关
+  /// （`tcp_base` 为带密钥的 http://127.0.0.1:端口/密钥），没有开启 TCP 网关时返回错误。
+  pub async fn handle_scheme_request(
+    &self,
+    req: http::Request<Vec<u8>>,
+    tcp_base: Option<&str>,
+  ) -> http::Response<Vec<u8>> {
+    let (parts, body) = req.into_parts();
+    let path = parts
+      .uri
+      .path_and_query()
+      .map(|p| p.as_str().to_string())
+      .unwrap_or_else(|| "/".to_string());
+    let req = Request::from_parts(parts, Body::from(body));
+
+    let resp = match self.scheme.clone().oneshot(req).await {
+      Ok(resp) => resp,
+      Err(never) => match never {},
+    };
+
+    let (parts, body) = resp.into_parts();
+    let declared = parts
+      .headers
+      .get(header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > SCHEME_BODY_LIMIT) {
+      return scheme_too_large(tcp_base, &path);
+    }
+
+    let mut stream = body.into_data_stream();
+    let mut buf = Vec::with_capacity(declared.unwrap_or(0));
+    while let Some(chunk) = stream.next().await {
+      match chunk {
+        Ok(bytes) if buf.len() + bytes.len() <= SCHEME_BODY_LIMIT => buf.extend_from_slice(&bytes),
+        Ok(_) => return scheme_too_large(tcp_base, &path),
+        Err(e) => {
+          return GatewayError::Upstream(format!("read response body failed: {e}")).into_scheme_response();
+        }
+      }
+    }
+    http::Response::from_parts(parts, buf)
+  }
+
+  /// 额外监听 127.0.0.1 的随机端口，可以流式传输大文件。
+  /// 端口对本机所有进程可见，因此必须带上本次启动的网关密钥。
+  pub async fn serve_tcp(
+    &self,
+    gateway_key: String,
+  ) -> Result<(SocketAddr, tokio::task::JoinHandle<()>), String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+      .await
+      .map_err(|e| format!("bind gateway failed: {e}"))?;
+
+    let addr = listener
+      .local_addr()
+      .map_err(|e| format!("get local addr failed: {e}"))?;
+
+    let guard = GatewayGuard {
+      key: Arc::from(gateway_key.as_str()),
+      port: addr.port(),
+    };
+
+    // 两种携带密钥的方式：/{key}/... 路径前缀（<img src> 等无法加请求头的场景），
+    // 或者根路径 + x-pdh-gateway-key 请求头
+    let routes = gateway_routes(&self.state);
+    let app = Router::new()
+      .nest(&format!("/{gateway_key}"), routes.clone())
+      .merge(routes.route_layer(middleware::from_fn_with_state(
+        guard.clone(),
+        require_key_header,
+      )))
+      .layer(middleware::from_fn_with_state(guard, check_host_and_origin))
+      .layer(cors_layer())
+      .with_state(self.state.clone());
+
+    let handle = tokio::spawn(async move {
+      let server = axum::serve(listener, app);
+      let _ = server.await;
+    });
+
+    Ok((addr, handle))
+  }
+}
+
+/// pdh:// 响应超过内存上限：有 TCP 网关时让 WebView 改用它流式获取（307 保留方法与请求体）。
+fn scheme_too_large(tcp_base: Option<&str>, path: &str) -> http::Response<Vec<u8>> {
+  let Some(base) = tcp_base else {
+    return GatewayError::ResponseTooLarge.into_scheme_response();
+  };
+  let location = format!("{}{}", base.trim_end_matches('/'), path);
+  let mut resp = http::Response::new(Vec::new());
+  *resp.status_mut() = StatusCode::TEMPORARY_REDIRECT;
+  if let Ok(value) = HeaderValue::from_str(&location) {
+    resp.headers_mut().insert(header::LOCATION, value);
+  }
+  resp
+}
+
+/// 网关还没建好时 pdh:// 请求的响应。
+pub fn scheme_not_ready() -> http::Response<Vec<u8>> {
+  GatewayError::NotReady.into_scheme_response()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const UPSTREAM: &str = "https://pdh.example.com";
+
+  fn key(token: Option<&str>, method: Method, headers: &[(HeaderName, &str)]) -> Option<String> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+      map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+    }
+    request_coalesce_key(UPSTREAM, token, &method, "/api/notes?page=1", &map)
+  }
+
+  #[test]
+  fn coalesce_key_only_for_plain_gets() {
+    let k = key(Some("t1"), Method::GET, &[]).unwrap();
+    assert!(k.starts_with("https://pdh.example.com/api/notes?page=1|"));
+    assert!(!k.contains("t1"));
+
+    assert_eq!(key(Some("t1"), Method::POST, &[]), None);
+    assert_eq!(key(Some("t1"), Method::HEAD, &[]), None);
+    assert_eq!(key(Some("t1"), Method::GET, &[(header::RANGE, "bytes=0-1")]), None);
+    assert_eq!(key(Some("t1"), Method::GET, &[(header::IF_NONE_MATCH, "\"abc\"")]), None);
+    assert_eq!(key(Some("t1"), Method::GET, &[(header::IF_MODIFIED_SINCE, "Tue, 01 Oct 2024 00:00:00 GMT")]), None);
+  }
+
+  #[test]
+  fn coalesce_key_separates_identities() {
+    let a = key(Some("t1"), Method::GET, &[]);
+    assert_eq!(a, key(Some("t1"), Method::GET, &[]));
+    assert_ne!(a, key(Some("t2"), Method::GET, &[]));
+    assert_ne!(a, key(None, Method::GET, &[]));
+
+    // 前端自带的 Authorization 优先于网关 token
+    let client = key(Some("t1"), Method::GET, &[(header::AUTHORIZATION, "Bearer other")]);
+    assert_ne!(a, client);
+    assert_eq!(client, key(None, Method::GET, &[(header::AUTHORIZATION, "Bearer other")]));
+  }
+
+  #[test]
+  fn cache_drops_credential_and_hop_by_hop_headers() {
+    for name in ["set-cookie", "Set-Cookie", "x-pdh-auth-token", "www-authenticate", "connection", "transfer-encoding"] {
+      assert!(!is_storable_response_header(name), "{name}");
+    }
+    for name in ["content-type", "etag", "last-modified", "cache-control"] {
+      assert!(is_storable_response_header(name), "{name}");
+    }
+  }
+
+  #[test]
+  fn coalesce_key_varies_on_content_negotiation() {
+    let json = key(Some("t1"), Method::GET, &[(header::ACCEPT, "application/json")]);
+    let html = key(Some("t1"), Method::GET, &[(header::ACCEPT, "text/html")]);
+    assert_ne!(json, html);
+    assert_ne!(
+      key(Some("t1"), Method::GET, &[(header::ACCEPT_LANGUAGE, "zh-CN")]),
+      key(Some("t1"), Method::GET, &[(header::ACCEPT_LANGUAGE, "en")])
+    );
+  }
 }
@@ -0,0 +1,184 @@
+use std::error::Error as _;
+
+use axum::{
+  body::Body,
+  http::{header, HeaderName, HeaderValue, StatusCode},
+  response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+// 网关自己产生的错误响应都带这个头（值为错误码），前端据此区分“网关/网络问题”和“后端返回的错误”
+pub const GATEWAY_ERROR_HEADER: &str = "x-pdh-gateway-error";
+
+/// 网关处理请求失败的原因。渲染为 JSON：
+/// `{ "success": false, "message": ..., "error": { "code", "message", "cause" } }`
+#[derive(Debug)]
+pub enum GatewayError {
+  // 还没有设置服务器地址
+  BackendNotConfigured,
+  // 网关自身尚未就绪（启动中）
+  NotReady,
+  Forbidden(&'static str),
+  BadRequest(&'static str),
+  NotFound(&'static str),
+  PayloadTooLarge,
+  // pdh:// 响应超过内存上限且没有 TCP 网关可以改走
+  ResponseTooLarge,
+  // 以下为连接上游失败，cause 为完整的错误链
+  Dns(String),
+  Connect(String),
+  Tls(String),
+  Timeout(String),
+  Upstream(String),
+  Internal(String),
+}
+
+impl GatewayError {
+  pub fn code(&self) -> &'static str {
+    match self {
+      GatewayError::BackendNotConfigured => "backend_not_configured",
+      GatewayError::NotReady => "gateway_not_ready",
+      GatewayError::Forbidden(_) => "forbidden",
+      GatewayError::BadRequest(_) => "bad_request",
+      GatewayError::NotFound(_) => "not_found",
+      GatewayError::PayloadTooLarge => "payload_too_large",
+      GatewayError::ResponseTooLarge => "response_too_large",
+      GatewayError::Dns(_) => "dns_error",
+      GatewayError::Connect(_) => "connect_failed",
+      GatewayError::Tls(_) => "tls_error",
+      GatewayError::Timeout(_) => "upstream_timeout",
+      GatewayError::Upstream(_) => "upstream_error",
+      GatewayError::Internal(_) => "internal_error",
+    }
+  }
+
+  pub fn status(&self) -> StatusCode {
+    match self {
+      GatewayError::BackendNotConfigured | GatewayError::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+      GatewayError::Forbidden(_) => StatusCode::FORBIDDEN,
+      GatewayError::BadRequest(_) => StatusCode::BAD_REQUEST,
+      GatewayError::NotFound(_) => StatusCode::NOT_FOUND,
+      GatewayError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+      GatewayError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+      GatewayError::ResponseTooLarge => StatusCode::INSUFFICIENT_STORAGE,
+      GatewayError::Dns(_) | GatewayError::Connect(_) | GatewayError::Tls(_) | GatewayError::Upstream(_) => {
+        StatusCode::BAD_GATEWAY
+      }
+      GatewayError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+
+  pub fn message(&self) -> &'static str {
+    match self {
+      GatewayError::BackendNotConfigured => "未设置服务器地址",
+      GatewayError::NotReady => "本地网关尚未就绪",
+      GatewayError::Forbidden(msg) | GatewayError::BadRequest(msg) | GatewayError::NotFound(msg) => msg,
+      GatewayError::PayloadTooLarge => "请求体过大",
+      GatewayError::ResponseTooLarge => "响应过大，请开启本机端口网关或使用分段请求",
+      GatewayError::Dns(_) => "无法解析服务器域名",
+      GatewayError::Connect(_) => "无法连接到服务器",
+      GatewayError::Tls(_) => "与服务器的 TLS 握手失败（证书不受信任或不匹配）",
+      GatewayError::Timeout(_) => "服务器响应超时",
+      GatewayError::Upstream(_) => "与服务器通信失败",
+      GatewayError::Internal(_) => "网关内部错误",
+    }
+  }
+
+  pub fn cause(&self) -> Option<&str> {
+    match self {
+      GatewayError::Dns(c)
+      | GatewayError::Connect(c)
+      | GatewayError::Tls(c)
+      | GatewayError::Timeout(c)
+      | GatewayError::Upstream(c)
+      | GatewayError::Internal(c) => Some(c),
+      _ => None,
+    }
+  }
+
+  /// 按错误链归类 reqwest 的发送错误。
+  pub fn from_reqwest(err: &reqwest::Error) -> Self {
+    let cause = error_chain(err);
+    if err.is_timeout() {
+      return GatewayError::Timeout(cause);
+    }
+    if is_tls_error(err) {
+      return GatewayError::Tls(cause);
+    }
+    if err.is_connect() {
+      let lower = cause.to_ascii_lowercase();
+      if lower.contains("dns error") || lower.contains("failed to lookup address") {
+        return GatewayError::Dns(cause);
+      }
+      return GatewayError::Connect(cause);
+    }
+    GatewayError::Upstream(cause)
+  }
+
+  fn body(&self) -> Vec<u8> {
+    json!({
+      "success": false,
+      "message": self.message(),
+      "error": {
+        "code": self.code(),
+        "message": self.message(),
+        "cause": self.cause(),
+      },
+    })
+    .to_string()
+    .into_bytes()
+  }
+
+  /// pdh:// 协议在 axum 之外的失败路径（网关未就绪、收取响应体失败或过大）使用。
+  pub fn into_scheme_response(self) -> axum::http::Response<Vec<u8>> {
+    let mut resp = axum::http::Response::new(self.body());
+    *resp.status_mut() = self.status();
+    set_error_headers(resp.headers_mut(), self.code());
+    resp
+  }
+}
+
+fn set_error_headers(headers: &mut axum::http::HeaderMap, code: &'static str) {
+  headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json; charset=utf-8"));
+  headers.insert(HeaderName::from_static(GATEWAY_ERROR_HEADER), HeaderValue::from_static(code));
+  headers.insert(
+    HeaderName::from_static("cross-origin-resource-policy"),
+    HeaderValue::from_static("cross-origin"),
+  );
+}
+
+impl IntoResponse for GatewayError {
+  fn into_response(self) -> Response {
+    if let Some(cause) = self.cause() {
+      log::warn!("[gateway] {}{}: {}", crate::request_id::log_prefix(), self.code(), cause);
+    }
+    let mut resp = Response::new(Body::from(self.body()));
+    *resp.status_mut() = self.status();
+    set_error_headers(resp.headers_mut(), self.code());
+    resp
+  }
+}
+
+/// 把错误链拼成一行，底层原因（证书、DNS 等）通常在最里层。
+pub fn error_chain(err: &reqwest::Error) -> String {
+  let mut msg = err.to_string();
+  let mut source = err.source();
+  while let Some(e) = source {
+    msg.push_str(": ");
+    msg.push_str(&e.to_string());
+    source = e.source();
+  }
+  msg
+}
+
+pub fn is_tls_error(err: &reqwest::Error) -> bool {
+  let mut source = err.source();
+  while let Some(e) = source {
+    let msg = e.to_string().to_ascii_lowercase();
+    if msg.contains("certificate") || msg.contains("tls") || msg.contains("handshake") {
+      return true;
+    }
+    source = e.source();
+  }
+  false
+}
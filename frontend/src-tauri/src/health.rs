@@ -0,0 +1,263 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex, RwLock},
+  time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::Emitter;
+use tokio::sync::Notify;
+
+use crate::api_cache::now_secs;
+use crate::failover;
+use crate::gateway::GatewayConfig;
+use crate::gateway_error::is_tls_error;
+use crate::net::HttpClient;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const HISTORY_LEN: usize = 60;
+// 被唤醒后稍等再探测，避免离线时每个失败请求都触发一次探测
+const WAKE_DEBOUNCE: Duration = Duration::from_secs(1);
+// /health 超过这个延迟视为“降级”
+const DEGRADED_LATENCY_MS: u64 = 1500;
+// 连续失败达到这个次数才判定离线，偶发一次失败只算降级
+const OFFLINE_AFTER_FAILURES: u32 = 3;
+
+const ONLINE_INTERVAL: Duration = Duration::from_secs(30);
+const DEGRADED_INTERVAL: Duration = Duration::from_secs(10);
+const OFFLINE_MIN_INTERVAL: Duration = Duration::from_secs(5);
+const OFFLINE_MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+  Unknown,
+  Online,
+  Degraded,
+  Offline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthSample {
+  pub at: u64,
+  pub endpoint: String,
+  pub ok: bool,
+  pub status: Option<u16>,
+  pub latency_ms: Option<u64>,
+  pub error: Option<String>,
+  pub tls_error: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GatewayStatus {
+  pub state: ConnectionState,
+  pub backend: Option<String>,
+  pub endpoint: Option<String>,
+  pub latency_ms: Option<u64>,
+  pub consecutive_failures: u32,
+  pub last_ok_at: Option<u64>,
+  pub last_error: Option<String>,
+  pub tls_error: bool,
+  pub checked_at: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub history: Option<Vec<HealthSample>>,
+}
+
+struct StatusInner {
+  state: ConnectionState,
+  backend: Option<String>,
+  consecutive_failures: u32,
+  last_ok_at: Option<u64>,
+  history: VecDeque<HealthSample>,
+}
+
+/// 后端连通性监测：按自适应间隔探测 /health，每次探测后发出 `pdh-gateway-status` 事件。
+/// 服务器有多个访问地址时同一轮探测全部地址，结果交给 failover 选择使用的地址。
+pub struct HealthMonitor {
+  inner: Mutex<StatusInner>,
+  wake: Notify,
+}
+
+impl Default for HealthMonitor {
+  fn default() -> Self {
+    Self {
+      inner: Mutex::new(StatusInner {
+        state: ConnectionState::Unknown,
+        backend: None,
+        consecutive_failures: 0,
+        last_ok_at: None,
+        history: VecDeque::with_capacity(HISTORY_LEN),
+      }),
+      wake: Notify::new(),
+    }
+  }
+}
+
+impl HealthMonitor {
+  /// 切换服务器或请求连不上上游时调用：立即重新探测。
+  pub fn wake(&self) {
+    self.wake.notify_one();
+  }
+
+  pub fn status(&self, with_history: bool) -> GatewayStatus {
+    let inner = match self.inner.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    let last = inner.history.back();
+    GatewayStatus {
+      state: inner.state,
+      backend: inner.backend.clone(),
+      endpoint: last.map(|s| s.endpoint.clone()),
+      latency_ms: last.and_then(|s| s.latency_ms),
+      consecutive_failures: inner.consecutive_failures,
+      last_ok_at: inner.last_ok_at,
+      last_error: last.and_then(|s| s.error.clone()),
+      tls_error: last.map(|s| s.tls_error).unwrap_or(false),
+      checked_at: last.map(|s| s.at),
+      history: with_history.then(|| inner.history.iter().cloned().collect()),
+    }
+  }
+
+  fn record(&self, backend: Option<String>, sample: Option<HealthSample>) -> ConnectionState {
+    let mut inner = match self.inner.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+
+    // 换了服务器：旧服务器的历史没有参考价值
+    if inner.backend != backend {
+      inner.backend = backend;
+      inner.consecutive_failures = 0;
+      inner.last_ok_at = None;
+      inner.history.clear();
+    }
+
+    let Some(sample) = sample else {
+      inner.state = ConnectionState::Unknown;
+      return inner.state;
+    };
+
+    if sample.ok {
+      inner.consecutive_failures = 0;
+      inner.last_ok_at = Some(sample.at);
+      inner.state = if sample.latency_ms.unwrap_or(0) > DEGRADED_LATENCY_MS {
+        ConnectionState::Degraded
+      } else {
+        ConnectionState::Online
+      };
+    } else {
+      inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+      inner.state = if inner.consecutive_failures >= OFFLINE_AFTER_FAILURES {
+        ConnectionState::Offline
+      } else {
+        ConnectionState::Degraded
+      };
+    }
+
+    if inner.history.len() >= HISTORY_LEN {
+      inner.history.pop_front();
+    }
+    inner.history.push_back(sample);
+    inner.state
+  }
+
+  fn next_interval(&self, state: ConnectionState) -> Duration {
+    match state {
+      ConnectionState::Online => ONLINE_INTERVAL,
+      ConnectionState::Degraded => DEGRADED_INTERVAL,
+      ConnectionState::Unknown => ONLINE_INTERVAL,
+      ConnectionState::Offline => {
+        // 离线时指数退避：5s, 10s, 20s ... 最多 60s
+        let failures = self
+          .inner
+          .lock()
+          .map(|i| i.consecutive_failures)
+          .unwrap_or(OFFLINE_AFTER_FAILURES);
+        let exp = failures.saturating_sub(OFFLINE_AFTER_FAILURES).min(4);
+        (OFFLINE_MIN_INTERVAL * 2u32.pow(exp)).min(OFFLINE_MAX_INTERVAL)
+      }
+    }
+  }
+}
+
+/// 探测一个访问地址的 /health。
+pub async fn probe(client: &reqwest::Client, endpoint: &str) -> HealthSample {
+  let started = Instant::now();
+  let result = client
+    .get(format!("{}/health", endpoint))
+    .timeout(PROBE_TIMEOUT)
+    .send()
+    .await;
+  let latency_ms = started.elapsed().as_millis() as u64;
+
+  let mut sample = HealthSample {
+    at: now_secs(),
+    endpoint: endpoint.to_string(),
+    ok: false,
+    status: None,
+    latency_ms: None,
+    error: None,
+    tls_error: false,
+  };
+
+  match result {
+    Ok(resp) => {
+      sample.status = Some(resp.status().as_u16());
+      sample.latency_ms = Some(latency_ms);
+      if resp.status().is_success() {
+        sample.ok = true;
+      } else {
+        sample.error = Some(format!("status {}", resp.status().as_u16()));
+      }
+    }
+    Err(e) => {
+      sample.tls_error = is_tls_error(&e);
+      sample.error = Some(e.to_string());
+    }
+  }
+  sample
+}
+
+pub fn spawn(
+  app: tauri::AppHandle,
+  config: Arc<RwLock<GatewayConfig>>,
+  http: Arc<HttpClient>,
+  monitor: Arc<HealthMonitor>,
+) {
+  tauri::async_runtime::spawn(async move {
+    loop {
+      let client = http.get();
+      let (backend, endpoints, upstream) = config
+        .read()
+        .map(|cfg| (cfg.backend_base_url.clone(), cfg.endpoints.clone(), cfg.upstream_base_url()))
+        .unwrap_or((None, Vec::new(), None));
+
+      let sample = match (&backend, &upstream) {
+        // 多个访问地址：一起探测，选出地址后记录正在使用的那个
+        (Some(backend), Some(current)) if endpoints.len() > 1 => {
+          let samples = futures_util::future::join_all(endpoints.iter().map(|ep| probe(&client, ep))).await;
+          let active = failover::apply(&app, &config, backend, &samples, Some(current.as_str()))
+            .unwrap_or_else(|| current.clone());
+          match samples.iter().position(|s| s.endpoint == active) {
+            Some(i) => samples.into_iter().nth(i),
+            None => Some(probe(&client, &active).await),
+          }
+        }
+        (_, Some(endpoint)) => Some(probe(&client, endpoint).await),
+        _ => None,
+      };
+      let state = monitor.record(backend, sample);
+      let _ = app.emit("pdh-gateway-status", monitor.status(false));
+
+      tokio::select! {
+        _ = tokio::time::sleep(monitor.next_interval(state)) => {}
+        _ = monitor.wake.notified() => {
+          tokio::time::sleep(WAKE_DEBOUNCE).await;
+        }
+      }
+    }
+  });
+}
@@ -0,0 +1,240 @@
+use std::{
+  collections::VecDeque,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex,
+  },
+  time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+  body::Body,
+  http::{HeaderMap, Request},
+  middleware::Next,
+  response::Response,
+};
+use futures_util::StreamExt;
+use serde::Serialize;
+use tauri::Emitter;
+
+const CAPACITY: usize = 200;
+
+// 这些头可能携带凭据：只记录“存在”，不记录值
+const REDACTED_HEADERS: &[&str] = &[
+  "authorization",
+  "proxy-authorization",
+  "cookie",
+  "set-cookie",
+  "x-pdh-auth-token",
+  crate::gateway::GATEWAY_KEY_HEADER,
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InspectEntry {
+  pub id: u64,
+  // 毫秒时间戳
+  pub started_at: u64,
+  pub method: String,
+  pub path: String,
+  pub status: u16,
+  // 收到响应头的耗时
+  pub duration_ms: u64,
+  // 响应体传输完毕（或被客户端中断）的耗时
+  pub total_ms: u64,
+  pub request_bytes: Option<u64>,
+  pub response_bytes: u64,
+  // 响应体是否完整传完；客户端中途取消（如视频拖动）时为 false
+  pub completed: bool,
+  pub request_headers: Vec<(String, String)>,
+  pub response_headers: Vec<(String, String)>,
+  // 网关实际发往后端的请求（仅 /api 代理）
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub upstream: Option<UpstreamRequest>,
+}
+
+/// proxy_api 通过响应扩展把实际发往后端的请求交给检查器。
+/// Authorization 整个不记录，只标记是否由网关注入了 token。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamRequest {
+  pub url: String,
+  pub headers: Vec<(String, String)>,
+  pub token_injected: bool,
+}
+
+impl UpstreamRequest {
+  pub fn new(url: &str, headers: &HeaderMap) -> Self {
+    let mut without_auth = headers.clone();
+    let token_injected = without_auth.remove(axum::http::header::AUTHORIZATION).is_some();
+    Self {
+      url: url.to_string(),
+      headers: redacted_headers(&without_auth),
+      token_injected,
+    }
+  }
+}
+
+/// 网关请求检查器：开启后在内存中保留最近的请求记录，并实时发出 `pdh-gateway-inspect` 事件。
+/// 默认关闭，不落盘；只记录 WebView 发给网关的请求，网关注入的 Authorization 不经过这里。
+#[derive(Default)]
+pub struct GatewayInspector {
+  enabled: AtomicBool,
+  next_id: AtomicU64,
+  entries: Mutex<VecDeque<InspectEntry>>,
+}
+
+impl GatewayInspector {
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Relaxed)
+  }
+
+  pub fn set_enabled(&self, enabled: bool) {
+    self.enabled.store(enabled, Ordering::Relaxed);
+  }
+
+  pub fn list(&self) -> Vec<InspectEntry> {
+    match self.entries.lock() {
+      Ok(guard) => guard.iter().cloned().collect(),
+      Err(poisoned) => poisoned.into_inner().iter().cloned().collect(),
+    }
+  }
+
+  pub fn clear(&self) {
+    match self.entries.lock() {
+      Ok(mut guard) => guard.clear(),
+      Err(poisoned) => poisoned.into_inner().clear(),
+    }
+  }
+
+  fn push(&self, app: &tauri::AppHandle, entry: InspectEntry) {
+    // 记录过程中被关闭：丢弃
+    if !self.is_enabled() {
+      return;
+    }
+    let _ = app.emit("pdh-gateway-inspect", &entry);
+    let mut guard = match self.entries.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    if guard.len() >= CAPACITY {
+      guard.pop_front();
+    }
+    guard.push_back(entry);
+  }
+}
+
+fn redacted_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+  headers
+    .iter()
+    .map(|(name, value)| {
+      let name = name.as_str().to_ascii_lowercase();
+      let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+        "[redacted]".to_string()
+      } else {
+        String::from_utf8_lossy(value.as_bytes()).to_string()
+      };
+      (name, value)
+    })
+    .collect()
+}
+
+fn now_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_millis() as u64)
+    .unwrap_or(0)
+}
+
+/// 响应体被完整读取或中途丢弃时写入记录。
+struct PendingEntry {
+  inspector: Arc<GatewayInspector>,
+  app: tauri::AppHandle,
+  entry: Option<InspectEntry>,
+  started: Instant,
+}
+
+impl Drop for PendingEntry {
+  fn drop(&mut self) {
+    if let Some(mut entry) = self.entry.take() {
+      entry.total_ms = self.started.elapsed().as_millis() as u64;
+      self.inspector.push(&self.app, entry);
+    }
+  }
+}
+
+/// 网关路由的中间件。关闭时直接放行，不做任何额外处理。
+pub async fn inspect_exchange(
+  inspector: Arc<GatewayInspector>,
+  app: tauri::AppHandle,
+  req: Request<Body>,
+  next: Next,
+) -> Response {
+  if !inspector.is_enabled() {
+    return next.run(req).await;
+  }
+
+  let started = Instant::now();
+  let started_at = now_millis();
+  let method = req.method().to_string();
+  // 路由层看到的路径已去掉 TCP 模式下的密钥前缀
+  let path = req
+    .uri()
+    .path_and_query()
+    .map(|pq| pq.as_str().to_string())
+    .unwrap_or_else(|| req.uri().path().to_string());
+  let request_bytes = req
+    .headers()
+    .get(axum::http::header::CONTENT_LENGTH)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.parse::<u64>().ok());
+  let request_headers = redacted_headers(req.headers());
+
+  let response = next.run(req).await;
+  let (mut parts, body) = response.into_parts();
+  let upstream = parts.extensions.remove::<UpstreamRequest>();
+
+  let pending = PendingEntry {
+    inspector: inspector.clone(),
+    app,
+    entry: Some(InspectEntry {
+      id: inspector.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+      started_at,
+      method,
+      path,
+      status: parts.status.as_u16(),
+      duration_ms: started.elapsed().as_millis() as u64,
+      total_ms: 0,
+      request_bytes,
+      response_bytes: 0,
+      completed: false,
+      request_headers,
+      response_headers: redacted_headers(&parts.headers),
+      upstream,
+    }),
+    started,
+  };
+
+  // 流结束（或响应被丢弃）时 pending 随之释放，在 Drop 里写入记录
+  let counted = futures_util::stream::unfold(
+    (body.into_data_stream(), pending),
+    |(mut stream, mut pending)| async move {
+      match stream.next().await {
+        Some(chunk) => {
+          if let (Ok(bytes), Some(entry)) = (&chunk, pending.entry.as_mut()) {
+            entry.response_bytes += bytes.len() as u64;
+          }
+          Some((chunk, (stream, pending)))
+        }
+        None => {
+          if let Some(entry) = pending.entry.as_mut() {
+            entry.completed = true;
+          }
+          None
+        }
+      }
+    },
+  );
+
+  Response::from_parts(parts, Body::from_stream(counted))
+}
@@ -1,8 +1,26 @@
+mod api_cache;
+mod attachment_cache;
+mod bundle;
+mod coalesce;
+mod diagnostics;
+mod error;
+mod failover;
 mod gateway;
+mod gateway_error;
+mod health;
+mod inspector;
 mod local_data;
+mod logging;
+mod metrics;
+mod net;
+mod request_id;
+mod retry;
+mod session;
+mod token_lifecycle;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use tauri::Emitter;
 use tauri::Manager;
@@ -12,18 +30,45 @@ use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::sync::{Mutex, watch};
 use tokio_util::io::ReaderStream;
 
+use crate::error::{ErrorCode, PdhError, PdhResult};
+
 struct GatewayState {
   config: Arc<RwLock<gateway::GatewayConfig>>,
   addr: Arc<RwLock<Option<std::net::SocketAddr>>>,
   upload_tasks: Arc<Mutex<HashMap<String, UploadTaskHandle>>>,
+  attachment_cache: Arc<RwLock<Option<Arc<attachment_cache::AttachmentCache>>>>,
+  api_cache: Arc<RwLock<Option<Arc<api_cache::ApiCache>>>>,
+  session: Arc<session::SessionRefresher>,
+  token_lifecycle: Arc<token_lifecycle::TokenLifecycle>,
+  health: Arc<health::HealthMonitor>,
+  http: Arc<net::HttpClient>,
+  inspector: Arc<inspector::GatewayInspector>,
+  metrics: Arc<metrics::GatewayMetrics>,
+  // 本次启动的网关密钥：只通过 pdh_gateway_url 交给 WebView
+  gateway_key: String,
+  // pdh:// 自定义协议使用的网关；setup 完成前为 None
+  gateway: Arc<RwLock<Option<gateway::Gateway>>>,
+  tcp_gateway_enabled: AtomicBool,
 }
 
 impl Default for GatewayState {
   fn default() -> Self {
+    let token_lifecycle = Arc::new(token_lifecycle::TokenLifecycle::default());
     Self {
       config: Arc::new(RwLock::new(gateway::GatewayConfig::default())),
       addr: Arc::new(RwLock::new(None)),
       upload_tasks: Arc::new(Mutex::new(HashMap::new())),
+      attachment_cache: Arc::new(RwLock::new(None)),
+      api_cache: Arc::new(RwLock::new(None)),
+      session: Arc::new(session::SessionRefresher::new(token_lifecycle.clone())),
+      token_lifecycle,
+      health: Arc::new(health::HealthMonitor::default()),
+      http: Arc::new(net::HttpClient::default()),
+      inspector: Arc::new(inspector::GatewayInspector::default()),
+      metrics: Arc::new(metrics::GatewayMetrics::default()),
+      gateway_key: gateway::generate_gateway_key(),
+      gateway: Arc::new(RwLock::new(None)),
+      tcp_gateway_enabled: AtomicBool::new(true),
     }
   }
 }
@@ -43,8 +88,8 @@ struct UploadTaskHandle {
 const KEYRING_SERVICE: &str = "personal-data-hub";
 const KEYRING_ACCOUNT_REFRESH_LEGACY: &str = "refresh-token";
 
-fn refresh_token_entry(account: &str) -> Result<keyring::Entry, String> {
-  keyring::Entry::new(KEYRING_SERVICE, account).map_err(|e| e.to_string())
+fn refresh_token_entry(account: &str) -> PdhResult<keyring::Entry> {
+  Ok(keyring::Entry::new(KEYRING_SERVICE, account)?)
 }
 
 fn is_no_entry_error(err: &keyring::Error) -> bool {
@@ -58,6 +103,15 @@ fn refresh_token_account_for_backend(backend_base_url: &str) -> String {
   format!("refresh-token|{}", backend_base_url.trim().trim_end_matches('/'))
 }
 
+fn refresh_token_account_for_user(backend_base_url: &str, username: &str) -> String {
+  // 按服务器 + 用户隔离：同一服务器上的多个账户各自保留会话，切换时不必重新输入密码
+  format!(
+    "refresh-token|{}|{}",
+    backend_base_url.trim().trim_end_matches('/'),
+    username.trim()
+  )
+}
+
 fn password_account_for_backend(backend_base_url: &str, username: &str) -> String {
   format!(
     "password|{}|{}",
@@ -66,7 +120,136 @@ fn password_account_for_backend(backend_base_url: &str, username: &str) -> Strin
   )
 }
 
-fn load_refresh_token_for_backend(backend_base_url: &str) -> Result<Option<String>, String> {
+fn client_key_account_for_backend(backend_base_url: &str) -> String {
+  format!("client-key|{}", backend_base_url.trim().trim_end_matches('/'))
+}
+
+fn load_client_key_for_backend(backend_base_url: &str) -> PdhResult<Option<String>> {
+  let entry = refresh_token_entry(&client_key_account_for_backend(backend_base_url))?;
+  match entry.get_password() {
+    Ok(pem) => Ok(if pem.trim().is_empty() { None } else { Some(pem) }),
+    Err(err) => {
+      if is_no_entry_error(&err) {
+        Ok(None)
+      } else {
+        Err(err.into())
+      }
+    }
+  }
+}
+
+fn store_client_key_for_backend(backend_base_url: &str, pem: Option<String>) -> PdhResult<()> {
+  let entry = refresh_token_entry(&client_key_account_for_backend(backend_base_url))?;
+  match pem.filter(|p| !p.trim().is_empty()) {
+    Some(p) => entry.set_password(&p).map_err(PdhError::from),
+    None => {
+      let _ = entry.delete_password();
+      Ok(())
+    }
+  }
+}
+
+fn proxy_password_account_for_backend(backend_base_url: &str) -> String {
+  format!("proxy-password|{}", backend_base_url.trim().trim_end_matches('/'))
+}
+
+fn load_proxy_password_for_backend(backend_base_url: &str) -> PdhResult<Option<String>> {
+  let entry = refresh_token_entry(&proxy_password_account_for_backend(backend_base_url))?;
+  match entry.get_password() {
+    Ok(pwd) => Ok(if pwd.is_empty() { None } else { Some(pwd) }),
+    Err(err) => {
+      if is_no_entry_error(&err) {
+        Ok(None)
+      } else {
+        Err(err.into())
+      }
+    }
+  }
+}
+
+fn store_proxy_password_for_backend(backend_base_url: &str, password: Option<String>) -> PdhResult<()> {
+  let entry = refresh_token_entry(&proxy_password_account_for_backend(backend_base_url))?;
+  match password.filter(|p| !p.is_empty()) {
+    Some(p) => entry.set_password(&p).map_err(PdhError::from),
+    None => {
+      let _ = entry.delete_password();
+      Ok(())
+    }
+  }
+}
+
+/// 服务器配置对应的客户端设置（含钥匙串里的客户端私钥和代理密码）。
+fn client_options_for_profile(profile: &local_data::BackendProfile) -> PdhResult<net::ClientOptions> {
+  let client_key_pem = if profile.tls.client_cert_pem.is_some() {
+    load_client_key_for_backend(&profile.url)?
+  } else {
+    None
+  };
+  let proxy_password = if profile.proxy.is_some() {
+    load_proxy_password_for_backend(&profile.url)?
+  } else {
+    None
+  };
+  Ok(net::ClientOptions {
+    tls: profile.tls.clone(),
+    client_key_pem,
+    proxy: profile.proxy.clone(),
+    proxy_password,
+  })
+}
+
+/// 没有对应的服务器配置时使用默认设置。
+fn client_options(profile: Option<&local_data::BackendProfile>) -> PdhResult<net::ClientOptions> {
+  match profile {
+    Some(p) => client_options_for_profile(p),
+    None => Ok(net::ClientOptions::default()),
+  }
+}
+
+/// 读取某个账户的 refresh token；没有用户名时读取按服务器保存的旧条目。
+/// `legacy_owner` 为旧条目的主人（该服务器最近使用的账户），只有它才会继承旧条目。
+fn load_refresh_token_for_backend(
+  backend_base_url: &str,
+  username: Option<&str>,
+  legacy_owner: Option<&str>,
+) -> PdhResult<Option<String>> {
+  let Some(user) = username.map(str::trim).filter(|u| !u.is_empty()) else {
+    return load_backend_scoped_refresh_token(backend_base_url);
+  };
+  let entry = refresh_token_entry(&refresh_token_account_for_user(backend_base_url, user))?;
+  match entry.get_password() {
+    Ok(token) => {
+      let t = token.trim().to_string();
+      Ok(if t.is_empty() { None } else { Some(t) })
+    }
+    Err(err) => {
+      if !is_no_entry_error(&err) {
+        return Err(err.into());
+      }
+      // 升级前按服务器保存的 token 属于当时最后登录的用户：只迁移给该用户，
+      // 其它账户没有自己的条目就是没有会话（否则会以别人的身份登录）
+      if legacy_owner.map(str::trim) != Some(user) {
+        return Ok(None);
+      }
+      let migrated = load_backend_scoped_refresh_token(backend_base_url)?;
+      if let Some(t) = &migrated {
+        if entry.set_password(t).is_ok() {
+          let _ = refresh_token_entry(&refresh_token_account_for_backend(backend_base_url))?.delete_password();
+        }
+      }
+      Ok(migrated)
+    }
+  }
+}
+
+fn has_refresh_token_for_user(backend_base_url: &str, username: &str) -> bool {
+  refresh_token_entry(&refresh_token_account_for_user(backend_base_url, username))
+    .and_then(|entry| entry.get_password().map_err(PdhError::from))
+    .map(|t| !t.trim().is_empty())
+    .unwrap_or(false)
+}
+
+fn load_backend_scoped_refresh_token(backend_base_url: &str) -> PdhResult<Option<String>> {
   let account = refresh_token_account_for_backend(backend_base_url);
   let entry = refresh_token_entry(&account)?;
   match entry.get_password() {
@@ -94,19 +277,31 @@ fn load_refresh_token_for_backend(backend_base_url: &str) -> Result<Option<Strin
             if is_no_entry_error(&legacy_err) {
               Ok(None)
             } else {
-              Err(legacy_err.to_string())
+              Err(legacy_err.into())
             }
           }
         }
       } else {
-        Err(err.to_string())
+        Err(err.into())
       }
     }
   }
 }
 
-fn store_refresh_token_for_backend(backend_base_url: &str, token: Option<String>) -> Result<(), String> {
-  let account = refresh_token_account_for_backend(backend_base_url);
+/// 写入（None 时删除）某个账户的 refresh token；没有用户名时写按服务器保存的条目。
+fn store_refresh_token_for_backend(
+  backend_base_url: &str,
+  username: Option<&str>,
+  token: Option<String>,
+) -> PdhResult<()> {
+  let account = match username.map(str::trim).filter(|u| !u.is_empty()) {
+    Some(user) => {
+      // 按用户保存后，旧的按服务器条目不再需要（留着会被误迁移给别的账户）
+      let _ = refresh_token_entry(&refresh_token_account_for_backend(backend_base_url))?.delete_password();
+      refresh_token_account_for_user(backend_base_url, user)
+    }
+    None => refresh_token_account_for_backend(backend_base_url),
+  };
   let entry = refresh_token_entry(&account)?;
   match token {
     Some(raw) => {
@@ -115,7 +310,7 @@ fn store_refresh_token_for_backend(backend_base_url: &str, token: Option<String>
         let _ = entry.delete_password();
         return Ok(());
       }
-      entry.set_password(&t).map_err(|e| e.to_string())
+      entry.set_password(&t).map_err(PdhError::from)
     }
     None => {
       let _ = entry.delete_password();
@@ -124,191 +319,946 @@ fn store_refresh_token_for_backend(backend_base_url: &str, token: Option<String>
   }
 }
 
+fn delete_password_for_backend(backend_base_url: &str, username: &str) -> PdhResult<()> {
+  let account = password_account_for_backend(backend_base_url, username);
+  let entry = refresh_token_entry(&account)?;
+  let _ = entry.delete_password();
+  Ok(())
+}
+
 #[tauri::command]
 fn pdh_secret_set_password(
+  app: tauri::AppHandle,
   backend_base_url: String,
   username: String,
   password: String,
-) -> Result<(), String> {
+) -> PdhResult<()> {
   let backend = backend_base_url.trim().trim_end_matches('/').to_string();
   if backend.is_empty() {
-    return Err("backend_base_url is empty".to_string());
+    return Err(PdhError::invalid_argument("backend_base_url is empty"));
   }
   let user = username.trim().to_string();
   if user.is_empty() {
-    return Err("username is empty".to_string());
+    return Err(PdhError::invalid_argument("username is empty"));
+  }
+
+  let account = password_account_for_backend(&backend, &user);
+  let entry = refresh_token_entry(&account)?;
+
+  let pwd = password.trim().to_string();
+  if pwd.is_empty() {
+    let _ = entry.delete_password();
+    return Ok(());
+  }
+
+  entry.set_password(&pwd)?;
+  // 记下用户名，删除服务器配置时才能找到这条密码
+  let _ = local_data::record_profile_username(&app, &backend, &user);
+  Ok(())
+}
+
+/// 密码只在 Rust 侧使用（保存的凭据登录），不返回给前端。
+fn load_password_for_backend(backend_base_url: &str, username: &str) -> PdhResult<Option<String>> {
+  let account = password_account_for_backend(backend_base_url, username);
+  let entry = refresh_token_entry(&account)?;
+
+  match entry.get_password() {
+    Ok(pwd) => {
+      let p = pwd.trim().to_string();
+      Ok(if p.is_empty() { None } else { Some(p) })
+    }
+    Err(err) => {
+      if is_no_entry_error(&err) {
+        Ok(None)
+      } else {
+        Err(err.into())
+      }
+    }
+  }
+}
+
+#[tauri::command]
+fn pdh_secret_has_password(backend_base_url: String, username: String) -> PdhResult<bool> {
+  let backend = backend_base_url.trim().trim_end_matches('/').to_string();
+  if backend.is_empty() {
+    return Err(PdhError::invalid_argument("backend_base_url is empty"));
+  }
+  let user = username.trim().to_string();
+  if user.is_empty() {
+    return Err(PdhError::invalid_argument("username is empty"));
+  }
+
+  Ok(load_password_for_backend(&backend, &user)?.is_some())
+}
+
+#[tauri::command]
+fn pdh_secret_delete_password(backend_base_url: String, username: String) -> PdhResult<()> {
+  let backend = backend_base_url.trim().trim_end_matches('/').to_string();
+  if backend.is_empty() {
+    return Err(PdhError::invalid_argument("backend_base_url is empty"));
+  }
+  let user = username.trim().to_string();
+  if user.is_empty() {
+    return Err(PdhError::invalid_argument("username is empty"));
+  }
+
+  delete_password_for_backend(&backend, &user)
+}
+
+/// 带密钥前缀的 TCP 网关地址；未开启或尚未监听时为 None。
+fn tcp_gateway_url(state: &GatewayState) -> Option<String> {
+  let addr = (*state.addr.read().ok()?)?;
+  Some(format!("http://{}/{}", addr, state.gateway_key))
+}
+
+#[tauri::command]
+fn pdh_gateway_url(state: State<GatewayState>) -> PdhResult<String> {
+  if let Some(url) = tcp_gateway_url(&state) {
+    return Ok(url);
+  }
+
+  // 关闭了 TCP 监听时，整个前端都走 pdh:// 自定义协议
+  let scheme_ready = state
+    .gateway
+    .read()
+    .map_err(|_| PdhError::poisoned())?
+    .is_some();
+  if scheme_ready && !state.tcp_gateway_enabled.load(Ordering::Relaxed) {
+    return Ok(gateway::SCHEME_BASE_URL.to_string());
+  }
+  Err(PdhError::new(ErrorCode::NotReady, "gateway not ready"))
+}
+
+pub(crate) fn normalize_backend_url(url: &str) -> PdhResult<Option<String>> {
+  let normalized = url.trim().trim_end_matches('/').to_string();
+  if normalized.is_empty() {
+    return Ok(None);
+  }
+
+  let lower = normalized.to_ascii_lowercase();
+  if !(lower.starts_with("http://") || lower.starts_with("https://")) {
+    return Err(PdhError::invalid_argument("backend url must start with http:// or https://"));
+  }
+
+  Ok(Some(normalized))
+}
+
+#[tauri::command]
+fn pdh_gateway_set_backend_url(app: tauri::AppHandle, state: State<GatewayState>, url: String) -> PdhResult<()> {
+  let normalized = normalize_backend_url(&url)?;
+  // 前端每次启动都会推送一遍：地址没变时保留服务器配置带来的访问地址列表
+  let unchanged = state
+    .config
+    .read()
+    .map_err(|_| PdhError::poisoned())?
+    .backend_base_url
+    == normalized;
+  if unchanged {
+    return Ok(());
+  }
+  // 地址对应已保存的服务器时沿用它的 TLS 设置；读配置文件与钥匙串都在拿写锁之前完成，
+  // 避免钥匙串弹窗等待期间阻塞所有经过网关的请求
+  let profile = match normalized.as_deref() {
+    Some(u) => local_data::find_profile_by_url(&app, u)?,
+    None => None,
+  };
+  let options = client_options(profile.as_ref())?;
+
+  let mut cfg = state
+    .config
+    .write()
+    .map_err(|_| PdhError::poisoned())?;
+  if cfg.backend_base_url == normalized {
+    return Ok(());
+  }
+  state.http.configure(options).map_err(PdhError::invalid_argument)?;
+  cfg.backend_base_url = normalized;
+  cfg.endpoints = Vec::new();
+  cfg.active_endpoint = None;
+  // 换了服务器，旧 token 不能再带过去；会话默认恢复该服务器上最后使用的账户
+  cfg.bearer_token = None;
+  cfg.account = profile.as_ref().and_then(|p| p.last_username.clone());
+  cfg.legacy_owner = cfg.account.clone();
+  state.token_lifecycle.clear();
+  state.health.wake();
+  Ok(())
+}
+
+#[tauri::command]
+fn pdh_gateway_status(state: State<GatewayState>) -> health::GatewayStatus {
+  state.health.status(true)
+}
+
+#[tauri::command]
+async fn pdh_gateway_diagnose(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  url: Option<String>,
+) -> PdhResult<diagnostics::DiagnosticReport> {
+  let (backend, endpoints, upstream, token) = {
+    let cfg = state
+      .config
+      .read()
+      .map_err(|_| PdhError::poisoned())?;
+    (
+      cfg.backend_base_url.clone(),
+      cfg.endpoints.clone(),
+      cfg.upstream_base_url(),
+      cfg.bearer_token.clone(),
+    )
+  };
+
+  // 不传 url 时诊断网关当前使用的地址
+  let target = match url.as_deref().map(normalize_backend_url).transpose()?.flatten() {
+    Some(u) => u,
+    None => upstream.ok_or_else(PdhError::backend_not_configured)?,
+  };
+
+  // 只有诊断当前服务器（含其备用地址）时才带上 token，避免泄露给其它地址
+  let is_current = backend.as_deref() == Some(target.as_str()) || endpoints.contains(&target);
+  let token = if is_current { token } else { None };
+
+  // TLS 设置：当前服务器用网关正在用的；其它地址若是已保存的服务器则用它自己的
+  let profile = match (is_current, backend.as_deref()) {
+    (true, Some(b)) => local_data::find_profile_by_url(&app, b)?,
+    _ => local_data::find_profile_by_url(&app, &target)?,
+  };
+  let tls = match profile.as_ref() {
+    Some(p) => diagnostics::TlsOptions {
+      settings: p.tls.clone(),
+      client_key_pem: load_client_key_for_backend(&p.url)?,
+    },
+    None => diagnostics::TlsOptions::default(),
+  };
+  let client = if is_current {
+    state.http.get()
+  } else {
+    state.http.build(&client_options(profile.as_ref())?).map_err(PdhError::invalid_argument)?
+  };
+
+  Ok(diagnostics::diagnose(app.package_info().version.to_string(), target, token, client, tls).await)
+}
+
+/// 让网关切到指定服务器，并用该服务器保存的 refresh token 恢复会话。
+/// 返回是否恢复成功；没有可用会话时前端会收到 expired 事件。token 只留在网关里。
+async fn activate_profile(
+  app: &tauri::AppHandle,
+  state: &State<'_, GatewayState>,
+  profile: Option<&local_data::BackendProfile>,
+) -> PdhResult<bool> {
+  state.http.configure(client_options(profile)?).map_err(PdhError::invalid_argument)?;
+  state
+    .session
+    .switch_backend(
+      &state.config,
+      profile.map(|p| p.url.clone()),
+      profile.map(|p| p.endpoint_list()).unwrap_or_default(),
+      profile.and_then(|p| p.last_username.clone()),
+    )
+    .await
+    .map_err(PdhError::internal)?;
+  state.health.wake();
+  let _ = app.emit("pdh-profile-switched", json!({ "profile": profile }));
+
+  if profile.is_none() {
+    session::emit_auth_state(app, "expired", json!({ "reason": "no active profile" }));
+    return Ok(false);
+  }
+
+  match state.session.refresh(&state.http, &state.config, None).await {
+    Ok(refreshed) => {
+      session::emit_auth_state(app, "refreshed", json!({ "expiresIn": refreshed.expires_in() }));
+      Ok(true)
+    }
+    Err(e) => {
+      if e.is_final() {
+        session::emit_auth_state(app, "expired", json!({ "reason": e.message() }));
+      }
+      Ok(false)
+    }
+  }
+}
+
+#[tauri::command]
+async fn pdh_profiles_switch(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  id: String,
+) -> PdhResult<serde_json::Value> {
+  let profile = local_data::find_profile(&app, id.trim())?;
+  let snapshot = local_data::set_active_profile(&app, &profile.id)?;
+  let authenticated = activate_profile(&app, &state, Some(&profile)).await?;
+
+  Ok(json!({
+    "profile": profile,
+    "profiles": snapshot,
+    "authenticated": authenticated,
+  }))
+}
+
+#[tauri::command]
+fn pdh_profiles_set_endpoints(
+  app: tauri::AppHandle,
+  state: State<GatewayState>,
+  id: String,
+  endpoints: Vec<String>,
+) -> PdhResult<local_data::BackendProfile> {
+  let profile = local_data::set_profile_endpoints(&app, id.trim(), endpoints)?;
+
+  // 正在使用的服务器：立即生效（当前地址不在新列表里时退回主地址）
+  let mut cfg = state
+    .config
+    .write()
+    .map_err(|_| PdhError::poisoned())?;
+  if cfg.backend_base_url.as_deref() == Some(profile.url.as_str()) {
+    cfg.endpoints = profile.endpoint_list();
+    if let Some(active) = cfg.active_endpoint.clone() {
+      if !cfg.endpoints.contains(&active) {
+        cfg.active_endpoint = None;
+      }
+    }
+    state.health.wake();
+  }
+
+  Ok(profile)
+}
+
+#[tauri::command]
+async fn pdh_profiles_remove(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  id: String,
+) -> PdhResult<local_data::ProfilesSnapshot> {
+  let (removed, snapshot) = local_data::take_profile(&app, id.trim())?;
+
+  // 清理该服务器在钥匙串中的全部条目
+  let _ = store_refresh_token_for_backend(&removed.url, None, None);
+  let _ = store_client_key_for_backend(&removed.url, None);
+  let _ = store_proxy_password_for_backend(&removed.url, None);
+  for user in removed.known_usernames.iter().chain(removed.last_username.iter()) {
+    let _ = store_refresh_token_for_backend(&removed.url, Some(user), None);
+    let _ = delete_password_for_backend(&removed.url, user);
+  }
+
+  // 删除的是网关正在使用的服务器：切到新的当前服务器（没有则清空）
+  let current = backend_base_url_from_state(&state).ok();
+  if current.as_deref() == Some(removed.url.as_str()) {
+    let next = snapshot
+      .active_profile_id
+      .as_ref()
+      .and_then(|id| snapshot.profiles.iter().find(|p| &p.id == id));
+    activate_profile(&app, &state, next).await?;
+  }
+
+  Ok(snapshot)
+}
+
+#[tauri::command]
+fn pdh_profiles_set_tls(
+  app: tauri::AppHandle,
+  state: State<GatewayState>,
+  id: String,
+  ca_pem: Option<String>,
+  pinned_spki_sha256: Vec<String>,
+  client_cert_pem: Option<String>,
+  client_key_pem: Option<String>,
+) -> PdhResult<local_data::BackendProfile> {
+  let profile = local_data::find_profile(&app, id.trim())?;
+  let non_empty = |v: Option<String>| v.filter(|s| !s.trim().is_empty());
+  let tls = net::TlsSettings {
+    ca_pem: non_empty(ca_pem),
+    pinned_spki_sha256: pinned_spki_sha256
+      .into_iter()
+      .map(|p| p.trim().to_string())
+      .filter(|p| !p.is_empty())
+      .collect(),
+    client_cert_pem: non_empty(client_cert_pem),
+  };
+
+  // client_key_pem：None 保留钥匙串中的私钥，空字符串表示删除
+  let mut options = client_options_for_profile(&profile)?;
+  if let Some(pem) = &client_key_pem {
+    options.client_key_pem = non_empty(Some(pem.clone()));
+  } else if options.client_key_pem.is_none() {
+    options.client_key_pem = load_client_key_for_backend(&profile.url)?;
+  }
+  options.tls = tls.clone();
+  // 先校验能构造出客户端，再落盘
+  state.http.build(&options).map_err(PdhError::invalid_argument)?;
+  if client_key_pem.is_some() {
+    store_client_key_for_backend(&profile.url, options.client_key_pem.clone())?;
+  }
+  let profile = local_data::set_profile_tls(&app, &profile.id, tls)?;
+
+  // 正在使用的服务器：立即换用新客户端并重新探测
+  if backend_base_url_from_state(&state).ok().as_deref() == Some(profile.url.as_str()) {
+    state.http.configure(options).map_err(PdhError::invalid_argument)?;
+    state.health.wake();
+  }
+
+  Ok(profile)
+}
+
+#[tauri::command]
+fn pdh_profiles_set_proxy(
+  app: tauri::AppHandle,
+  state: State<GatewayState>,
+  id: String,
+  proxy: Option<net::ProxySettings>,
+  password: Option<String>,
+) -> PdhResult<local_data::BackendProfile> {
+  let profile = local_data::find_profile(&app, id.trim())?;
+  let proxy = proxy.filter(|p| !p.url.trim().is_empty());
+
+  // password：None 保留钥匙串中的密码，空字符串表示删除；关闭代理时一并删除
+  let mut options = client_options_for_profile(&profile)?;
+  options.proxy_password = match (&proxy, &password) {
+    (None, _) => None,
+    (Some(_), Some(pwd)) => Some(pwd.clone()).filter(|p| !p.is_empty()),
+    (Some(_), None) => load_proxy_password_for_backend(&profile.url)?,
+  };
+  options.proxy = proxy.clone();
+  state.http.build(&options).map_err(PdhError::invalid_argument)?;
+  if proxy.is_none() || password.is_some() {
+    store_proxy_password_for_backend(&profile.url, options.proxy_password.clone())?;
+  }
+  let profile = local_data::set_profile_proxy(&app, &profile.id, proxy)?;
+
+  if backend_base_url_from_state(&state).ok().as_deref() == Some(profile.url.as_str()) {
+    state.http.configure(options).map_err(PdhError::invalid_argument)?;
+    state.health.wake();
+  }
+
+  Ok(profile)
+}
+
+/// 用给定的代理设置请求服务器的 /health。不传 url 时测试网关当前使用的地址；
+/// 不传 password 时使用该服务器已保存的代理密码。
+#[tauri::command]
+async fn pdh_proxy_test(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  proxy: net::ProxySettings,
+  password: Option<String>,
+  url: Option<String>,
+) -> PdhResult<net::ProxyTestResult> {
+  let (backend, upstream) = {
+    let cfg = state
+      .config
+      .read()
+      .map_err(|_| PdhError::poisoned())?;
+    (cfg.backend_base_url.clone(), cfg.upstream_base_url())
+  };
+  let target = match url.as_deref().map(normalize_backend_url).transpose()?.flatten() {
+    Some(u) => u,
+    None => upstream.ok_or_else(PdhError::backend_not_configured)?,
+  };
+
+  // 目标是已保存的服务器时沿用它的 TLS 设置，否则 https 自签名服务器无法测试
+  let lookup = if url.is_none() { backend.unwrap_or_default() } else { target.clone() };
+  let mut options = match local_data::find_profile_by_url(&app, &lookup)? {
+    Some(profile) => {
+      let mut options = client_options_for_profile(&profile)?;
+      if password.is_none() && options.proxy_password.is_none() {
+        options.proxy_password = load_proxy_password_for_backend(&profile.url)?;
+      }
+      options
+    }
+    None => net::ClientOptions::default(),
+  };
+  if let Some(pwd) = password {
+    options.proxy_password = Some(pwd).filter(|p| !p.is_empty());
+  }
+  options.proxy = Some(proxy);
+  let client = state.http.build(&options).map_err(PdhError::invalid_argument)?;
+
+  Ok(net::test_proxy(&client, &target).await)
+}
+
+#[tauri::command]
+fn pdh_gateway_inspect_set_enabled(state: State<GatewayState>, enabled: bool) -> bool {
+  state.inspector.set_enabled(enabled);
+  enabled
+}
+
+#[tauri::command]
+fn pdh_gateway_inspect_list(state: State<GatewayState>) -> serde_json::Value {
+  json!({
+    "enabled": state.inspector.is_enabled(),
+    "entries": state.inspector.list(),
+  })
+}
+
+#[tauri::command]
+fn pdh_gateway_inspect_clear(state: State<GatewayState>) {
+  state.inspector.clear();
+}
+
+#[tauri::command]
+fn pdh_gateway_metrics(state: State<GatewayState>) -> metrics::MetricsSnapshot {
+  state.metrics.snapshot()
+}
+
+#[tauri::command]
+fn pdh_network_settings_get(state: State<GatewayState>) -> net::NetworkSettings {
+  state.http.network()
+}
+
+/// 保存全局网络设置并立即重建共享客户端（进行中的请求不受影响）。
+#[tauri::command]
+fn pdh_network_settings_set(
+  app: tauri::AppHandle,
+  state: State<GatewayState>,
+  settings: net::NetworkSettings,
+) -> PdhResult<net::NetworkSettings> {
+  state.http.set_network(settings.clone()).map_err(PdhError::invalid_argument)?;
+  local_data::save_network_settings(&app, &settings)?;
+  state.health.wake();
+  Ok(settings)
+}
+
+#[tauri::command]
+fn pdh_log_settings_get(app: tauri::AppHandle) -> logging::LogSettings {
+  local_data::log_settings(&app)
+}
+
+#[tauri::command]
+fn pdh_log_settings_set(app: tauri::AppHandle, settings: logging::LogSettings) -> PdhResult<logging::LogSettings> {
+  logging::apply_level(&settings).map_err(PdhError::invalid_argument)?;
+  local_data::save_log_settings(&app, &settings)?;
+  Ok(settings)
+}
+
+/// 让用户选择保存位置并导出诊断包（zip）；取消选择时返回 None。
+#[tauri::command]
+async fn pdh_export_diagnostics(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+) -> PdhResult<Option<String>> {
+  let secs = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  let default_name = format!("pdh-diagnostics-{secs}.zip");
+  let picked = tauri::async_runtime::spawn_blocking(move || {
+    rfd::FileDialog::new()
+      .set_title("导出诊断信息")
+      .set_file_name(default_name.as_str())
+      .add_filter("zip", &["zip"])
+      .save_file()
+  })
+  .await
+  .map_err(|e| PdhError::internal(format!("save_file join failed: {e}")))?;
+  let Some(target) = picked else {
+    return Ok(None);
+  };
+
+  let (backend, endpoints, active_endpoint) = {
+    let cfg = state
+      .config
+      .read()
+      .map_err(|_| PdhError::poisoned())?;
+    (cfg.backend_base_url.clone(), cfg.endpoints.clone(), cfg.active_endpoint.clone())
+  };
+  let gateway_listening = state
+    .addr
+    .read()
+    .map(|addr| addr.is_some())
+    .unwrap_or(false);
+  let gateway = json!({
+    "backend": backend,
+    "endpoints": endpoints,
+    "activeEndpoint": active_endpoint,
+    "tcpGatewayEnabled": state.tcp_gateway_enabled.load(Ordering::Relaxed),
+    "tcpGatewayListening": gateway_listening,
+    "status": state.health.status(true),
+    "network": state.http.network(),
+    "metrics": state.metrics.snapshot(),
+    "uploadTasks": state.upload_tasks.lock().await.len(),
+  });
+
+  let info = app.package_info();
+  let version = json!({
+    "app": info.name,
+    "version": info.version.to_string(),
+    "tauri": tauri::VERSION,
+    "os": std::env::consts::OS,
+    "family": std::env::consts::FAMILY,
+    "arch": std::env::consts::ARCH,
+    "debug": cfg!(debug_assertions),
+    "exportedAt": secs,
+  });
+
+  let input = bundle::BundleInput {
+    logs_dir: local_data::logs_dir(&app).ok(),
+    settings: local_data::settings_snapshot(&app)?,
+    gateway,
+    version,
+  };
+  let path = target.clone();
+  tauri::async_runtime::spawn_blocking(move || bundle::write_diagnostics_bundle(&path, input))
+    .await
+    .map_err(|e| PdhError::internal(format!("export join failed: {e}")))??;
+
+  log::info!("[diagnostics] bundle exported to {}", target.display());
+  Ok(Some(target.to_string_lossy().to_string()))
+}
+
+/// 清理本机会话：当前服务器的 refresh token（顺带清理 legacy 单值，避免升级遗留）、
+/// 网关持有的 access token、账户与主动刷新计划，以及该账户的 API / 附件缓存。
+async fn clear_local_session(state: &State<'_, GatewayState>) -> PdhResult<()> {
+  let (backend, account) = state.session.logout(&state.config).await.map_err(PdhError::internal)?;
+  if let Some(backend) = backend {
+    let scope = api_cache::ApiCache::account_scope(&backend, account.as_deref());
+    if let Some(cache) = state.api_cache.read().ok().and_then(|g| g.clone()) {
+      cache.purge_scope(&scope);
+    }
+    if let Some(cache) = state.attachment_cache.read().ok().and_then(|g| g.clone()) {
+      cache.purge_scope(&scope);
+    }
+  }
+  let legacy = refresh_token_entry(KEYRING_ACCOUNT_REFRESH_LEGACY)?;
+  let _ = legacy.delete_password();
+  Ok(())
+}
+
+#[tauri::command]
+async fn pdh_auth_clear_refresh_token(app: tauri::AppHandle, state: State<'_, GatewayState>) -> PdhResult<()> {
+  clear_local_session(&state).await?;
+  session::emit_auth_state(&app, "logged-out", json!({}));
+  Ok(())
+}
+
+/// 上传任务带着当前会话的 token：退出或切换账户前先停掉，避免继续以旧身份写入。
+async fn cancel_upload_tasks(state: &State<'_, GatewayState>) {
+  let guard = state.upload_tasks.lock().await;
+  for handle in guard.values() {
+    let _ = handle.tx.send(UploadRunState::Canceled);
+  }
+}
+
+/// 退出登录：先尽力让后端撤销会话（离线时跳过），再清理本机会话；
+/// `forget_password` 时同时删除该用户保存的密码。进行中的上传任务一并取消。
+#[tauri::command]
+async fn pdh_auth_logout(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  username: Option<String>,
+  forget_password: Option<bool>,
+) -> PdhResult<serde_json::Value> {
+  let (backend, upstream, token) = {
+    let cfg = state
+      .config
+      .read()
+      .map_err(|_| PdhError::poisoned())?;
+    (cfg.backend_base_url.clone(), cfg.upstream_base_url(), cfg.bearer_token.clone())
+  };
+
+  cancel_upload_tasks(&state).await;
+
+  let mut revoked = false;
+  if let Some(upstream) = upstream {
+    // 重启后网关只有 refresh token（或 access token 已过期）：先用 refresh token 换一个，再请求撤销
+    let mut token = match token.filter(|t| !t.trim().is_empty()) {
+      Some(t) => Some(t),
+      None => state.session.refresh(&state.http, &state.config, None).await.ok().map(|r| r.token),
+    };
+    let mut refreshed = false;
+    while let Some(current) = token.take() {
+      let req = state
+        .http
+        .with_request_timeout(state.http.get().post(format!("{}/api/auth/logout", upstream)))
+        .bearer_auth(current.trim());
+      match request_id::apply(req).send().await {
+        Ok(resp) if resp.status().is_success() => revoked = true,
+        Ok(resp) if resp.status() == reqwest::StatusCode::UNAUTHORIZED && !refreshed => {
+          refreshed = true;
+          token = state
+            .session
+            .refresh(&state.http, &state.config, Some(&current))
+            .await
+            .ok()
+            .map(|r| r.token);
+        }
+        Ok(resp) => log::warn!("[auth] upstream logout returned {}", resp.status().as_u16()),
+        Err(e) => log::warn!("[auth] upstream logout failed: {}", e),
+      }
+    }
   }
 
-  let account = password_account_for_backend(&backend, &user);
-  let entry = refresh_token_entry(&account)?;
+  clear_local_session(&state).await?;
 
-  let pwd = password.trim().to_string();
-  if pwd.is_empty() {
-    let _ = entry.delete_password();
-    return Ok(());
+  if forget_password.unwrap_or(false) {
+    let user = username.as_deref().map(str::trim).unwrap_or("");
+    if let (Some(backend), false) = (backend.as_deref(), user.is_empty()) {
+      delete_password_for_backend(backend, user)?;
+    }
   }
 
-  entry.set_password(&pwd).map_err(|e| e.to_string())
+  session::emit_auth_state(&app, "logged-out", json!({ "revoked": revoked }));
+  Ok(json!({ "revoked": revoked }))
 }
 
+/// 按服务器列出已知账户：是否还有可恢复的会话、是否保存了密码、是否为网关当前账户。
+/// 传 backend_base_url 时只返回该服务器。
 #[tauri::command]
-fn pdh_secret_get_password(
-  backend_base_url: String,
-  username: String,
-) -> Result<Option<String>, String> {
-  let backend = backend_base_url.trim().trim_end_matches('/').to_string();
-  if backend.is_empty() {
-    return Err("backend_base_url is empty".to_string());
-  }
-  let user = username.trim().to_string();
-  if user.is_empty() {
-    return Err("username is empty".to_string());
-  }
-
-  let account = password_account_for_backend(&backend, &user);
-  let entry = refresh_token_entry(&account)?;
+fn pdh_auth_list_accounts(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  backend_base_url: Option<String>,
+) -> PdhResult<Vec<serde_json::Value>> {
+  let filter = match backend_base_url.as_deref() {
+    Some(url) => normalize_backend_url(url)?,
+    None => None,
+  };
+  let (current_backend, current_account) = state
+    .config
+    .read()
+    .map(|cfg| (cfg.backend_base_url.clone(), cfg.account.clone()))
+    .map_err(|_| PdhError::poisoned())?;
 
-  match entry.get_password() {
-    Ok(pwd) => {
-      let p = pwd.trim().to_string();
-      Ok(if p.is_empty() { None } else { Some(p) })
+  let snapshot = local_data::pdh_profiles_list(app)?;
+  let mut out = Vec::new();
+  for profile in snapshot.profiles {
+    if filter.as_deref().is_some_and(|url| url != profile.url) {
+      continue;
     }
-    Err(err) => {
-      if is_no_entry_error(&err) {
-        Ok(None)
-      } else {
-        Err(err.to_string())
+    let mut usernames: Vec<String> = profile.known_usernames.clone();
+    if let Some(last) = &profile.last_username {
+      if !usernames.contains(last) {
+        usernames.push(last.clone());
       }
     }
+    let is_current_backend = current_backend.as_deref() == Some(profile.url.as_str());
+    let accounts: Vec<serde_json::Value> = usernames
+      .iter()
+      .map(|user| {
+        // 升级前按服务器保存的会话归属于最后登录的用户
+        let legacy_session = profile.last_username.as_deref() == Some(user.as_str())
+          && load_backend_scoped_refresh_token(&profile.url).ok().flatten().is_some();
+        json!({
+          "username": user,
+          "hasSession": has_refresh_token_for_user(&profile.url, user) || legacy_session,
+          "hasSavedPassword": load_password_for_backend(&profile.url, user).ok().flatten().is_some(),
+          "active": is_current_backend && current_account.as_deref() == Some(user.as_str()),
+        })
+      })
+      .collect();
+    out.push(json!({
+      "profileId": profile.id,
+      "backend": profile.url,
+      "accounts": accounts,
+    }));
   }
+  Ok(out)
 }
 
+/// 切换当前服务器上的账户：用该账户保存的 refresh token 恢复会话；
+/// 会话已失效但保存了密码时直接重新登录，都不需要再输入密码。
+/// 两种方式都成功后才切换，并同样发出 `pdh-auth-state`（refreshed）；否则仍停留在当前账户。
 #[tauri::command]
-fn pdh_secret_delete_password(backend_base_url: String, username: String) -> Result<(), String> {
-  let backend = backend_base_url.trim().trim_end_matches('/').to_string();
-  if backend.is_empty() {
-    return Err("backend_base_url is empty".to_string());
-  }
+async fn pdh_auth_switch_account(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  username: String,
+) -> PdhResult<serde_json::Value> {
+  let backend = backend_base_url_from_state(&state)?;
   let user = username.trim().to_string();
   if user.is_empty() {
-    return Err("username is empty".to_string());
+    return Err(PdhError::invalid_argument("username is empty"));
   }
 
-  let account = password_account_for_backend(&backend, &user);
-  let entry = refresh_token_entry(&account)?;
-  let _ = entry.delete_password();
-  Ok(())
+  // 先用目标账户的会话换到新 token 再切换；失败时当前账户与 token 保持不变
+  match state.session.switch_account(&state.http, &state.config, user.clone()).await {
+    Ok(refreshed) => {
+      let expires_in = refreshed.expires_in();
+      cancel_upload_tasks(&state).await;
+      let _ = local_data::record_profile_username(&app, &backend, &user);
+      session::emit_auth_state(&app, "refreshed", json!({ "expiresIn": expires_in, "account": user }));
+      Ok(json!({ "username": user, "authenticated": true, "expiresIn": expires_in }))
+    }
+    Err(e) if e.is_final() => {
+      let Some(password) = load_password_for_backend(&backend, &user)? else {
+        return Ok(json!({ "username": user, "authenticated": false, "reason": e.message() }));
+      };
+      // 登录成功才会替换网关的账户与 token（并记录为最近使用的账户）
+      let body = login_upstream(&app, &state, &backend, &user, &password).await?;
+      cancel_upload_tasks(&state).await;
+      let data = body.get("data").cloned().unwrap_or(json!({}));
+      let expires_in = data.get("expiresIn").cloned().unwrap_or(json!(null));
+      session::emit_auth_state(&app, "refreshed", json!({ "expiresIn": expires_in, "account": user }));
+      Ok(json!({
+        "username": user,
+        "authenticated": true,
+        "user": data.get("user").cloned().unwrap_or(json!(null)),
+        "expiresIn": expires_in,
+      }))
+    }
+    Err(e) => Err(PdhError::from(e)),
+  }
 }
 
-#[tauri::command]
-fn pdh_gateway_url(state: State<GatewayState>) -> Result<String, String> {
-  let addr = state
-    .addr
+fn attachment_cache_from_state(
+  state: &State<GatewayState>,
+) -> PdhResult<Arc<attachment_cache::AttachmentCache>> {
+  state
+    .attachment_cache
     .read()
-    .map_err(|_| "gateway state poisoned".to_string())?
-    .ok_or_else(|| "gateway not ready".to_string())?;
-  Ok(format!("http://{}", addr))
+    .map_err(|_| PdhError::poisoned())?
+    .clone()
+    .ok_or_else(|| PdhError::new(ErrorCode::NotReady, "attachment cache not ready"))
 }
 
 #[tauri::command]
-fn pdh_gateway_set_backend_url(state: State<GatewayState>, url: String) -> Result<(), String> {
-  let normalized = url.trim().trim_end_matches('/').to_string();
-  let mut cfg = state
-    .config
-    .write()
-    .map_err(|_| "gateway state poisoned".to_string())?;
-
-  if normalized.is_empty() {
-    cfg.backend_base_url = None;
-    return Ok(());
-  }
-
-  let lower = normalized.to_ascii_lowercase();
-  if !(lower.starts_with("http://") || lower.starts_with("https://")) {
-    return Err("backend url must start with http:// or https://".to_string());
-  }
-
-  cfg.backend_base_url = Some(normalized);
-  Ok(())
+fn pdh_attachment_cache_info(
+  state: State<GatewayState>,
+) -> PdhResult<attachment_cache::AttachmentCacheInfo> {
+  Ok(attachment_cache_from_state(&state)?.info())
 }
 
 #[tauri::command]
-fn pdh_gateway_set_token(state: State<GatewayState>, token: Option<String>) -> Result<(), String> {
-  let mut cfg = state
-    .config
-    .write()
-    .map_err(|_| "gateway state poisoned".to_string())?;
-  cfg.bearer_token = token.and_then(|t| {
-    let trimmed = t.trim().to_string();
-    if trimmed.is_empty() {
-      None
-    } else {
-      Some(trimmed)
-    }
-  });
-  Ok(())
+fn pdh_attachment_cache_set_cap(
+  state: State<GatewayState>,
+  cap_bytes: u64,
+) -> PdhResult<attachment_cache::AttachmentCacheInfo> {
+  let cache = attachment_cache_from_state(&state)?;
+  cache.set_cap(cap_bytes);
+  Ok(cache.info())
 }
 
 #[tauri::command]
-fn pdh_auth_clear_refresh_token(state: State<'_, GatewayState>) -> Result<(), String> {
-  // 清理当前服务器的 refresh token；同时顺带清理 legacy 单值，避免升级遗留
-  if let Ok(backend) = backend_base_url_from_state(&state) {
-    let _ = store_refresh_token_for_backend(&backend, None);
-  }
-  let legacy = refresh_token_entry(KEYRING_ACCOUNT_REFRESH_LEGACY)?;
-  let _ = legacy.delete_password();
-  Ok(())
+fn pdh_attachment_cache_purge(
+  state: State<GatewayState>,
+) -> PdhResult<attachment_cache::AttachmentCacheInfo> {
+  let cache = attachment_cache_from_state(&state)?;
+  cache.purge().map_err(PdhError::io)?;
+  Ok(cache.info())
 }
 
 #[tauri::command]
-async fn pdh_pick_directory() -> Result<Option<String>, String> {
+async fn pdh_pick_directory() -> PdhResult<Option<String>> {
   let picked = tauri::async_runtime::spawn_blocking(|| {
     rfd::FileDialog::new()
       .set_title("选择新的本地数据存放位置（会在其中创建 personal-data-hub-data）")
       .pick_folder()
   })
   .await
-  .map_err(|e| format!("pick_folder join failed: {e}"))?;
+  .map_err(|e| PdhError::internal(format!("pick_folder join failed: {e}")))?;
 
   Ok(picked.map(|p| p.to_string_lossy().to_string()))
 }
 
-fn backend_base_url_from_state(state: &State<GatewayState>) -> Result<String, String> {
+fn upstream_base_url_from_config(config: &RwLock<gateway::GatewayConfig>) -> PdhResult<String> {
+  config
+    .read()
+    .map_err(|_| PdhError::poisoned())?
+    .upstream_base_url()
+    .ok_or_else(PdhError::backend_not_configured)
+}
+
+fn backend_base_url_from_state(state: &State<GatewayState>) -> PdhResult<String> {
   let cfg = state
     .config
     .read()
-    .map_err(|_| "gateway state poisoned".to_string())?;
+    .map_err(|_| PdhError::poisoned())?;
   cfg
     .backend_base_url
     .clone()
-    .ok_or_else(|| "backend url not set".to_string())
+    .ok_or_else(PdhError::backend_not_configured)
 }
 
-fn normalize_attachment_category(category: &str) -> Result<&'static str, String> {
+fn normalize_attachment_category(category: &str) -> PdhResult<&'static str> {
   match category.trim() {
     "image" => Ok("image"),
     "video" => Ok("video"),
     "document" => Ok("document"),
     "script" => Ok("script"),
-    _ => Err("invalid attachment category".to_string()),
+    _ => Err(PdhError::invalid_argument("invalid attachment category")),
+  }
+}
+
+fn invalid_response(err: serde_json::Error) -> PdhError {
+  PdhError::new(ErrorCode::Upstream, format!("invalid response: {err}"))
+}
+
+/// 上传任务的鉴权：每次请求前读取网关当前的 access token（主动刷新后立即生效），
+/// 遇到 401 时与网关共用 SessionRefresher 刷新一次。
+struct UploadAuth {
+  app: tauri::AppHandle,
+  // 任务启动时的账户：切换账户后不再带上新账户的 token
+  account: Option<String>,
+  config: Arc<RwLock<gateway::GatewayConfig>>,
+  http: Arc<net::HttpClient>,
+  session: Arc<session::SessionRefresher>,
+}
+
+impl UploadAuth {
+  fn token(&self) -> String {
+    self
+      .config
+      .read()
+      .ok()
+      .filter(|cfg| cfg.account == self.account)
+      .and_then(|cfg| cfg.bearer_token.clone())
+      .unwrap_or_default()
+  }
+
+  /// `err` 为用 `used` 发出的请求失败的结果；仅 401 时刷新，返回 true 表示值得用新 token 立即重试。
+  async fn renew_after(&self, err: &PdhError, used: &str) -> bool {
+    let unauthorized = err
+      .details
+      .as_ref()
+      .and_then(|d| d.get("status"))
+      .and_then(|s| s.as_u64())
+      == Some(401);
+    if !unauthorized {
+      return false;
+    }
+    match self.session.refresh(&self.http, &self.config, Some(used)).await {
+      Ok(refreshed) => {
+        if !refreshed.body.is_null() {
+          session::emit_auth_state(&self.app, "refreshed", json!({ "expiresIn": refreshed.expires_in() }));
+        }
+        true
+      }
+      Err(e) => {
+        log::warn!("[upload] {}token refresh after 401 failed: {}", request_id::log_prefix(), e.message());
+        if e.is_final() {
+          session::emit_auth_state(&self.app, "expired", json!({ "reason": e.message() }));
+        }
+        false
+      }
+    }
   }
 }
 
-fn emit_upload_task_event(app: &tauri::AppHandle, payload: serde_json::Value) {
+/// 上传任务事件带上任务的请求 ID（与发往后端的 X-Request-Id 相同），失败时同时写日志。
+fn emit_upload_task_event(app: &tauri::AppHandle, mut payload: serde_json::Value) {
+  if let (Some(obj), Some(id)) = (payload.as_object_mut(), request_id::current()) {
+    obj.insert("requestId".to_string(), json!(id));
+  }
+  if payload.get("status").and_then(|s| s.as_str()) == Some("failed") {
+    log::warn!(
+      "[upload] {}task {} failed: {}",
+      request_id::log_prefix(),
+      payload.get("taskId").and_then(|s| s.as_str()).unwrap_or(""),
+      payload.get("error").and_then(|s| s.as_str()).unwrap_or("")
+    );
+  }
   let _ = app.emit("pdh-attachment-upload-task", payload);
 }
 
 async fn upload_init_session(
-  client: &reqwest::Client,
+  http: &net::HttpClient,
   backend: &str,
   token: &str,
   category: &str,
   original_name: &str,
   mime: &str,
   size: u64,
-) -> Result<String, String> {
+) -> PdhResult<String> {
   let url = format!("{}/api/attachments/uploads/init", backend.trim().trim_end_matches('/'));
-  let mut req = client.post(url).json(&json!({
+  let mut req = request_id::apply(http.with_request_timeout(http.get().post(url))).json(&json!({
     "category": category,
     "originalName": original_name,
     "mimeType": mime,
@@ -319,14 +1269,14 @@ async fn upload_init_session(
     req = req.bearer_auth(token.trim());
   }
 
-  let resp = req.send().await.map_err(|e| e.to_string())?;
+  let resp = req.send().await?;
   let status = resp.status();
-  let body = resp.text().await.map_err(|e| e.to_string())?;
+  let body = resp.text().await?;
   if !status.is_success() {
-    return Err(format!("init failed ({}): {}", status.as_u16(), body));
+    return Err(PdhError::upstream("init", status.as_u16(), body));
   }
 
-  let v = serde_json::from_str::<serde_json::Value>(&body).map_err(|e| e.to_string())?;
+  let v = serde_json::from_str::<serde_json::Value>(&body).map_err(invalid_response)?;
   let upload_id = v
     .get("data")
     .and_then(|d| d.get("uploadId"))
@@ -336,36 +1286,36 @@ async fn upload_init_session(
     .to_string();
 
   if upload_id.is_empty() {
-    return Err("init response missing uploadId".to_string());
+    return Err(PdhError::new(ErrorCode::Upstream, "init response missing uploadId"));
   }
 
   Ok(upload_id)
 }
 
 async fn upload_status(
-  client: &reqwest::Client,
+  http: &net::HttpClient,
   backend: &str,
   token: &str,
   upload_id: &str,
-) -> Result<u64, String> {
+) -> PdhResult<u64> {
   let url = format!(
     "{}/api/attachments/uploads/{}",
     backend.trim().trim_end_matches('/'),
     upload_id.trim()
   );
-  let mut req = client.get(url);
+  let mut req = request_id::apply(http.with_request_timeout(http.get().get(url)));
   if !token.trim().is_empty() {
     req = req.bearer_auth(token.trim());
   }
 
-  let resp = req.send().await.map_err(|e| e.to_string())?;
+  let resp = req.send().await?;
   let status = resp.status();
-  let body = resp.text().await.map_err(|e| e.to_string())?;
+  let body = resp.text().await?;
   if !status.is_success() {
-    return Err(format!("status failed ({}): {}", status.as_u16(), body));
+    return Err(PdhError::upstream("status", status.as_u16(), body));
   }
 
-  let v = serde_json::from_str::<serde_json::Value>(&body).map_err(|e| e.to_string())?;
+  let v = serde_json::from_str::<serde_json::Value>(&body).map_err(invalid_response)?;
   let bytes = v
     .get("data")
     .and_then(|d| d.get("bytesReceived"))
@@ -375,13 +1325,13 @@ async fn upload_status(
 }
 
 async fn upload_chunk(
-  client: &reqwest::Client,
+  http: &net::HttpClient,
   backend: &str,
   token: &str,
   upload_id: &str,
   offset: u64,
   bytes: Vec<u8>,
-) -> Result<(), String> {
+) -> PdhResult<()> {
   let url = format!(
     "{}/api/attachments/uploads/{}/chunk?offset={}",
     backend.trim().trim_end_matches('/'),
@@ -392,63 +1342,63 @@ async fn upload_chunk(
   let part = reqwest::multipart::Part::bytes(bytes).file_name("chunk");
   let form = reqwest::multipart::Form::new().part("chunk", part);
 
-  let mut req = client.post(url).multipart(form);
+  let mut req = request_id::apply(http.with_request_timeout(http.get().post(url))).multipart(form);
   if !token.trim().is_empty() {
     req = req.bearer_auth(token.trim());
   }
 
-  let resp = req.send().await.map_err(|e| e.to_string())?;
+  let resp = req.send().await?;
   let status = resp.status();
-  let body = resp.text().await.map_err(|e| e.to_string())?;
+  let body = resp.text().await?;
   if !status.is_success() {
-    return Err(format!("chunk failed ({}): {}", status.as_u16(), body));
+    return Err(PdhError::upstream("chunk", status.as_u16(), body));
   }
   Ok(())
 }
 
 async fn upload_complete(
-  client: &reqwest::Client,
+  http: &net::HttpClient,
   backend: &str,
   token: &str,
   upload_id: &str,
-) -> Result<serde_json::Value, String> {
+) -> PdhResult<serde_json::Value> {
   let url = format!(
     "{}/api/attachments/uploads/{}/complete",
     backend.trim().trim_end_matches('/'),
     upload_id.trim()
   );
-  let mut req = client.post(url);
+  let mut req = request_id::apply(http.with_request_timeout(http.get().post(url)));
   if !token.trim().is_empty() {
     req = req.bearer_auth(token.trim());
   }
 
-  let resp = req.send().await.map_err(|e| e.to_string())?;
+  let resp = req.send().await?;
   let status = resp.status();
-  let body = resp.text().await.map_err(|e| e.to_string())?;
+  let body = resp.text().await?;
   if !status.is_success() {
-    return Err(format!("complete failed ({}): {}", status.as_u16(), body));
+    return Err(PdhError::upstream("complete", status.as_u16(), body));
   }
 
-  serde_json::from_str::<serde_json::Value>(&body).map_err(|e| e.to_string())
+  serde_json::from_str::<serde_json::Value>(&body).map_err(invalid_response)
 }
 
 async fn upload_abort(
-  client: &reqwest::Client,
+  http: &net::HttpClient,
   backend: &str,
   token: &str,
   upload_id: &str,
-) -> Result<(), String> {
+) -> PdhResult<()> {
   let url = format!(
     "{}/api/attachments/uploads/{}",
     backend.trim().trim_end_matches('/'),
     upload_id.trim()
   );
-  let mut req = client.delete(url);
+  let mut req = request_id::apply(http.with_request_timeout(http.get().delete(url)));
   if !token.trim().is_empty() {
     req = req.bearer_auth(token.trim());
   }
 
-  let resp = req.send().await.map_err(|e| e.to_string())?;
+  let resp = req.send().await?;
   if !resp.status().is_success() {
     // abort 是 best-effort：不阻断
     return Ok(());
@@ -462,19 +1412,24 @@ async fn run_upload_task_from_path(
   task_id: String,
   tx: watch::Sender<UploadRunState>,
   mut rx: watch::Receiver<UploadRunState>,
-  backend: String,
-  token: String,
+  config: Arc<RwLock<gateway::GatewayConfig>>,
+  http: Arc<net::HttpClient>,
+  metrics: Arc<metrics::GatewayMetrics>,
+  auth: UploadAuth,
   file_path: PathBuf,
   category: String,
 ) {
+  metrics.upload_started();
   let category = match normalize_attachment_category(&category) {
     Ok(v) => v.to_string(),
     Err(e) => {
       emit_upload_task_event(&app, json!({
         "taskId": task_id,
         "status": "failed",
-        "error": e,
+        "error": e.message,
+        "errorCode": e.code,
       }));
+      metrics.upload_finished(metrics::UploadOutcome::Failed);
       let _ = tasks.lock().await.remove(&task_id);
       return;
     }
@@ -487,7 +1442,9 @@ async fn run_upload_task_from_path(
         "taskId": task_id,
         "status": "failed",
         "error": format!("stat file failed: {e}"),
+        "errorCode": ErrorCode::Io,
       }));
+      metrics.upload_finished(metrics::UploadOutcome::Failed);
       let _ = tasks.lock().await.remove(&task_id);
       return;
     }
@@ -505,16 +1462,40 @@ async fn run_upload_task_from_path(
     .essence_str()
     .to_string();
 
-  let client = reqwest::Client::new();
-  let upload_id = match upload_init_session(&client, &backend, &token, &category, &file_name, &mime, total_bytes).await {
+  let mut backend = match upstream_base_url_from_config(&config) {
+    Ok(b) => b,
+    Err(e) => {
+      emit_upload_task_event(&app, json!({
+        "taskId": task_id,
+        "status": "failed",
+        "error": e.message,
+        "errorCode": e.code,
+        "totalBytes": total_bytes,
+      }));
+      metrics.upload_finished(metrics::UploadOutcome::Failed);
+      let _ = tasks.lock().await.remove(&task_id);
+      return;
+    }
+  };
+
+  let token = auth.token();
+  let mut init = upload_init_session(&http, &backend, &token, &category, &file_name, &mime, total_bytes).await;
+  if let Err(e) = &init {
+    if auth.renew_after(e, &token).await {
+      init = upload_init_session(&http, &backend, &auth.token(), &category, &file_name, &mime, total_bytes).await;
+    }
+  }
+  let upload_id = match init {
     Ok(id) => id,
     Err(e) => {
       emit_upload_task_event(&app, json!({
         "taskId": task_id,
         "status": "failed",
-        "error": e,
+        "error": e.message,
+        "errorCode": e.code,
         "totalBytes": total_bytes,
       }));
+      metrics.upload_finished(metrics::UploadOutcome::Failed);
       let _ = tasks.lock().await.remove(&task_id);
       return;
     }
@@ -535,10 +1516,12 @@ async fn run_upload_task_from_path(
         "taskId": task_id,
         "status": "failed",
         "error": format!("open file failed: {e}"),
+        "errorCode": ErrorCode::Io,
         "totalBytes": total_bytes,
         "uploadId": upload_id,
       }));
-      let _ = upload_abort(&client, &backend, &token, &upload_id).await;
+      let _ = upload_abort(&http, &backend, &auth.token(), &upload_id).await;
+      metrics.upload_finished(metrics::UploadOutcome::Failed);
       let _ = tasks.lock().await.remove(&task_id);
       return;
     }
@@ -546,11 +1529,13 @@ async fn run_upload_task_from_path(
 
   // 1MB：更细粒度的进度更新，暂停/取消响应更快
   const CHUNK_SIZE: usize = 1 * 1024 * 1024;
+  // 本轮已因 401 刷新过 token：再失败就暂停，避免对失效会话反复刷新
+  let mut renewed = false;
 
   loop {
     let state = *rx.borrow();
     if state == UploadRunState::Canceled {
-      let _ = upload_abort(&client, &backend, &token, &upload_id).await;
+      let _ = upload_abort(&http, &backend, &auth.token(), &upload_id).await;
       emit_upload_task_event(&app, json!({
         "taskId": task_id,
         "status": "canceled",
@@ -558,6 +1543,7 @@ async fn run_upload_task_from_path(
         "totalBytes": total_bytes,
         "uploadId": upload_id,
       }));
+      metrics.upload_finished(metrics::UploadOutcome::Canceled);
       break;
     }
 
@@ -572,17 +1558,29 @@ async fn run_upload_task_from_path(
       if rx.changed().await.is_err() {
         break;
       }
+      renewed = false;
       continue;
     }
 
+    // 健康检查可能已切换访问地址：上传会话在服务端，换地址续传即可
+    if let Ok(next) = upstream_base_url_from_config(&config) {
+      backend = next;
+    }
+
     // Running：对齐服务端 offset（断点续传）
-    let offset = match upload_status(&client, &backend, &token, &upload_id).await {
+    let token = auth.token();
+    let offset = match upload_status(&http, &backend, &token, &upload_id).await {
       Ok(b) => b,
       Err(e) => {
+        if !renewed && auth.renew_after(&e, &token).await {
+          renewed = true;
+          continue;
+        }
         emit_upload_task_event(&app, json!({
           "taskId": task_id,
           "status": "failed",
-          "error": e,
+          "error": e.message,
+          "errorCode": e.code,
           "totalBytes": total_bytes,
           "uploadId": upload_id,
         }));
@@ -592,7 +1590,8 @@ async fn run_upload_task_from_path(
     };
 
     if offset >= total_bytes {
-      match upload_complete(&client, &backend, &token, &upload_id).await {
+      let token = auth.token();
+      match upload_complete(&http, &backend, &token, &upload_id).await {
         Ok(v) => {
           let attachment = v.get("data").cloned().unwrap_or(json!(null));
           emit_upload_task_event(&app, json!({
@@ -603,13 +1602,19 @@ async fn run_upload_task_from_path(
             "uploadId": upload_id,
             "attachment": attachment,
           }));
+          metrics.upload_finished(metrics::UploadOutcome::Done);
           break;
         }
         Err(e) => {
+          if !renewed && auth.renew_after(&e, &token).await {
+            renewed = true;
+            continue;
+          }
           emit_upload_task_event(&app, json!({
             "taskId": task_id,
             "status": "failed",
-            "error": e,
+            "error": e.message,
+            "errorCode": e.code,
             "bytesSent": offset,
             "totalBytes": total_bytes,
             "uploadId": upload_id,
@@ -626,6 +1631,7 @@ async fn run_upload_task_from_path(
         "taskId": task_id,
         "status": "failed",
         "error": format!("seek failed: {e}"),
+        "errorCode": ErrorCode::Io,
         "bytesSent": offset,
         "totalBytes": total_bytes,
         "uploadId": upload_id,
@@ -644,6 +1650,7 @@ async fn run_upload_task_from_path(
           "taskId": task_id,
           "status": "failed",
           "error": format!("read failed: {e}"),
+          "errorCode": ErrorCode::Io,
           "bytesSent": offset,
           "totalBytes": total_bytes,
           "uploadId": upload_id,
@@ -658,6 +1665,7 @@ async fn run_upload_task_from_path(
         "taskId": task_id,
         "status": "failed",
         "error": "unexpected EOF".to_string(),
+        "errorCode": ErrorCode::Io,
         "bytesSent": offset,
         "totalBytes": total_bytes,
         "uploadId": upload_id,
@@ -669,11 +1677,18 @@ async fn run_upload_task_from_path(
     buf.truncate(n);
 
     // 上传 chunk
-    if let Err(e) = upload_chunk(&client, &backend, &token, &upload_id, offset, buf).await {
+    let chunk_started = std::time::Instant::now();
+    let token = auth.token();
+    if let Err(e) = upload_chunk(&http, &backend, &token, &upload_id, offset, buf).await {
+      if !renewed && auth.renew_after(&e, &token).await {
+        renewed = true;
+        continue;
+      }
       emit_upload_task_event(&app, json!({
         "taskId": task_id,
         "status": "failed",
-        "error": e,
+        "error": e.message,
+        "errorCode": e.code,
         "bytesSent": offset,
         "totalBytes": total_bytes,
         "uploadId": upload_id,
@@ -682,6 +1697,9 @@ async fn run_upload_task_from_path(
       continue;
     }
 
+    renewed = false;
+    metrics.upload_chunk(n as u64, chunk_started.elapsed());
+
     emit_upload_task_event(&app, json!({
       "taskId": task_id,
       "status": "uploading",
@@ -694,18 +1712,67 @@ async fn run_upload_task_from_path(
   let _ = tasks.lock().await.remove(&task_id);
 }
 
+/// 整文件上传一次；每次调用重新打开文件，401 刷新 token 后可以直接重试。返回响应体与文件大小。
+async fn upload_whole_file(
+  http: &net::HttpClient,
+  url: &str,
+  request_id: &str,
+  token: &str,
+  file_path: &Path,
+  file_name: &str,
+  mime: &str,
+) -> PdhResult<(String, u64)> {
+  let file = tokio::fs::File::open(file_path)
+    .await
+    .map_err(|e| PdhError::io(format!("open file failed: {e}")))?;
+  let total_bytes = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+
+  let stream = ReaderStream::new(file);
+  let body = reqwest::Body::wrap_stream(stream);
+  let part = reqwest::multipart::Part::stream(body)
+    .file_name(file_name.to_string())
+    .mime_str(mime)?;
+  let form = reqwest::multipart::Form::new().part("file", part);
+
+  // 整文件流式上传不设总超时，靠连接/读取超时发现服务器失联
+  let mut req = http
+    .get()
+    .post(url)
+    .header(request_id::REQUEST_ID_HEADER, request_id)
+    .multipart(form);
+
+  if !token.trim().is_empty() {
+    req = req.bearer_auth(token.trim());
+  }
+
+  let resp = req.send().await?;
+  let status = resp.status();
+  let body = resp.text().await?;
+
+  if !status.is_success() {
+    log::warn!("[upload] [req {}] upload failed ({})", request_id, status.as_u16());
+    return Err(PdhError::upstream("upload", status.as_u16(), body.clone()).with_details(json!({
+      "status": status.as_u16(),
+      "body": body,
+      "requestId": request_id,
+    })));
+  }
+  Ok((body, total_bytes))
+}
+
 #[tauri::command]
 async fn pdh_upload_attachment_from_path(
+  app: tauri::AppHandle,
   state: State<'_, GatewayState>,
   path: String,
   category: String,
-) -> Result<serde_json::Value, String> {
-  let backend = backend_base_url_from_state(&state)?;
+) -> PdhResult<serde_json::Value> {
+  let backend = upstream_base_url_from_config(&state.config)?;
   let category = normalize_attachment_category(&category)?;
 
   let file_path = PathBuf::from(path.trim());
   if file_path.as_os_str().is_empty() {
-    return Err("path is empty".to_string());
+    return Err(PdhError::invalid_argument("path is empty"));
   }
 
   let file_name = file_path
@@ -719,43 +1786,35 @@ async fn pdh_upload_attachment_from_path(
     .essence_str()
     .to_string();
 
-  let file = tokio::fs::File::open(&file_path)
-    .await
-    .map_err(|e| format!("open file failed: {e}"))?;
-
-  let stream = ReaderStream::new(file);
-  let body = reqwest::Body::wrap_stream(stream);
-  let part = reqwest::multipart::Part::stream(body)
-    .file_name(file_name)
-    .mime_str(&mime)
-    .map_err(|e| e.to_string())?;
-  let form = reqwest::multipart::Form::new().part("file", part);
-
-  let token = {
-    let cfg = state
+  let auth = UploadAuth {
+    app,
+    account: state
       .config
       .read()
-      .map_err(|_| "gateway state poisoned".to_string())?;
-    cfg.bearer_token.clone().unwrap_or_default()
+      .map_err(|_| PdhError::poisoned())?
+      .account
+      .clone(),
+    config: state.config.clone(),
+    http: state.http.clone(),
+    session: state.session.clone(),
   };
 
   let url = format!("{}/api/attachments/{}", backend, category);
-  let client = reqwest::Client::new();
-  let mut req = client.post(url).multipart(form);
-
-  if !token.trim().is_empty() {
-    req = req.bearer_auth(token.trim());
-  }
-
-  let resp = req.send().await.map_err(|e| e.to_string())?;
-  let status = resp.status();
-  let body = resp.text().await.map_err(|e| e.to_string())?;
-
-  if !status.is_success() {
-    return Err(format!("upload failed ({}): {}", status.as_u16(), body));
+  let request_id = request_id::generate();
+  let mut started = std::time::Instant::now();
+  let token = auth.token();
+  let mut result = upload_whole_file(&state.http, &url, &request_id, &token, &file_path, &file_name, &mime).await;
+  if let Err(e) = &result {
+    // access token 过期：刷新后重新打开文件再传一次
+    if auth.renew_after(e, &token).await {
+      started = std::time::Instant::now();
+      result = upload_whole_file(&state.http, &url, &request_id, &auth.token(), &file_path, &file_name, &mime).await;
+    }
   }
+  let (body, total_bytes) = result?;
+  state.metrics.upload_chunk(total_bytes, started.elapsed());
 
-  serde_json::from_str::<serde_json::Value>(&body).map_err(|e| e.to_string())
+  serde_json::from_str::<serde_json::Value>(&body).map_err(invalid_response)
 }
 
 #[tauri::command]
@@ -765,25 +1824,19 @@ async fn pdh_attachment_upload_task_start(
   taskId: String,
   path: String,
   category: String,
-) -> Result<(), String> {
+) -> PdhResult<()> {
   #[allow(non_snake_case)]
   let task_id = taskId.trim().to_string();
   if task_id.is_empty() {
-    return Err("taskId is empty".to_string());
+    return Err(PdhError::invalid_argument("taskId is empty"));
   }
 
-  let backend = backend_base_url_from_state(&state)?;
-  let token = {
-    let cfg = state
-      .config
-      .read()
-      .map_err(|_| "gateway state poisoned".to_string())?;
-    cfg.bearer_token.clone().unwrap_or_default()
-  };
+  // 提前校验后端已配置；实际地址与 token 由任务在每次请求前读取
+  upstream_base_url_from_config(&state.config)?;
 
   let file_path = PathBuf::from(path.trim());
   if file_path.as_os_str().is_empty() {
-    return Err("path is empty".to_string());
+    return Err(PdhError::invalid_argument("path is empty"));
   }
 
   let (tx, rx) = watch::channel(UploadRunState::Running);
@@ -791,33 +1844,49 @@ async fn pdh_attachment_upload_task_start(
   {
     let mut guard = state.upload_tasks.lock().await;
     if guard.contains_key(&task_id) {
-      return Err("task already exists".to_string());
+      return Err(PdhError::new(ErrorCode::AlreadyExists, "task already exists"));
     }
     guard.insert(task_id.clone(), UploadTaskHandle { tx: tx.clone() });
   }
 
   let tasks = state.upload_tasks.clone();
+  let config = state.config.clone();
+  let http = state.http.clone();
+  let metrics = state.metrics.clone();
+  let auth = UploadAuth {
+    app: app.clone(),
+    account: state
+      .config
+      .read()
+      .map_err(|_| PdhError::poisoned())?
+      .account
+      .clone(),
+    config: state.config.clone(),
+    http: state.http.clone(),
+    session: state.session.clone(),
+  };
   let app_handle = app.clone();
-  tauri::async_runtime::spawn(async move {
-    run_upload_task_from_path(
-      app_handle,
-      tasks,
-      task_id,
-      tx,
-      rx,
-      backend,
-      token,
-      file_path,
-      category,
-    )
-    .await;
-  });
+  // 整个任务共用一个请求 ID：事件、日志与发往后端的各次请求都能对上
+  let task = run_upload_task_from_path(
+    app_handle,
+    tasks,
+    task_id,
+    tx,
+    rx,
+    config,
+    http,
+    metrics,
+    auth,
+    file_path,
+    category,
+  );
+  tauri::async_runtime::spawn(request_id::scope(request_id::generate(), task));
 
   Ok(())
 }
 
 #[tauri::command]
-async fn pdh_attachment_upload_task_pause(state: State<'_, GatewayState>, taskId: String) -> Result<(), String> {
+async fn pdh_attachment_upload_task_pause(state: State<'_, GatewayState>, taskId: String) -> PdhResult<()> {
   #[allow(non_snake_case)]
   let task_id = taskId.trim().to_string();
   let guard = state.upload_tasks.lock().await;
@@ -825,12 +1894,12 @@ async fn pdh_attachment_upload_task_pause(state: State<'_, GatewayState>, taskId
     let _ = h.tx.send(UploadRunState::Paused);
     Ok(())
   } else {
-    Err("task not found".to_string())
+    Err(PdhError::not_found("task not found"))
   }
 }
 
 #[tauri::command]
-async fn pdh_attachment_upload_task_resume(state: State<'_, GatewayState>, taskId: String) -> Result<(), String> {
+async fn pdh_attachment_upload_task_resume(state: State<'_, GatewayState>, taskId: String) -> PdhResult<()> {
   #[allow(non_snake_case)]
   let task_id = taskId.trim().to_string();
   let guard = state.upload_tasks.lock().await;
@@ -838,12 +1907,12 @@ async fn pdh_attachment_upload_task_resume(state: State<'_, GatewayState>, taskI
     let _ = h.tx.send(UploadRunState::Running);
     Ok(())
   } else {
-    Err("task not found".to_string())
+    Err(PdhError::not_found("task not found"))
   }
 }
 
 #[tauri::command]
-async fn pdh_attachment_upload_task_cancel(state: State<'_, GatewayState>, taskId: String) -> Result<(), String> {
+async fn pdh_attachment_upload_task_cancel(state: State<'_, GatewayState>, taskId: String) -> PdhResult<()> {
   #[allow(non_snake_case)]
   let task_id = taskId.trim().to_string();
   let guard = state.upload_tasks.lock().await;
@@ -851,39 +1920,70 @@ async fn pdh_attachment_upload_task_cancel(state: State<'_, GatewayState>, taskI
     let _ = h.tx.send(UploadRunState::Canceled);
     Ok(())
   } else {
-    Err("task not found".to_string())
+    Err(PdhError::not_found("task not found"))
   }
 }
 
 #[tauri::command]
 async fn pdh_auth_login(
+  app: tauri::AppHandle,
   state: State<'_, GatewayState>,
   username: String,
   password: String,
-) -> Result<serde_json::Value, String> {
+) -> PdhResult<serde_json::Value> {
   let backend = backend_base_url_from_state(&state)?;
-  let url = format!("{}/api/auth/login", backend);
+  login_upstream(&app, &state, &backend, &username, &password).await
+}
 
-  let client = reqwest::Client::new();
-  let resp = client
-    .post(url)
+/// 用 `pdh_secret_set_password` 保存的密码登录当前服务器；密码全程不经过前端。
+#[tauri::command]
+async fn pdh_auth_login_with_saved_credentials(
+  app: tauri::AppHandle,
+  state: State<'_, GatewayState>,
+  username: String,
+) -> PdhResult<serde_json::Value> {
+  let backend = backend_base_url_from_state(&state)?;
+  let user = username.trim().to_string();
+  if user.is_empty() {
+    return Err(PdhError::invalid_argument("username is empty"));
+  }
+  let password = load_password_for_backend(&backend, &user)?
+    .ok_or_else(|| PdhError::new(ErrorCode::NotAuthenticated, "no saved password"))?;
+  login_upstream(&app, &state, &backend, &user, &password).await
+}
+
+/// 向后端登录：refresh token 存入钥匙串，access token 交给网关，只把用户信息返回给前端。
+async fn login_upstream(
+  app: &tauri::AppHandle,
+  state: &State<'_, GatewayState>,
+  backend: &str,
+  username: &str,
+  password: &str,
+) -> PdhResult<serde_json::Value> {
+  let url = format!("{}/api/auth/login", upstream_base_url_from_config(&state.config)?);
+
+  let resp = state
+    .http
+    .with_request_timeout(state.http.get().post(url))
     .json(&json!({ "username": username, "password": password }))
     .send()
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
   let status = resp.status();
   let body = resp
     .json::<serde_json::Value>()
-    .await
-    .map_err(|e| e.to_string())?;
+    .await?;
 
   if !status.is_success() {
     let msg = body
       .get("message")
       .and_then(|v| v.as_str())
       .unwrap_or("login failed");
-    return Err(msg.to_string());
+    return Err(
+      PdhError::new(ErrorCode::AuthFailed, msg)
+        .with_details(json!({ "status": status.as_u16() }))
+        .retryable(status.is_server_error()),
+    );
   }
 
   let token = body
@@ -900,13 +2000,18 @@ async fn pdh_auth_login(
     .map(|s| s.to_string());
 
   // 保存 refresh token 到系统凭据库；前端永不持有
-  store_refresh_token_for_backend(&backend, refresh_token)?;
+  store_refresh_token_for_backend(backend, Some(username), refresh_token)?;
+  let _ = local_data::record_profile_username(app, backend, username);
 
-  // 同步更新网关当前 bearer token（尽快生效）
+  // access token 只交给网关（不返回给前端），并按有效期安排主动刷新
   if !token.is_empty() {
     if let Ok(mut cfg) = state.config.write() {
       cfg.bearer_token = Some(token.clone());
+      cfg.account = Some(username.trim().to_string());
     }
+    state
+      .token_lifecycle
+      .schedule(backend, body.get("data").and_then(|d| d.get("expiresIn")));
   }
 
   let sanitized = json!({
@@ -914,7 +2019,6 @@ async fn pdh_auth_login(
     "data": {
       "user": body.get("data").and_then(|d| d.get("user")).cloned().unwrap_or(json!(null)),
       "expiresIn": body.get("data").and_then(|d| d.get("expiresIn")).cloned().unwrap_or(json!("")),
-      "token": token,
     },
     "message": body.get("message").and_then(|v| v.as_str()).unwrap_or("登录成功"),
   });
@@ -923,66 +2027,19 @@ async fn pdh_auth_login(
 }
 
 #[tauri::command]
-async fn pdh_auth_refresh(state: State<'_, GatewayState>) -> Result<serde_json::Value, String> {
-  let backend = backend_base_url_from_state(&state)?;
-  let refresh_token =
-    load_refresh_token_for_backend(&backend)?.ok_or_else(|| "no refresh token".to_string())?;
-  let url = format!("{}/api/auth/refresh", backend);
-
-  let client = reqwest::Client::new();
-  let resp = client
-    .post(url)
-    .json(&json!({ "refreshToken": refresh_token }))
-    .send()
-    .await
-    .map_err(|e| e.to_string())?;
-
-  let status = resp.status();
-  let body = resp
-    .json::<serde_json::Value>()
+async fn pdh_auth_refresh(state: State<'_, GatewayState>) -> PdhResult<serde_json::Value> {
+  // 与网关的 401 自动刷新共用同一把锁，避免前后端同时轮换 refresh token
+  let refreshed = state
+    .session
+    .refresh(&state.http, &state.config, None)
     .await
-    .map_err(|e| e.to_string())?;
-
-  if !status.is_success() {
-    // 401/403：refresh 无效，清理本地 refresh token
-    if status.as_u16() == 401 || status.as_u16() == 403 {
-      let _ = store_refresh_token_for_backend(&backend, None);
-    }
-    let msg = body
-      .get("message")
-      .and_then(|v| v.as_str())
-      .unwrap_or("refresh failed");
-    return Err(msg.to_string());
-  }
-
-  let token = body
-    .get("data")
-    .and_then(|d| d.get("token"))
-    .and_then(|v| v.as_str())
-    .unwrap_or("")
-    .trim()
-    .to_string();
-  let next_refresh = body
-    .get("data")
-    .and_then(|d| d.get("refreshToken"))
-    .and_then(|v| v.as_str())
-    .map(|s| s.to_string());
-
-  if let Some(rt) = next_refresh {
-    store_refresh_token_for_backend(&backend, Some(rt))?;
-  }
-
-  if !token.is_empty() {
-    if let Ok(mut cfg) = state.config.write() {
-      cfg.bearer_token = Some(token.clone());
-    }
-  }
+    .map_err(PdhError::from)?;
+  let body = refreshed.body;
 
   let sanitized = json!({
     "success": body.get("success").and_then(|v| v.as_bool()).unwrap_or(true),
     "data": {
       "expiresIn": body.get("data").and_then(|d| d.get("expiresIn")).cloned().unwrap_or(json!("")),
-      "token": token,
     },
     "message": body.get("message").and_then(|v| v.as_str()).unwrap_or("刷新成功"),
   });
@@ -994,52 +2051,163 @@ async fn pdh_auth_refresh(state: State<'_, GatewayState>) -> Result<serde_json::
 pub fn run() {
   tauri::Builder::default()
     .manage(GatewayState::default())
+    .register_asynchronous_uri_scheme_protocol(gateway::URI_SCHEME, |ctx, request, responder| {
+      let state = ctx.app_handle().state::<GatewayState>();
+      let gw = state.gateway.read().ok().and_then(|guard| guard.clone());
+      let tcp_base = tcp_gateway_url(&state);
+      tauri::async_runtime::spawn(async move {
+        let response = match gw {
+          Some(gw) => gw.handle_scheme_request(request, tcp_base.as_deref()).await,
+          None => gateway::scheme_not_ready(),
+        };
+        responder.respond(response);
+      });
+    })
     .setup(|app| {
-      if cfg!(debug_assertions) {
-        app.handle().plugin(
-          tauri_plugin_log::Builder::default()
-            .level(log::LevelFilter::Info)
-            .build(),
-        )?;
+      // 发布版也写日志：数据目录下的轮转文件，级别可在设置中调整
+      let logs_dir = local_data::logs_dir(app.handle()).ok();
+      app.handle().plugin(logging::plugin(logs_dir.clone()).build())?;
+      let log_settings = local_data::log_settings(app.handle());
+      if let Err(e) = logging::apply_level(&log_settings) {
+        log::set_max_level(log::LevelFilter::Info);
+        log::warn!("[logging] {}", e);
+      }
+      logging::install_panic_hook(logs_dir, app.package_info().version.to_string());
+
+      // asset 协议只覆盖壁纸目录（自定义数据目录在运行时授权）
+      if let Err(e) = local_data::allow_wallpaper_assets(app.handle()) {
+        log::warn!("[local-data] {}", e);
       }
 
-      // Start local gateway (random port, localhost-only)
+      // Start local gateway: pdh:// scheme, plus optional 127.0.0.1 random port
       let state = app.state::<GatewayState>();
-      let cfg = state.config.clone();
-      let addr_store = state.addr.clone();
-      tauri::async_runtime::spawn(async move {
-        match gateway::start_gateway(cfg).await {
-          Ok((addr, _handle)) => {
-            if let Ok(mut guard) = addr_store.write() {
-              *guard = Some(addr);
-            }
-            log::info!("[gateway] started at http://{}", addr);
+      if let Err(e) = state.http.set_network(local_data::network_settings(app.handle())) {
+        log::warn!("[network] invalid settings, using defaults: {}", e);
+      }
+      // 启动即指向上次使用的服务器，不必等前端推送
+      match local_data::active_profile(app.handle()) {
+        Ok(Some(profile)) => {
+          if let Err(e) = client_options_for_profile(&profile).and_then(|o| state.http.configure(o).map_err(PdhError::invalid_argument)) {
+            log::warn!("[profiles] client settings: {}", e);
           }
-          Err(e) => {
-            log::error!("[gateway] failed to start: {}", e);
+          if let Ok(mut cfg) = state.config.write() {
+            cfg.endpoints = profile.endpoint_list();
+            cfg.account = profile.last_username.clone();
+            cfg.legacy_owner = profile.last_username.clone();
+            cfg.backend_base_url = Some(profile.url);
           }
         }
-      });
+        Ok(None) => {}
+        Err(e) => log::warn!("[profiles] load failed: {}", e),
+      }
+      // 离线缓存放在本地数据目录下；解析失败时网关照常工作，只是没有缓存
+      let (api_cache, attachment_cache) = match local_data::cache_dir(app.handle()) {
+        Ok(dir) => (
+          Some(Arc::new(api_cache::ApiCache::new(dir.join("api")))),
+          Some(Arc::new(attachment_cache::AttachmentCache::open(dir.join("attachments")))),
+        ),
+        Err(e) => {
+          log::warn!("[gateway] cache disabled: {}", e);
+          (None, None)
+        }
+      };
+      if let Ok(mut guard) = state.attachment_cache.write() {
+        *guard = attachment_cache.clone();
+      }
+      if let Ok(mut guard) = state.api_cache.write() {
+        *guard = api_cache.clone();
+      }
+      let gw = gateway::Gateway::new(
+        app.handle().clone(),
+        gateway::GatewayDeps {
+          config: state.config.clone(),
+          http: state.http.clone(),
+          session: state.session.clone(),
+          health: state.health.clone(),
+          inspector: state.inspector.clone(),
+          metrics: state.metrics.clone(),
+          api_cache,
+          attachment_cache,
+        },
+      );
+      health::spawn(app.handle().clone(), state.config.clone(), state.http.clone(), state.health.clone());
+      token_lifecycle::spawn(
+        app.handle().clone(),
+        state.config.clone(),
+        state.http.clone(),
+        state.session.clone(),
+        state.health.clone(),
+        state.token_lifecycle.clone(),
+      );
+      if let Ok(mut guard) = state.gateway.write() {
+        *guard = Some(gw.clone());
+      }
+
+      let tcp_enabled = local_data::tcp_gateway_enabled(app.handle());
+      state.tcp_gateway_enabled.store(tcp_enabled, Ordering::Relaxed);
+      if tcp_enabled {
+        let addr_store = state.addr.clone();
+        let gateway_key = state.gateway_key.clone();
+        tauri::async_runtime::spawn(async move {
+          match gw.serve_tcp(gateway_key).await {
+            Ok((addr, _handle)) => {
+              if let Ok(mut guard) = addr_store.write() {
+                *guard = Some(addr);
+              }
+              log::info!("[gateway] started at http://{}", addr);
+            }
+            Err(e) => {
+              log::error!("[gateway] failed to start: {}", e);
+            }
+          }
+        });
+      }
 
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
       pdh_gateway_url,
       pdh_gateway_set_backend_url,
-      pdh_gateway_set_token,
+      pdh_gateway_status,
+      pdh_gateway_diagnose,
+      pdh_gateway_inspect_set_enabled,
+      pdh_gateway_inspect_list,
+      pdh_gateway_inspect_clear,
+      pdh_gateway_metrics,
+      pdh_network_settings_get,
+      pdh_network_settings_set,
+      pdh_log_settings_get,
+      pdh_log_settings_set,
+      pdh_export_diagnostics,
       pdh_upload_attachment_from_path,
       pdh_attachment_upload_task_start,
       pdh_attachment_upload_task_pause,
       pdh_attachment_upload_task_resume,
       pdh_attachment_upload_task_cancel,
       pdh_auth_login,
+      pdh_auth_login_with_saved_credentials,
       pdh_auth_refresh,
       pdh_auth_clear_refresh_token,
+      pdh_auth_logout,
+      pdh_auth_list_accounts,
+      pdh_auth_switch_account,
       pdh_secret_set_password,
-      pdh_secret_get_password,
+      pdh_secret_has_password,
       pdh_secret_delete_password,
+      pdh_attachment_cache_info,
+      pdh_attachment_cache_set_cap,
+      pdh_attachment_cache_purge,
       local_data::pdh_local_data_info,
       local_data::pdh_local_data_migrate,
+      local_data::pdh_local_data_set_tcp_gateway,
+      local_data::pdh_profiles_list,
+      local_data::pdh_profiles_add,
+      pdh_profiles_switch,
+      pdh_profiles_set_endpoints,
+      pdh_profiles_remove,
+      pdh_profiles_set_tls,
+      pdh_profiles_set_proxy,
+      pdh_proxy_test,
       local_data::pdh_theme_presets_list,
       local_data::pdh_theme_presets_save,
       local_data::pdh_theme_presets_delete,
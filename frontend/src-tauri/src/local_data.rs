@@ -7,6 +7,8 @@ use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::Manager;
 
+use crate::error::{ErrorCode, PdhError, PdhResult};
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LocalDataInfo {
@@ -14,6 +16,7 @@ pub struct LocalDataInfo {
   pub default_data_dir: String,
   pub config_path: String,
   pub using_custom_dir: bool,
+  pub tcp_gateway_enabled: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +32,15 @@ pub struct ThemePreset {
 #[serde(rename_all = "camelCase")]
 struct LocalDataConfig {
   data_dir: Option<String>,
+  // 是否额外开启 127.0.0.1 端口上的网关；未设置时默认开启，修改后重启生效
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  tcp_gateway: Option<bool>,
+  // 连接池 / 超时 / HTTP/2 设置；未设置时用默认值
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  network: Option<crate::net::NetworkSettings>,
+  // 日志级别；未设置时为 info，修改后立即生效
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  logging: Option<crate::logging::LogSettings>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -118,6 +130,75 @@ impl Default for TransparencyFile {
   }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendProfile {
+  pub id: String,
+  pub name: String,
+  pub url: String,
+  // 同一服务器的全部访问地址（按优先级，如局域网地址 + 公网域名）；为空时只用 url
+  #[serde(default)]
+  pub endpoints: Vec<String>,
+  // 私有 CA / 证书 pin / mTLS 客户端证书；客户端私钥存系统钥匙串
+  #[serde(default)]
+  pub tls: crate::net::TlsSettings,
+  // 出站代理；代理密码存系统钥匙串
+  #[serde(default)]
+  pub proxy: Option<crate::net::ProxySettings>,
+  pub last_username: Option<String>,
+  // 在该服务器上保存过密码的用户名：删除服务器时据此清理钥匙串
+  #[serde(default)]
+  pub known_usernames: Vec<String>,
+  pub is_default: bool,
+}
+
+impl BackendProfile {
+  pub fn endpoint_list(&self) -> Vec<String> {
+    if self.endpoints.is_empty() {
+      vec![self.url.clone()]
+    } else {
+      self.endpoints.clone()
+    }
+  }
+}
+
+fn normalize_endpoints(endpoints: Vec<String>) -> PdhResult<Vec<String>> {
+  let mut out: Vec<String> = Vec::new();
+  for raw in endpoints {
+    if let Some(url) = crate::normalize_backend_url(&raw)? {
+      if !out.contains(&url) {
+        out.push(url);
+      }
+    }
+  }
+  Ok(out)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfilesFile {
+  version: u32,
+  active_profile_id: Option<String>,
+  profiles: Vec<BackendProfile>,
+}
+
+impl Default for ProfilesFile {
+  fn default() -> Self {
+    Self {
+      version: 1,
+      active_profile_id: None,
+      profiles: Vec::new(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesSnapshot {
+  pub active_profile_id: Option<String>,
+  pub profiles: Vec<BackendProfile>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WallpaperDeleteResult {
@@ -144,19 +225,19 @@ impl Default for ThemePresetsFile {
   }
 }
 
-fn config_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn config_path(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
   let dir = app
     .path()
     .app_config_dir()
-    .map_err(|e| format!("resolve config dir failed: {e}"))?;
+    .map_err(|e| PdhError::io(format!("resolve config dir failed: {e}")))?;
   Ok(dir.join("local-data.json"))
 }
 
-fn default_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn default_data_dir(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
   let dir = app
     .path()
     .app_data_dir()
-    .map_err(|e| format!("resolve app data dir failed: {e}"))?;
+    .map_err(|e| PdhError::io(format!("resolve app data dir failed: {e}")))?;
   Ok(dir.join("data"))
 }
 
@@ -171,16 +252,16 @@ fn load_config(path: &Path) -> LocalDataConfig {
   }
 }
 
-fn save_config(path: &Path, cfg: &LocalDataConfig) -> Result<(), String> {
+fn save_config(path: &Path, cfg: &LocalDataConfig) -> PdhResult<()> {
   if let Some(parent) = path.parent() {
-    fs::create_dir_all(parent).map_err(|e| format!("create config dir failed: {e}"))?;
+    fs::create_dir_all(parent).map_err(|e| PdhError::io(format!("create config dir failed: {e}")))?;
   }
-  let raw = serde_json::to_string_pretty(cfg).map_err(|e| format!("serialize config failed: {e}"))?;
-  fs::write(path, raw).map_err(|e| format!("write config failed: {e}"))?;
+  let raw = serde_json::to_string_pretty(cfg).map_err(|e| PdhError::internal(format!("serialize config failed: {e}")))?;
+  fs::write(path, raw).map_err(|e| PdhError::io(format!("write config failed: {e}")))?;
   Ok(())
 }
 
-fn resolve_data_dir(app: &tauri::AppHandle) -> Result<(PathBuf, PathBuf, PathBuf, bool), String> {
+fn resolve_data_dir(app: &tauri::AppHandle) -> PdhResult<(PathBuf, PathBuf, PathBuf, bool)> {
   let cfg_path = config_path(app)?;
   let default_dir = default_data_dir(app)?;
   let cfg = load_config(&cfg_path);
@@ -193,13 +274,97 @@ fn resolve_data_dir(app: &tauri::AppHandle) -> Result<(PathBuf, PathBuf, PathBuf
   Ok((data_dir, default_dir, cfg_path, using_custom))
 }
 
-fn ensure_dir(path: &Path) -> Result<(), String> {
-  fs::create_dir_all(path).map_err(|e| format!("create dir failed: {e}"))?;
+pub(crate) fn cache_dir(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
+  let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
+  Ok(data_dir.join("cache"))
+}
+
+pub(crate) fn tcp_gateway_enabled(app: &tauri::AppHandle) -> bool {
+  match config_path(app) {
+    Ok(path) => load_config(&path).tcp_gateway.unwrap_or(true),
+    Err(_) => true,
+  }
+}
+
+pub(crate) fn network_settings(app: &tauri::AppHandle) -> crate::net::NetworkSettings {
+  config_path(app)
+    .ok()
+    .and_then(|path| load_config(&path).network)
+    .unwrap_or_default()
+}
+
+pub(crate) fn save_network_settings(app: &tauri::AppHandle, settings: &crate::net::NetworkSettings) -> PdhResult<()> {
+  let cfg_path = config_path(app)?;
+  let mut cfg = load_config(&cfg_path);
+  cfg.network = Some(settings.clone());
+  save_config(&cfg_path, &cfg)
+}
+
+pub(crate) fn log_settings(app: &tauri::AppHandle) -> crate::logging::LogSettings {
+  config_path(app)
+    .ok()
+    .and_then(|path| load_config(&path).logging)
+    .unwrap_or_default()
+}
+
+pub(crate) fn save_log_settings(app: &tauri::AppHandle, settings: &crate::logging::LogSettings) -> PdhResult<()> {
+  let cfg_path = config_path(app)?;
+  let mut cfg = load_config(&cfg_path);
+  cfg.logging = Some(settings.clone());
+  save_config(&cfg_path, &cfg)
+}
+
+/// 日志与崩溃报告放在数据目录下；迁移数据目录后重启才会切换。
+pub(crate) fn logs_dir(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
+  let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
+  Ok(data_dir.join("logs"))
+}
+
+/// 诊断包里的设置快照（未脱敏，由调用方处理）：本地数据配置与服务器列表。
+pub(crate) fn settings_snapshot(app: &tauri::AppHandle) -> PdhResult<serde_json::Value> {
+  let (data_dir, _default, cfg_path, using_custom) = resolve_data_dir(app)?;
+  let config = load_config(&cfg_path);
+  let profiles = load_profiles(app)?;
+  Ok(serde_json::json!({
+    "dataDir": data_dir.to_string_lossy(),
+    "usingCustomDir": using_custom,
+    "localData": config,
+    "profiles": profiles,
+  }))
+}
+
+/// asset 协议只开放壁纸图片目录（cache/、logs/ 等不可读）；静态 scope 只覆盖默认数据目录，
+/// 自定义数据目录在运行时补充授权。
+pub(crate) fn allow_wallpaper_assets(app: &tauri::AppHandle) -> PdhResult<()> {
+  app
+    .asset_protocol_scope()
+    .allow_directory(wallpapers_images_dir(app)?, true)
+    .map_err(|e| PdhError::internal(format!("allow asset scope failed: {e}")))
+}
+
+/// 按文件名解析壁纸图片路径，只允许 themes/wallpapers 目录内的普通文件。
+pub(crate) fn wallpaper_file_path(app: &tauri::AppHandle, name: &str) -> PdhResult<PathBuf> {
+  let rel = Path::new(name);
+  if name.trim().is_empty() || rel.components().count() != 1 || !ensure_relative_path_no_escape(rel) {
+    return Err(PdhError::invalid_argument("invalid wallpaper file name"));
+  }
+
+  let dir = wallpapers_images_dir(app)?;
+  let dir_canon = fs::canonicalize(&dir).map_err(|e| PdhError::io(format!("resolve wallpapers dir failed: {e}")))?;
+  let file_canon = fs::canonicalize(dir.join(rel)).map_err(|_| PdhError::not_found("wallpaper not found"))?;
+  if !file_canon.starts_with(&dir_canon) || !file_canon.is_file() {
+    return Err(PdhError::not_found("wallpaper not found"));
+  }
+  Ok(file_canon)
+}
+
+fn ensure_dir(path: &Path) -> PdhResult<()> {
+  fs::create_dir_all(path).map_err(|e| PdhError::io(format!("create dir failed: {e}")))?;
   Ok(())
 }
 
-fn is_dir_empty(path: &Path) -> Result<bool, String> {
-  let mut it = fs::read_dir(path).map_err(|e| format!("read dir failed: {e}"))?;
+fn is_dir_empty(path: &Path) -> PdhResult<bool> {
+  let mut it = fs::read_dir(path).map_err(|e| PdhError::io(format!("read dir failed: {e}")))?;
   Ok(it.next().is_none())
 }
 
@@ -207,14 +372,14 @@ fn canonicalize_if_exists(path: &Path) -> Option<PathBuf> {
   fs::canonicalize(path).ok()
 }
 
-fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
+fn copy_dir_recursive(src: &Path, dst: &Path) -> PdhResult<()> {
   ensure_dir(dst)?;
 
-  for entry in fs::read_dir(src).map_err(|e| format!("read dir failed: {e}"))? {
-    let entry = entry.map_err(|e| format!("read dir entry failed: {e}"))?;
+  for entry in fs::read_dir(src).map_err(|e| PdhError::io(format!("read dir failed: {e}")))? {
+    let entry = entry.map_err(|e| PdhError::io(format!("read dir entry failed: {e}")))?;
     let ty = entry
       .file_type()
-      .map_err(|e| format!("read file type failed: {e}"))?;
+      .map_err(|e| PdhError::io(format!("read file type failed: {e}")))?;
     let src_path = entry.path();
     let dst_path = dst.join(entry.file_name());
 
@@ -225,16 +390,16 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
 
     if ty.is_file() {
       if dst_path.exists() {
-        return Err(format!(
-          "目标路径已存在同名文件：{}",
-          dst_path.to_string_lossy()
-        ));
+        return Err(
+          PdhError::new(ErrorCode::Conflict, "target file already exists")
+            .with_details(serde_json::json!({ "path": dst_path.to_string_lossy() })),
+        );
       }
       if let Some(parent) = dst_path.parent() {
         ensure_dir(parent)?;
       }
       fs::copy(&src_path, &dst_path)
-        .map_err(|e| format!("copy file failed ({}): {e}", src_path.to_string_lossy()))?;
+        .map_err(|e| PdhError::io(format!("copy file failed ({}): {e}", src_path.to_string_lossy())))?;
       continue;
     }
   }
@@ -242,17 +407,17 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
   Ok(())
 }
 
-fn presets_file_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn presets_file_path(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
   let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
   Ok(data_dir.join("themes").join("presets.json"))
 }
 
-fn wallpapers_file_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn wallpapers_file_path(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
   let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
   Ok(data_dir.join("themes").join("wallpapers.json"))
 }
 
-fn wallpapers_images_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn wallpapers_images_dir(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
   let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
   Ok(data_dir.join("themes").join("wallpapers"))
 }
@@ -318,9 +483,9 @@ fn write_wallpaper_bytes(
   original_name: &str,
   mime_type: &str,
   bytes: &[u8],
-) -> Result<PathBuf, String> {
+) -> PdhResult<PathBuf> {
   if id.trim().is_empty() {
-    return Err("wallpaper id is empty".to_string());
+    return Err(PdhError::invalid_argument("wallpaper id is empty"));
   }
 
   let dir = wallpapers_images_dir(app)?;
@@ -333,15 +498,15 @@ fn write_wallpaper_bytes(
   let file_name = format!("{id}.{ext}");
   let rel = Path::new(&file_name);
   if !ensure_relative_path_no_escape(rel) {
-    return Err("invalid wallpaper file name".to_string());
+    return Err(PdhError::invalid_argument("invalid wallpaper file name"));
   }
 
   let path = dir.join(rel);
-  fs::write(&path, bytes).map_err(|e| format!("write wallpaper file failed: {e}"))?;
+  fs::write(&path, bytes).map_err(|e| PdhError::io(format!("write wallpaper file failed: {e}")))?;
   Ok(path)
 }
 
-fn maybe_delete_wallpaper_file(app: &tauri::AppHandle, url: &str) -> Result<(), String> {
+fn maybe_delete_wallpaper_file(app: &tauri::AppHandle, url: &str) -> PdhResult<()> {
   let raw = url.trim();
   if raw.is_empty() || raw.starts_with("data:") {
     return Ok(());
@@ -366,11 +531,179 @@ fn maybe_delete_wallpaper_file(app: &tauri::AppHandle, url: &str) -> Result<(),
   Ok(())
 }
 
-fn transparency_file_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn transparency_file_path(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
   let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
   Ok(data_dir.join("themes").join("transparency.json"))
 }
 
+fn profiles_file_path(app: &tauri::AppHandle) -> PdhResult<PathBuf> {
+  let (data_dir, _default, _cfg_path, _custom) = resolve_data_dir(app)?;
+  Ok(data_dir.join("profiles.json"))
+}
+
+fn load_profiles(app: &tauri::AppHandle) -> PdhResult<ProfilesFile> {
+  let path = profiles_file_path(app)?;
+  let raw = match fs::read_to_string(&path) {
+    Ok(s) => s,
+    Err(_) => return Ok(ProfilesFile::default()),
+  };
+  let parsed = serde_json::from_str::<ProfilesFile>(&raw).unwrap_or_default();
+  Ok(parsed)
+}
+
+fn save_profiles(app: &tauri::AppHandle, file: &ProfilesFile) -> PdhResult<()> {
+  let path = profiles_file_path(app)?;
+  if let Some(parent) = path.parent() {
+    ensure_dir(parent)?;
+  }
+  let raw = serde_json::to_string_pretty(file).map_err(|e| PdhError::internal(format!("serialize profiles failed: {e}")))?;
+  fs::write(path, raw).map_err(|e| PdhError::io(format!("write profiles failed: {e}")))?;
+  Ok(())
+}
+
+fn normalize_profiles_file(file: &mut ProfilesFile) {
+  // 默认服务器有且只有一个：没有标记时取第一个
+  let default_id = file
+    .profiles
+    .iter()
+    .find(|p| p.is_default)
+    .or_else(|| file.profiles.first())
+    .map(|p| p.id.clone());
+  for profile in file.profiles.iter_mut() {
+    profile.is_default = Some(&profile.id) == default_id.as_ref();
+  }
+
+  let active_valid = file
+    .active_profile_id
+    .as_ref()
+    .map(|id| file.profiles.iter().any(|p| &p.id == id))
+    .unwrap_or(false);
+  if !active_valid {
+    file.active_profile_id = default_id;
+  }
+}
+
+fn profiles_snapshot(file: ProfilesFile) -> ProfilesSnapshot {
+  ProfilesSnapshot {
+    active_profile_id: file.active_profile_id,
+    profiles: file.profiles,
+  }
+}
+
+/// 当前选中的服务器（没有选中时为默认服务器）。
+pub(crate) fn active_profile(app: &tauri::AppHandle) -> PdhResult<Option<BackendProfile>> {
+  let mut file = load_profiles(app)?;
+  normalize_profiles_file(&mut file);
+  let active = file.active_profile_id.clone();
+  Ok(file.profiles.into_iter().find(|p| Some(&p.id) == active.as_ref()))
+}
+
+pub(crate) fn find_profile(app: &tauri::AppHandle, id: &str) -> PdhResult<BackendProfile> {
+  load_profiles(app)?
+    .profiles
+    .into_iter()
+    .find(|p| p.id == id)
+    .ok_or_else(|| PdhError::not_found("profile not found"))
+}
+
+pub(crate) fn set_active_profile(app: &tauri::AppHandle, id: &str) -> PdhResult<ProfilesSnapshot> {
+  let mut file = load_profiles(app)?;
+  if !file.profiles.iter().any(|p| p.id == id) {
+    return Err(PdhError::not_found("profile not found"));
+  }
+  file.active_profile_id = Some(id.to_string());
+  normalize_profiles_file(&mut file);
+  save_profiles(app, &file)?;
+  Ok(profiles_snapshot(file))
+}
+
+/// 从列表中移除服务器，返回被移除的条目（调用方负责清理钥匙串）。
+pub(crate) fn take_profile(app: &tauri::AppHandle, id: &str) -> PdhResult<(BackendProfile, ProfilesSnapshot)> {
+  let mut file = load_profiles(app)?;
+  let idx = file
+    .profiles
+    .iter()
+    .position(|p| p.id == id)
+    .ok_or_else(|| PdhError::not_found("profile not found"))?;
+  let removed = file.profiles.remove(idx);
+  normalize_profiles_file(&mut file);
+  save_profiles(app, &file)?;
+  Ok((removed, profiles_snapshot(file)))
+}
+
+pub(crate) fn set_profile_endpoints(
+  app: &tauri::AppHandle,
+  id: &str,
+  endpoints: Vec<String>,
+) -> PdhResult<BackendProfile> {
+  let endpoints = normalize_endpoints(endpoints)?;
+  let mut file = load_profiles(app)?;
+  let profile = file
+    .profiles
+    .iter_mut()
+    .find(|p| p.id == id)
+    .ok_or_else(|| PdhError::not_found("profile not found"))?;
+  profile.endpoints = endpoints;
+  let updated = profile.clone();
+  save_profiles(app, &file)?;
+  Ok(updated)
+}
+
+pub(crate) fn set_profile_tls(
+  app: &tauri::AppHandle,
+  id: &str,
+  tls: crate::net::TlsSettings,
+) -> PdhResult<BackendProfile> {
+  let mut file = load_profiles(app)?;
+  let profile = file
+    .profiles
+    .iter_mut()
+    .find(|p| p.id == id)
+    .ok_or_else(|| PdhError::not_found("profile not found"))?;
+  profile.tls = tls;
+  let updated = profile.clone();
+  save_profiles(app, &file)?;
+  Ok(updated)
+}
+
+pub(crate) fn set_profile_proxy(
+  app: &tauri::AppHandle,
+  id: &str,
+  proxy: Option<crate::net::ProxySettings>,
+) -> PdhResult<BackendProfile> {
+  let mut file = load_profiles(app)?;
+  let profile = file
+    .profiles
+    .iter_mut()
+    .find(|p| p.id == id)
+    .ok_or_else(|| PdhError::not_found("profile not found"))?;
+  profile.proxy = proxy;
+  let updated = profile.clone();
+  save_profiles(app, &file)?;
+  Ok(updated)
+}
+
+pub(crate) fn find_profile_by_url(app: &tauri::AppHandle, url: &str) -> PdhResult<Option<BackendProfile>> {
+  Ok(load_profiles(app)?.profiles.into_iter().find(|p| p.url == url))
+}
+
+/// 登录成功 / 保存密码后记录用户名；url 不在列表中时忽略。
+pub(crate) fn record_profile_username(app: &tauri::AppHandle, url: &str, username: &str) -> PdhResult<()> {
+  let user = username.trim();
+  if user.is_empty() {
+    return Ok(());
+  }
+  let mut file = load_profiles(app)?;
+  let Some(profile) = file.profiles.iter_mut().find(|p| p.url == url) else {
+    return Ok(());
+  };
+  profile.last_username = Some(user.to_string());
+  if !profile.known_usernames.iter().any(|u| u == user) {
+    profile.known_usernames.push(user.to_string());
+  }
+  save_profiles(app, &file)
+}
+
 fn generate_local_id(prefix: &str) -> String {
   let millis = SystemTime::now()
     .duration_since(UNIX_EPOCH)
@@ -387,7 +720,7 @@ fn normalize_transparency_value(value: TransparencyValue) -> TransparencyValue {
   }
 }
 
-fn load_wallpapers(app: &tauri::AppHandle) -> Result<WallpapersFile, String> {
+fn load_wallpapers(app: &tauri::AppHandle) -> PdhResult<WallpapersFile> {
   let path = wallpapers_file_path(app)?;
   let raw = match fs::read_to_string(&path) {
     Ok(s) => s,
@@ -397,13 +730,13 @@ fn load_wallpapers(app: &tauri::AppHandle) -> Result<WallpapersFile, String> {
   Ok(parsed)
 }
 
-fn save_wallpapers(app: &tauri::AppHandle, file: &WallpapersFile) -> Result<(), String> {
+fn save_wallpapers(app: &tauri::AppHandle, file: &WallpapersFile) -> PdhResult<()> {
   let path = wallpapers_file_path(app)?;
   if let Some(parent) = path.parent() {
     ensure_dir(parent)?;
   }
-  let raw = serde_json::to_string_pretty(file).map_err(|e| format!("serialize wallpapers failed: {e}"))?;
-  fs::write(path, raw).map_err(|e| format!("write wallpapers failed: {e}"))?;
+  let raw = serde_json::to_string_pretty(file).map_err(|e| PdhError::internal(format!("serialize wallpapers failed: {e}")))?;
+  fs::write(path, raw).map_err(|e| PdhError::io(format!("write wallpapers failed: {e}")))?;
   Ok(())
 }
 
@@ -411,7 +744,7 @@ fn sort_wallpapers_desc(wallpapers: &mut Vec<LocalWallpaper>) {
   wallpapers.sort_by(|a, b| b.created_at.cmp(&a.created_at));
 }
 
-fn migrate_wallpaper_data_urls(app: &tauri::AppHandle, file: &mut WallpapersFile) -> Result<(), String> {
+fn migrate_wallpaper_data_urls(app: &tauri::AppHandle, file: &mut WallpapersFile) -> PdhResult<()> {
   for wallpaper in file.wallpapers.iter_mut() {
     let raw = wallpaper.url.trim();
     if !raw.starts_with("data:") {
@@ -441,7 +774,7 @@ fn migrate_wallpaper_data_urls(app: &tauri::AppHandle, file: &mut WallpapersFile
   Ok(())
 }
 
-fn normalize_wallpapers_file(app: &tauri::AppHandle, file: &mut WallpapersFile) -> Result<(), String> {
+fn normalize_wallpapers_file(app: &tauri::AppHandle, file: &mut WallpapersFile) -> PdhResult<()> {
   migrate_wallpaper_data_urls(app, file)?;
   sort_wallpapers_desc(&mut file.wallpapers);
 
@@ -476,7 +809,7 @@ fn normalize_wallpapers_file(app: &tauri::AppHandle, file: &mut WallpapersFile)
   Ok(())
 }
 
-fn load_transparency(app: &tauri::AppHandle) -> Result<TransparencyFile, String> {
+fn load_transparency(app: &tauri::AppHandle) -> PdhResult<TransparencyFile> {
   let path = transparency_file_path(app)?;
   let raw = match fs::read_to_string(&path) {
     Ok(s) => s,
@@ -497,18 +830,18 @@ fn load_transparency(app: &tauri::AppHandle) -> Result<TransparencyFile, String>
   Ok(parsed)
 }
 
-fn save_transparency(app: &tauri::AppHandle, file: &TransparencyFile) -> Result<(), String> {
+fn save_transparency(app: &tauri::AppHandle, file: &TransparencyFile) -> PdhResult<()> {
   let path = transparency_file_path(app)?;
   if let Some(parent) = path.parent() {
     ensure_dir(parent)?;
   }
-  let raw = serde_json::to_string_pretty(file).map_err(|e| format!("serialize transparency failed: {e}"))?;
-  fs::write(path, raw).map_err(|e| format!("write transparency failed: {e}"))?;
+  let raw = serde_json::to_string_pretty(file).map_err(|e| PdhError::internal(format!("serialize transparency failed: {e}")))?;
+  fs::write(path, raw).map_err(|e| PdhError::io(format!("write transparency failed: {e}")))?;
   Ok(())
 }
 
 #[tauri::command]
-pub fn pdh_wallpapers_list(app: tauri::AppHandle) -> Result<Vec<LocalWallpaper>, String> {
+pub fn pdh_wallpapers_list(app: tauri::AppHandle) -> PdhResult<Vec<LocalWallpaper>> {
   let mut file = load_wallpapers(&app)?;
   normalize_wallpapers_file(&app, &mut file)?;
   save_wallpapers(&app, &file)?;
@@ -516,7 +849,7 @@ pub fn pdh_wallpapers_list(app: tauri::AppHandle) -> Result<Vec<LocalWallpaper>,
 }
 
 #[tauri::command]
-pub fn pdh_wallpapers_get_current(app: tauri::AppHandle) -> Result<Option<LocalWallpaper>, String> {
+pub fn pdh_wallpapers_get_current(app: tauri::AppHandle) -> PdhResult<Option<LocalWallpaper>> {
   let mut file = load_wallpapers(&app)?;
   normalize_wallpapers_file(&app, &mut file)?;
   save_wallpapers(&app, &file)?;
@@ -532,11 +865,11 @@ pub fn pdh_wallpapers_create(
   description: String,
   created_at: String,
   updated_at: String,
-) -> Result<LocalWallpaper, String> {
+) -> PdhResult<LocalWallpaper> {
   let mut file = load_wallpapers(&app)?;
 
   if bytes.is_empty() {
-    return Err("wallpaper bytes is empty".to_string());
+    return Err(PdhError::invalid_argument("wallpaper bytes is empty"));
   }
 
   let id = generate_local_id("local");
@@ -569,21 +902,21 @@ pub fn pdh_wallpapers_create(
     .iter()
     .find(|item| item.id == next.id)
     .cloned()
-    .ok_or_else(|| "create wallpaper failed".to_string())?;
+    .ok_or_else(|| PdhError::internal("create wallpaper failed"))?;
 
   Ok(created)
 }
 
 #[tauri::command]
-pub fn pdh_wallpapers_set_current(app: tauri::AppHandle, id: String) -> Result<LocalWallpaper, String> {
+pub fn pdh_wallpapers_set_current(app: tauri::AppHandle, id: String) -> PdhResult<LocalWallpaper> {
   let target = id.trim();
   if target.is_empty() {
-    return Err("wallpaper id is empty".to_string());
+    return Err(PdhError::invalid_argument("wallpaper id is empty"));
   }
 
   let mut file = load_wallpapers(&app)?;
   if !file.wallpapers.iter().any(|item| item.id == target) {
-    return Err("wallpaper not found".to_string());
+    return Err(PdhError::not_found("wallpaper not found"));
   }
 
   file.current_wallpaper_id = Some(target.to_string());
@@ -594,14 +927,14 @@ pub fn pdh_wallpapers_set_current(app: tauri::AppHandle, id: String) -> Result<L
     .wallpapers
     .into_iter()
     .find(|item| item.id == target)
-    .ok_or_else(|| "wallpaper not found".to_string())
+    .ok_or_else(|| PdhError::not_found("wallpaper not found"))
 }
 
 #[tauri::command]
-pub fn pdh_wallpapers_delete(app: tauri::AppHandle, id: String) -> Result<WallpaperDeleteResult, String> {
+pub fn pdh_wallpapers_delete(app: tauri::AppHandle, id: String) -> PdhResult<WallpaperDeleteResult> {
   let target = id.trim();
   if target.is_empty() {
-    return Err("wallpaper id is empty".to_string());
+    return Err(PdhError::invalid_argument("wallpaper id is empty"));
   }
 
   let mut file = load_wallpapers(&app)?;
@@ -615,7 +948,7 @@ pub fn pdh_wallpapers_delete(app: tauri::AppHandle, id: String) -> Result<Wallpa
   file.wallpapers.retain(|item| item.id != target);
 
   if file.wallpapers.len() == before {
-    return Err("wallpaper not found".to_string());
+    return Err(PdhError::not_found("wallpaper not found"));
   }
 
   if file.current_wallpaper_id.as_deref() == Some(target) {
@@ -642,10 +975,10 @@ pub fn pdh_wallpapers_update_description(
   id: String,
   description: String,
   updated_at: String,
-) -> Result<LocalWallpaper, String> {
+) -> PdhResult<LocalWallpaper> {
   let target = id.trim();
   if target.is_empty() {
-    return Err("wallpaper id is empty".to_string());
+    return Err(PdhError::invalid_argument("wallpaper id is empty"));
   }
 
   let mut file = load_wallpapers(&app)?;
@@ -661,7 +994,7 @@ pub fn pdh_wallpapers_update_description(
   }
 
   if !found {
-    return Err("wallpaper not found".to_string());
+    return Err(PdhError::not_found("wallpaper not found"));
   }
 
   normalize_wallpapers_file(&app, &mut file)?;
@@ -671,11 +1004,11 @@ pub fn pdh_wallpapers_update_description(
     .wallpapers
     .into_iter()
     .find(|item| item.id == target)
-    .ok_or_else(|| "wallpaper not found".to_string())
+    .ok_or_else(|| PdhError::not_found("wallpaper not found"))
 }
 
 #[tauri::command]
-pub fn pdh_wallpapers_stats(app: tauri::AppHandle) -> Result<WallpaperStats, String> {
+pub fn pdh_wallpapers_stats(app: tauri::AppHandle) -> PdhResult<WallpaperStats> {
   let mut file = load_wallpapers(&app)?;
   normalize_wallpapers_file(&app, &mut file)?;
   save_wallpapers(&app, &file)?;
@@ -702,7 +1035,7 @@ pub fn pdh_wallpapers_stats(app: tauri::AppHandle) -> Result<WallpaperStats, Str
 }
 
 #[tauri::command]
-pub fn pdh_transparency_get_current(app: tauri::AppHandle) -> Result<Option<TransparencyValue>, String> {
+pub fn pdh_transparency_get_current(app: tauri::AppHandle) -> PdhResult<Option<TransparencyValue>> {
   let file = load_transparency(&app)?;
   Ok(file.current)
 }
@@ -711,7 +1044,7 @@ pub fn pdh_transparency_get_current(app: tauri::AppHandle) -> Result<Option<Tran
 pub fn pdh_transparency_set_current(
   app: tauri::AppHandle,
   transparency: TransparencyValue,
-) -> Result<TransparencyValue, String> {
+) -> PdhResult<TransparencyValue> {
   let mut file = load_transparency(&app)?;
   let normalized = normalize_transparency_value(transparency);
   file.current = Some(normalized.clone());
@@ -720,7 +1053,7 @@ pub fn pdh_transparency_set_current(
 }
 
 #[tauri::command]
-pub fn pdh_transparency_clear_current(app: tauri::AppHandle) -> Result<(), String> {
+pub fn pdh_transparency_clear_current(app: tauri::AppHandle) -> PdhResult<()> {
   let mut file = load_transparency(&app)?;
   file.current = None;
   save_transparency(&app, &file)?;
@@ -728,7 +1061,7 @@ pub fn pdh_transparency_clear_current(app: tauri::AppHandle) -> Result<(), Strin
 }
 
 #[tauri::command]
-pub fn pdh_transparency_list_configs(app: tauri::AppHandle) -> Result<Vec<TransparencyConfigRecord>, String> {
+pub fn pdh_transparency_list_configs(app: tauri::AppHandle) -> PdhResult<Vec<TransparencyConfigRecord>> {
   let file = load_transparency(&app)?;
   Ok(file.configs)
 }
@@ -737,7 +1070,7 @@ pub fn pdh_transparency_list_configs(app: tauri::AppHandle) -> Result<Vec<Transp
 pub fn pdh_transparency_get_config(
   app: tauri::AppHandle,
   name: String,
-) -> Result<Option<TransparencyConfigRecord>, String> {
+) -> PdhResult<Option<TransparencyConfigRecord>> {
   let target = name.trim();
   if target.is_empty() {
     return Ok(None);
@@ -755,10 +1088,10 @@ pub fn pdh_transparency_save_config(
   transparency: TransparencyValue,
   created_at: String,
   updated_at: String,
-) -> Result<TransparencyConfigRecord, String> {
+) -> PdhResult<TransparencyConfigRecord> {
   let target = name.trim();
   if target.is_empty() {
-    return Err("config name is empty".to_string());
+    return Err(PdhError::invalid_argument("config name is empty"));
   }
 
   let mut file = load_transparency(&app)?;
@@ -784,11 +1117,11 @@ pub fn pdh_transparency_save_config(
     .configs
     .into_iter()
     .find(|item| item.name == target)
-    .ok_or_else(|| "save transparency config failed".to_string())
+    .ok_or_else(|| PdhError::internal("save transparency config failed"))
 }
 
 #[tauri::command]
-pub fn pdh_transparency_delete_config(app: tauri::AppHandle, name: String) -> Result<(), String> {
+pub fn pdh_transparency_delete_config(app: tauri::AppHandle, name: String) -> PdhResult<()> {
   let target = name.trim();
   if target.is_empty() {
     return Ok(());
@@ -800,7 +1133,7 @@ pub fn pdh_transparency_delete_config(app: tauri::AppHandle, name: String) -> Re
   Ok(())
 }
 
-fn load_presets(app: &tauri::AppHandle) -> Result<ThemePresetsFile, String> {
+fn load_presets(app: &tauri::AppHandle) -> PdhResult<ThemePresetsFile> {
   let path = presets_file_path(app)?;
   let raw = match fs::read_to_string(&path) {
     Ok(s) => s,
@@ -810,18 +1143,18 @@ fn load_presets(app: &tauri::AppHandle) -> Result<ThemePresetsFile, String> {
   Ok(parsed)
 }
 
-fn save_presets(app: &tauri::AppHandle, file: &ThemePresetsFile) -> Result<(), String> {
+fn save_presets(app: &tauri::AppHandle, file: &ThemePresetsFile) -> PdhResult<()> {
   let path = presets_file_path(app)?;
   if let Some(parent) = path.parent() {
     ensure_dir(parent)?;
   }
-  let raw = serde_json::to_string_pretty(file).map_err(|e| format!("serialize presets failed: {e}"))?;
-  fs::write(path, raw).map_err(|e| format!("write presets failed: {e}"))?;
+  let raw = serde_json::to_string_pretty(file).map_err(|e| PdhError::internal(format!("serialize presets failed: {e}")))?;
+  fs::write(path, raw).map_err(|e| PdhError::io(format!("write presets failed: {e}")))?;
   Ok(())
 }
 
 #[tauri::command]
-pub fn pdh_local_data_info(app: tauri::AppHandle) -> Result<LocalDataInfo, String> {
+pub fn pdh_local_data_info(app: tauri::AppHandle) -> PdhResult<LocalDataInfo> {
   let (data_dir, default_dir, cfg_path, using_custom) = resolve_data_dir(&app)?;
   ensure_dir(&data_dir)?;
 
@@ -830,14 +1163,15 @@ pub fn pdh_local_data_info(app: tauri::AppHandle) -> Result<LocalDataInfo, Strin
     default_data_dir: default_dir.to_string_lossy().to_string(),
     config_path: cfg_path.to_string_lossy().to_string(),
     using_custom_dir: using_custom,
+    tcp_gateway_enabled: load_config(&cfg_path).tcp_gateway.unwrap_or(true),
   })
 }
 
 #[tauri::command]
-pub fn pdh_local_data_migrate(app: tauri::AppHandle, target_base_dir: String) -> Result<LocalDataInfo, String> {
+pub fn pdh_local_data_migrate(app: tauri::AppHandle, target_base_dir: String) -> PdhResult<LocalDataInfo> {
   let base_raw = target_base_dir.trim();
   if base_raw.is_empty() {
-    return Err("target_base_dir is empty".to_string());
+    return Err(PdhError::invalid_argument("target_base_dir is empty"));
   }
 
   let (src_dir, _default_dir, cfg_path, _using_custom) = resolve_data_dir(&app)?;
@@ -845,7 +1179,7 @@ pub fn pdh_local_data_migrate(app: tauri::AppHandle, target_base_dir: String) ->
 
   let base = PathBuf::from(base_raw);
   if base.as_os_str().is_empty() {
-    return Err("target_base_dir is invalid".to_string());
+    return Err(PdhError::invalid_argument("target_base_dir is invalid"));
   }
   ensure_dir(&base)?;
 
@@ -853,10 +1187,10 @@ pub fn pdh_local_data_migrate(app: tauri::AppHandle, target_base_dir: String) ->
   if dst_dir.exists() {
     let empty = is_dir_empty(&dst_dir)?;
     if !empty {
-      return Err(format!(
-        "目标目录非空：{}",
-        dst_dir.to_string_lossy()
-      ));
+      return Err(
+        PdhError::new(ErrorCode::Conflict, "target dir is not empty")
+          .with_details(serde_json::json!({ "path": dst_dir.to_string_lossy() })),
+      );
     }
   }
 
@@ -864,23 +1198,90 @@ pub fn pdh_local_data_migrate(app: tauri::AppHandle, target_base_dir: String) ->
   let dst_parent_canon = canonicalize_if_exists(&base);
   if let (Some(src_c), Some(dst_parent)) = (src_canon, dst_parent_canon) {
     if dst_parent.starts_with(&src_c) {
-      return Err("目标目录不能位于当前数据目录内部".to_string());
+      return Err(PdhError::invalid_argument("target dir must not be inside the current data dir"));
     }
   }
 
   ensure_dir(&dst_dir)?;
   copy_dir_recursive(&src_dir, &dst_dir)?;
 
-  let cfg = LocalDataConfig {
-    data_dir: Some(dst_dir.to_string_lossy().to_string()),
-  };
+  let mut cfg = load_config(&cfg_path);
+  cfg.data_dir = Some(dst_dir.to_string_lossy().to_string());
   save_config(&cfg_path, &cfg)?;
+  let _ = allow_wallpaper_assets(&app);
 
   pdh_local_data_info(app)
 }
 
 #[tauri::command]
-pub fn pdh_theme_presets_list(app: tauri::AppHandle) -> Result<Vec<ThemePreset>, String> {
+pub fn pdh_local_data_set_tcp_gateway(app: tauri::AppHandle, enabled: bool) -> PdhResult<LocalDataInfo> {
+  let cfg_path = config_path(&app)?;
+  let mut cfg = load_config(&cfg_path);
+  cfg.tcp_gateway = Some(enabled);
+  save_config(&cfg_path, &cfg)?;
+
+  pdh_local_data_info(app)
+}
+
+#[tauri::command]
+pub fn pdh_profiles_list(app: tauri::AppHandle) -> PdhResult<ProfilesSnapshot> {
+  let mut file = load_profiles(&app)?;
+  normalize_profiles_file(&mut file);
+  Ok(profiles_snapshot(file))
+}
+
+#[tauri::command]
+pub fn pdh_profiles_add(
+  app: tauri::AppHandle,
+  name: String,
+  url: String,
+  endpoints: Option<Vec<String>>,
+  last_username: Option<String>,
+  is_default: Option<bool>,
+) -> PdhResult<BackendProfile> {
+  let url = crate::normalize_backend_url(&url)?.ok_or_else(|| PdhError::invalid_argument("profile url is empty"))?;
+  let endpoints = normalize_endpoints(endpoints.unwrap_or_default())?;
+  let mut file = load_profiles(&app)?;
+  // 钥匙串条目按 URL 隔离，同一 URL 只能对应一个服务器配置
+  if file.profiles.iter().any(|p| p.url == url) {
+    return Err(PdhError::new(ErrorCode::AlreadyExists, "profile with this url already exists"));
+  }
+
+  let name = name.trim();
+  let last_username = last_username
+    .map(|u| u.trim().to_string())
+    .filter(|u| !u.is_empty());
+  let make_default = is_default.unwrap_or(false);
+  if make_default {
+    for profile in file.profiles.iter_mut() {
+      profile.is_default = false;
+    }
+  }
+
+  let next = BackendProfile {
+    id: generate_local_id("profile"),
+    name: if name.is_empty() { url.clone() } else { name.to_string() },
+    url,
+    endpoints,
+    tls: crate::net::TlsSettings::default(),
+    proxy: None,
+    known_usernames: last_username.iter().cloned().collect(),
+    last_username,
+    is_default: make_default,
+  };
+  file.profiles.push(next.clone());
+  normalize_profiles_file(&mut file);
+  save_profiles(&app, &file)?;
+
+  file
+    .profiles
+    .into_iter()
+    .find(|p| p.id == next.id)
+    .ok_or_else(|| PdhError::internal("add profile failed"))
+}
+
+#[tauri::command]
+pub fn pdh_theme_presets_list(app: tauri::AppHandle) -> PdhResult<Vec<ThemePreset>> {
   let file = load_presets(&app)?;
   Ok(file.presets)
 }
@@ -892,14 +1293,14 @@ pub fn pdh_theme_presets_save(
   name: String,
   created_at: String,
   payload: serde_json::Value,
-) -> Result<ThemePreset, String> {
+) -> PdhResult<ThemePreset> {
   let preset_id = id.trim().to_string();
   if preset_id.is_empty() {
-    return Err("id is empty".to_string());
+    return Err(PdhError::invalid_argument("id is empty"));
   }
   let preset_name = name.trim().to_string();
   if preset_name.is_empty() {
-    return Err("name is empty".to_string());
+    return Err(PdhError::invalid_argument("name is empty"));
   }
 
   let next = ThemePreset {
@@ -921,7 +1322,7 @@ pub fn pdh_theme_presets_save(
 }
 
 #[tauri::command]
-pub fn pdh_theme_presets_delete(app: tauri::AppHandle, id: String) -> Result<(), String> {
+pub fn pdh_theme_presets_delete(app: tauri::AppHandle, id: String) -> PdhResult<()> {
   let target = id.trim();
   if target.is_empty() {
     return Ok(());
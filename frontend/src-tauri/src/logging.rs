@@ -0,0 +1,266 @@
+use std::{
+  fs,
+  io::Write as _,
+  path::PathBuf,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+// 单个日志文件上限；超出后轮转，保留最近几份
+const MAX_LOG_FILE_BYTES: u128 = 5 * 1024 * 1024;
+const KEEP_LOG_FILES: usize = 5;
+pub const LOG_FILE_NAME: &str = "pdh";
+pub const CRASH_FILE_PREFIX: &str = "crash-";
+
+// 这些键名后面跟 `:` / `=` 时，其值视为凭据（匹配不区分大小写，覆盖 refreshToken 等）
+const SECRET_KEYS: &[&str] = &["token", "password", "secret", "authorization", "x-pdh-gateway-key"];
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogSettings {
+  // error / warn / info / debug / trace
+  pub level: String,
+}
+
+impl Default for LogSettings {
+  fn default() -> Self {
+    Self {
+      level: "info".to_string(),
+    }
+  }
+}
+
+impl LogSettings {
+  pub fn level_filter(&self) -> Result<LevelFilter, String> {
+    match self.level.trim().to_ascii_lowercase().as_str() {
+      "off" => Ok(LevelFilter::Off),
+      "error" => Ok(LevelFilter::Error),
+      "warn" => Ok(LevelFilter::Warn),
+      "info" => Ok(LevelFilter::Info),
+      "debug" => Ok(LevelFilter::Debug),
+      "trace" => Ok(LevelFilter::Trace),
+      other => Err(format!("unknown log level: {other}")),
+    }
+  }
+}
+
+/// 运行时调整日志级别（立即生效，不需要重启）。
+pub fn apply_level(settings: &LogSettings) -> Result<(), String> {
+  log::set_max_level(settings.level_filter()?);
+  Ok(())
+}
+
+/// 日志插件：写入数据目录下的轮转文件（debug 构建额外输出到 stdout），每行落盘前脱敏。
+/// 插件本身放行全部级别，实际级别由 `apply_level` 通过全局 max level 控制。
+pub fn plugin(logs_dir: Option<PathBuf>) -> tauri_plugin_log::Builder {
+  let file_target = match logs_dir {
+    Some(path) => TargetKind::Folder {
+      path,
+      file_name: Some(LOG_FILE_NAME.to_string()),
+    },
+    None => TargetKind::LogDir {
+      file_name: Some(LOG_FILE_NAME.to_string()),
+    },
+  };
+
+  let mut targets = vec![Target::new(file_target)];
+  if cfg!(debug_assertions) {
+    targets.push(Target::new(TargetKind::Stdout));
+  }
+
+  tauri_plugin_log::Builder::default()
+    .clear_targets()
+    .targets(targets.into_iter().map(|t| {
+      t.format(|out, message, _record| out.finish(format_args!("{}", redact(&message.to_string()))))
+    }))
+    .level(LevelFilter::Trace)
+    // 依赖库的调试输出量很大，对排查本应用的问题帮助有限
+    .level_for("hyper", LevelFilter::Info)
+    .level_for("hyper_util", LevelFilter::Info)
+    .level_for("h2", LevelFilter::Info)
+    .level_for("rustls", LevelFilter::Info)
+    .level_for("tao", LevelFilter::Info)
+    .level_for("wry", LevelFilter::Info)
+    .max_file_size(MAX_LOG_FILE_BYTES)
+    .rotation_strategy(RotationStrategy::KeepSome(KEEP_LOG_FILES))
+}
+
+fn is_value_end(b: u8) -> bool {
+  b.is_ascii_whitespace() || matches!(b, b'"' | b'\'' | b',' | b'}' | b']' | b'&' | b';' | b')')
+}
+
+/// 把文本里形如 `token=...`、`"password": "..."`、`Bearer ...` 的值替换为 `[redacted]`。
+pub fn redact(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let lower = text.to_ascii_lowercase();
+  let lower = lower.as_bytes();
+  let mut out = String::with_capacity(text.len());
+  let mut copied = 0;
+  let mut i = 0;
+
+  while i < bytes.len() {
+    let value_start = if lower[i..].starts_with(b"bearer ") {
+      Some(i + "bearer ".len())
+    } else {
+      SECRET_KEYS
+        .iter()
+        .find(|key| lower[i..].starts_with(key.as_bytes()))
+        .and_then(|key| {
+          // 键名后允许有引号和空白，再跟 `:` 或 `=`
+          let mut j = i + key.len();
+          while j < bytes.len() && (bytes[j] == b'"' || bytes[j] == b'\'' || bytes[j] == b' ') {
+            j += 1;
+          }
+          if j >= bytes.len() || !(bytes[j] == b':' || bytes[j] == b'=') {
+            return None;
+          }
+          j += 1;
+          while j < bytes.len() && (bytes[j] == b'"' || bytes[j] == b'\'' || bytes[j] == b' ') {
+            j += 1;
+          }
+          // `Authorization: Bearer xxx` 交给 bearer 分支处理
+          if lower[j..].starts_with(b"bearer ") {
+            j += "bearer ".len();
+          }
+          Some(j)
+        })
+    };
+
+    let Some(start) = value_start else {
+      i += 1;
+      continue;
+    };
+    let mut end = start;
+    while end < bytes.len() && !is_value_end(bytes[end]) {
+      end += 1;
+    }
+    if end > start {
+      out.push_str(&text[copied..start]);
+      out.push_str(REDACTED);
+      copied = end;
+    }
+    i = end.max(i + 1);
+  }
+
+  out.push_str(&text[copied..]);
+  out
+}
+
+/// 按键名递归脱敏 JSON（用于导出设置）。
+pub fn redact_json(value: &mut serde_json::Value) {
+  match value {
+    serde_json::Value::Object(map) => {
+      for (key, v) in map.iter_mut() {
+        let lower = key.to_ascii_lowercase();
+        if SECRET_KEYS.iter().any(|k| lower.contains(k)) && !v.is_null() {
+          *v = serde_json::Value::String(REDACTED.to_string());
+        } else {
+          redact_json(v);
+        }
+      }
+    }
+    serde_json::Value::Array(items) => items.iter_mut().for_each(redact_json),
+    serde_json::Value::String(s) => *s = redact(s),
+    _ => {}
+  }
+}
+
+/// 崩溃时在日志目录写一份报告（含回溯），再交给原来的 hook。
+pub fn install_panic_hook(crash_dir: Option<PathBuf>, version: String) {
+  let previous = std::panic::take_hook();
+  std::panic::set_hook(Box::new(move |info| {
+    let payload = info
+      .payload()
+      .downcast_ref::<&str>()
+      .map(|s| s.to_string())
+      .or_else(|| info.payload().downcast_ref::<String>().cloned())
+      .unwrap_or_else(|| "unknown panic payload".to_string());
+    let location = info
+      .location()
+      .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+      .unwrap_or_default();
+    let thread = std::thread::current().name().unwrap_or("unnamed").to_string();
+    let secs = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or(0);
+
+    log::error!("[panic] thread '{}' panicked at {}: {}", thread, location, payload);
+
+    if let Some(dir) = &crash_dir {
+      let report = format!(
+        "version: {version}\nos: {} {}\ntime: {secs}\nthread: {thread}\nlocation: {location}\nmessage: {payload}\n\nbacktrace:\n{}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::backtrace::Backtrace::force_capture(),
+      );
+      let path = dir.join(format!("{CRASH_FILE_PREFIX}{secs}.txt"));
+      let written = fs::create_dir_all(dir)
+        .and_then(|_| fs::File::create(&path))
+        .and_then(|mut f| f.write_all(redact(&report).as_bytes()));
+      if let Err(e) = written {
+        eprintln!("[panic] write crash report failed: {e}");
+      }
+    }
+
+    previous(info);
+  }));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn redact_key_value_pairs() {
+    assert_eq!(redact("refreshToken=abc123&x=1"), "refreshToken=[redacted]&x=1");
+    assert_eq!(
+      redact(r#"{"password": "hunter2", "user": "bob"}"#),
+      r#"{"password": "[redacted]", "user": "bob"}"#
+    );
+    assert_eq!(redact("client_secret = s3cr3t; next"), "client_secret = [redacted]; next");
+    assert_eq!(redact("x-pdh-gateway-key: deadbeef"), "x-pdh-gateway-key: [redacted]");
+  }
+
+  #[test]
+  fn redact_bearer_tokens() {
+    assert_eq!(redact("Authorization: Bearer abc.def.ghi"), "Authorization: Bearer [redacted]");
+    assert_eq!(redact("sent bearer xyz to server"), "sent bearer [redacted] to server");
+  }
+
+  #[test]
+  fn redact_leaves_other_text_alone() {
+    assert_eq!(redact("no secrets here"), "no secrets here");
+    // 键名后没有 `:` / `=` 时不是凭据
+    assert_eq!(redact("tokenizer ready, token expired"), "tokenizer ready, token expired");
+    assert_eq!(redact("token="), "token=");
+    assert_eq!(redact("登录 token=值123 完成"), "登录 token=[redacted] 完成");
+  }
+
+  #[test]
+  fn redact_json_by_key_and_value() {
+    let mut value = json!({
+      "refreshToken": "abc",
+      "url": "https://pdh.example.com",
+      "proxy": { "proxyPassword": "p", "clientKeyPem": null },
+      "notes": ["Bearer t0k", "plain"],
+      "port": 8444,
+    });
+    redact_json(&mut value);
+    assert_eq!(
+      value,
+      json!({
+        "refreshToken": "[redacted]",
+        "url": "https://pdh.example.com",
+        "proxy": { "proxyPassword": "[redacted]", "clientKeyPem": null },
+        "notes": ["Bearer [redacted]", "plain"],
+        "port": 8444,
+      })
+    );
+  }
+}
@@ -0,0 +1,282 @@
+use std::{
+  collections::BTreeMap,
+  fmt::Write as _,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+  },
+  time::{Duration, Instant},
+};
+
+use axum::{
+  body::Body,
+  http::{Request, StatusCode},
+  middleware::Next,
+  response::Response,
+};
+use futures_util::StreamExt;
+use serde::Serialize;
+
+// 延迟直方图的桶上限（秒），与 Prometheus 客户端的默认桶一致
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Histogram {
+  // 与 LATENCY_BUCKETS 一一对应的累计计数（le 语义）
+  pub buckets: Vec<u64>,
+  pub sum_seconds: f64,
+  pub count: u64,
+}
+
+impl Histogram {
+  fn observe(&mut self, elapsed: Duration) {
+    if self.buckets.is_empty() {
+      self.buckets = vec![0; LATENCY_BUCKETS.len()];
+    }
+    let secs = elapsed.as_secs_f64();
+    for (bucket, le) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+      if secs <= *le {
+        *bucket += 1;
+      }
+    }
+    self.sum_seconds += secs;
+    self.count += 1;
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteMetrics {
+  // 按状态码分类计数：2xx / 3xx / 4xx / 5xx
+  pub requests: BTreeMap<String, u64>,
+  // 收到响应头的延迟
+  pub latency: Histogram,
+  pub response_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadMetrics {
+  pub tasks_started: u64,
+  pub tasks_done: u64,
+  pub tasks_failed: u64,
+  pub tasks_canceled: u64,
+  pub bytes_sent: u64,
+  pub chunks_sent: u64,
+  pub chunk_latency: Histogram,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSnapshot {
+  pub uptime_seconds: u64,
+  pub routes: BTreeMap<&'static str, RouteMetrics>,
+  pub uploads: UploadMetrics,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UploadOutcome {
+  Done,
+  Failed,
+  Canceled,
+}
+
+/// 网关与上传任务的运行指标（进程内累计，重启清零）。
+pub struct GatewayMetrics {
+  started: Instant,
+  routes: Mutex<BTreeMap<&'static str, RouteMetrics>>,
+  uploads: Mutex<UploadMetrics>,
+  in_flight: AtomicU64,
+}
+
+impl Default for GatewayMetrics {
+  fn default() -> Self {
+    Self {
+      started: Instant::now(),
+      routes: Mutex::new(BTreeMap::new()),
+      uploads: Mutex::new(UploadMetrics::default()),
+      in_flight: AtomicU64::new(0),
+    }
+  }
+}
+
+fn status_class(status: StatusCode) -> String {
+  format!("{}xx", status.as_u16() / 100)
+}
+
+/// 按网关路由模板归类；路由层看到的路径已去掉 TCP 模式下的密钥前缀。
+fn route_label(path: &str) -> Option<&'static str> {
+  if path.starts_with("/api/") {
+    Some("/api/*path")
+  } else if path.starts_with("/attachments/") && path.ends_with("/thumb") {
+    Some("/attachments/:id/thumb")
+  } else if path.starts_with("/attachments/") {
+    Some("/attachments/:id")
+  } else if path.starts_with("/wallpapers/") {
+    Some("/wallpapers/:name")
+  } else if path == "/health" {
+    Some("/health")
+  } else {
+    None
+  }
+}
+
+impl GatewayMetrics {
+  fn with_route(&self, route: &'static str, f: impl FnOnce(&mut RouteMetrics)) {
+    let mut routes = match self.routes.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    f(routes.entry(route).or_default());
+  }
+
+  fn with_uploads(&self, f: impl FnOnce(&mut UploadMetrics)) {
+    let mut uploads = match self.uploads.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    f(&mut uploads);
+  }
+
+  pub fn upload_started(&self) {
+    self.with_uploads(|u| u.tasks_started += 1);
+  }
+
+  pub fn upload_chunk(&self, bytes: u64, elapsed: Duration) {
+    self.with_uploads(|u| {
+      u.bytes_sent += bytes;
+      u.chunks_sent += 1;
+      u.chunk_latency.observe(elapsed);
+    });
+  }
+
+  pub fn upload_finished(&self, outcome: UploadOutcome) {
+    self.with_uploads(|u| match outcome {
+      UploadOutcome::Done => u.tasks_done += 1,
+      UploadOutcome::Failed => u.tasks_failed += 1,
+      UploadOutcome::Canceled => u.tasks_canceled += 1,
+    });
+  }
+
+  pub fn snapshot(&self) -> MetricsSnapshot {
+    let routes = match self.routes.lock() {
+      Ok(guard) => guard.clone(),
+      Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    let uploads = match self.uploads.lock() {
+      Ok(guard) => guard.clone(),
+      Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    MetricsSnapshot {
+      uptime_seconds: self.started.elapsed().as_secs(),
+      routes,
+      uploads,
+    }
+  }
+
+  /// Prometheus 文本格式（0.0.4）。
+  pub fn render_prometheus(&self) -> String {
+    let snap = self.snapshot();
+    let mut out = String::new();
+
+    out.push_str("# HELP pdh_gateway_uptime_seconds Seconds since the gateway started.\n");
+    out.push_str("# TYPE pdh_gateway_uptime_seconds gauge\n");
+    let _ = writeln!(out, "pdh_gateway_uptime_seconds {}", snap.uptime_seconds);
+
+    out.push_str("# HELP pdh_gateway_in_flight_requests Requests currently being handled.\n");
+    out.push_str("# TYPE pdh_gateway_in_flight_requests gauge\n");
+    let _ = writeln!(out, "pdh_gateway_in_flight_requests {}", self.in_flight.load(Ordering::Relaxed));
+
+    out.push_str("# HELP pdh_gateway_requests_total Gateway requests by route and status class.\n");
+    out.push_str("# TYPE pdh_gateway_requests_total counter\n");
+    for (route, m) in &snap.routes {
+      for (class, count) in &m.requests {
+        let _ = writeln!(out, "pdh_gateway_requests_total{{route=\"{route}\",status=\"{class}\"}} {count}");
+      }
+    }
+
+    out.push_str("# HELP pdh_gateway_response_bytes_total Response body bytes sent to the WebView.\n");
+    out.push_str("# TYPE pdh_gateway_response_bytes_total counter\n");
+    for (route, m) in &snap.routes {
+      let _ = writeln!(out, "pdh_gateway_response_bytes_total{{route=\"{route}\"}} {}", m.response_bytes);
+    }
+
+    out.push_str("# HELP pdh_gateway_request_duration_seconds Time until response headers are ready.\n");
+    out.push_str("# TYPE pdh_gateway_request_duration_seconds histogram\n");
+    for (route, m) in &snap.routes {
+      write_histogram(&mut out, "pdh_gateway_request_duration_seconds", &format!("route=\"{route}\","), &m.latency);
+    }
+
+    let u = &snap.uploads;
+    out.push_str("# HELP pdh_upload_tasks_total Resumable upload tasks by outcome.\n");
+    out.push_str("# TYPE pdh_upload_tasks_total counter\n");
+    let _ = writeln!(out, "pdh_upload_tasks_total{{outcome=\"started\"}} {}", u.tasks_started);
+    let _ = writeln!(out, "pdh_upload_tasks_total{{outcome=\"done\"}} {}", u.tasks_done);
+    let _ = writeln!(out, "pdh_upload_tasks_total{{outcome=\"failed\"}} {}", u.tasks_failed);
+    let _ = writeln!(out, "pdh_upload_tasks_total{{outcome=\"canceled\"}} {}", u.tasks_canceled);
+
+    out.push_str("# HELP pdh_upload_bytes_total Bytes uploaded to the backend.\n");
+    out.push_str("# TYPE pdh_upload_bytes_total counter\n");
+    let _ = writeln!(out, "pdh_upload_bytes_total {}", u.bytes_sent);
+
+    out.push_str("# HELP pdh_upload_chunk_duration_seconds Time to upload one chunk.\n");
+    out.push_str("# TYPE pdh_upload_chunk_duration_seconds histogram\n");
+    write_histogram(&mut out, "pdh_upload_chunk_duration_seconds", "", &u.chunk_latency);
+
+    out
+  }
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &str, h: &Histogram) {
+  for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+    let count = h.buckets.get(i).copied().unwrap_or(0);
+    let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{le}\"}} {count}");
+  }
+  let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {}", h.count);
+  let labels = labels.trim_end_matches(',');
+  if labels.is_empty() {
+    let _ = writeln!(out, "{name}_sum {}", h.sum_seconds);
+    let _ = writeln!(out, "{name}_count {}", h.count);
+  } else {
+    let _ = writeln!(out, "{name}_sum{{{labels}}} {}", h.sum_seconds);
+    let _ = writeln!(out, "{name}_count{{{labels}}} {}", h.count);
+  }
+}
+
+struct InFlight(Arc<GatewayMetrics>);
+
+impl Drop for InFlight {
+  fn drop(&mut self) {
+    self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+/// 网关路由的中间件：记录请求数、响应头延迟与响应体字节数。
+pub async fn track(metrics: Arc<GatewayMetrics>, req: Request<Body>, next: Next) -> Response {
+  let Some(route) = route_label(req.uri().path()) else {
+    return next.run(req).await;
+  };
+
+  let started = Instant::now();
+  metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+  let in_flight = InFlight(metrics.clone());
+
+  let response = next.run(req).await;
+  let elapsed = started.elapsed();
+  metrics.with_route(route, |m| {
+    *m.requests.entry(status_class(response.status())).or_default() += 1;
+    m.latency.observe(elapsed);
+  });
+
+  // 字节数在响应体流过时累加；附件流量主要体现在这里
+  let (parts, body) = response.into_parts();
+  let counted = body.into_data_stream().map(move |chunk| {
+    if let Ok(bytes) = &chunk {
+      let len = bytes.len() as u64;
+      in_flight.0.with_route(route, |m| m.response_bytes += len);
+    }
+    chunk
+  });
+  Response::from_parts(parts, Body::from_stream(counted))
+}
@@ -0,0 +1,425 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use base64::engine::general_purpose;
+use base64::Engine;
+use rustls::{
+  client::{
+    danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    WebPkiServerVerifier,
+  },
+  crypto::CryptoProvider,
+  pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+  DigitallySignedStruct, SignatureScheme,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// 服务器配置里的 TLS 设置。客户端私钥不在这里，单独存系统钥匙串。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsSettings {
+  // 额外信任的 CA（PEM，可包含多张证书），在系统内置根证书之外生效
+  #[serde(default)]
+  pub ca_pem: Option<String>,
+  // 服务器证书公钥（SPKI）的 SHA-256，base64 编码，可带 "sha256/" 前缀；任意一个匹配即可
+  #[serde(default)]
+  pub pinned_spki_sha256: Vec<String>,
+  // mTLS 客户端证书链（PEM）
+  #[serde(default)]
+  pub client_cert_pem: Option<String>,
+}
+
+impl TlsSettings {
+  pub fn is_default(&self) -> bool {
+    self.ca_pem.is_none() && self.pinned_spki_sha256.is_empty() && self.client_cert_pem.is_none()
+  }
+}
+
+/// 连接池、超时与 HTTP/2 等全局网络设置（与服务器无关，保存在本机配置里）。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+  pub connect_timeout_secs: u64,
+  // 两次读到数据之间的最长等待；服务器“假死”时靠它中断上传/下载
+  pub read_timeout_secs: u64,
+  // 普通 API 请求的总耗时上限；附件流和整文件上传不受限制。0 表示不限
+  pub request_timeout_secs: u64,
+  pub pool_idle_timeout_secs: u64,
+  pub pool_max_idle_per_host: usize,
+  pub tcp_keepalive_secs: u64,
+  // 服务器支持时（ALPN 协商）使用 HTTP/2
+  pub http2: bool,
+  // 幂等请求在连接层失败时的最多重试次数；0 表示不重试
+  pub retry_max_attempts: u32,
+  // 指数退避的起始与上限（毫秒），实际等待在 [0, 退避值] 内随机
+  pub retry_base_delay_ms: u64,
+  pub retry_max_delay_ms: u64,
+  // 带可重放 body 的 PUT 是否也重试
+  pub retry_put: bool,
+}
+
+impl Default for NetworkSettings {
+  fn default() -> Self {
+    Self {
+      connect_timeout_secs: 10,
+      read_timeout_secs: 30,
+      request_timeout_secs: 60,
+      pool_idle_timeout_secs: 90,
+      pool_max_idle_per_host: 8,
+      tcp_keepalive_secs: 60,
+      http2: true,
+      retry_max_attempts: 2,
+      retry_base_delay_ms: 200,
+      retry_max_delay_ms: 5000,
+      retry_put: true,
+    }
+  }
+}
+
+impl NetworkSettings {
+  pub fn validate(&self) -> Result<(), String> {
+    if self.connect_timeout_secs == 0 || self.read_timeout_secs == 0 {
+      return Err("connect and read timeouts must be at least 1 second".to_string());
+    }
+    if self.retry_max_attempts > 10 {
+      return Err("retry attempts must not exceed 10".to_string());
+    }
+    if self.retry_base_delay_ms > self.retry_max_delay_ms || self.retry_max_delay_ms > 60_000 {
+      return Err("retry delays must satisfy base <= max <= 60000 ms".to_string());
+    }
+    Ok(())
+  }
+}
+
+struct ClientState {
+  client: reqwest::Client,
+  options: ClientOptions,
+  network: NetworkSettings,
+}
+
+/// 网关、登录/刷新、上传、健康检查共用的 HTTP 客户端（共享连接池）。
+/// 切换服务器、修改 TLS / 代理或网络设置时按最新设置整体重建。
+pub struct HttpClient {
+  inner: RwLock<ClientState>,
+}
+
+impl Default for HttpClient {
+  fn default() -> Self {
+    let options = ClientOptions::default();
+    let network = NetworkSettings::default();
+    let client = build_client(&options, &network).unwrap_or_default();
+    Self {
+      inner: RwLock::new(ClientState { client, options, network }),
+    }
+  }
+}
+
+impl HttpClient {
+  fn read(&self) -> std::sync::RwLockReadGuard<'_, ClientState> {
+    match self.inner.read() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+
+  fn write(&self) -> std::sync::RwLockWriteGuard<'_, ClientState> {
+    match self.inner.write() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+
+  pub fn get(&self) -> reqwest::Client {
+    self.read().client.clone()
+  }
+
+  pub fn network(&self) -> NetworkSettings {
+    self.read().network.clone()
+  }
+
+  /// 按当前网络设置构造一个独立的客户端（诊断、代理测试等一次性用途），不替换共享客户端。
+  pub fn build(&self, options: &ClientOptions) -> Result<reqwest::Client, String> {
+    build_client(options, &self.read().network)
+  }
+
+  /// 换用新的服务器设置（TLS / 代理）。构造失败时保持原客户端不变。
+  pub fn configure(&self, options: ClientOptions) -> Result<(), String> {
+    let mut state = self.write();
+    state.client = build_client(&options, &state.network)?;
+    state.options = options;
+    Ok(())
+  }
+
+  pub fn set_network(&self, network: NetworkSettings) -> Result<(), String> {
+    network.validate()?;
+    let mut state = self.write();
+    state.client = build_client(&state.options, &network)?;
+    state.network = network;
+    Ok(())
+  }
+
+  /// 给普通 API 请求加上总超时。
+  pub fn with_request_timeout(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match self.read().network.request_timeout_secs {
+      0 => req,
+      secs => req.timeout(Duration::from_secs(secs)),
+    }
+  }
+}
+
+fn normalize_pin(raw: &str) -> Result<Vec<u8>, String> {
+  let trimmed = raw.trim();
+  let b64 = trimmed.strip_prefix("sha256/").unwrap_or(trimmed);
+  let bytes = general_purpose::STANDARD
+    .decode(b64)
+    .map_err(|e| format!("invalid pin {trimmed}: {e}"))?;
+  if bytes.len() != 32 {
+    return Err(format!("invalid pin {trimmed}: expected a sha256 digest"));
+  }
+  Ok(bytes)
+}
+
+/// 证书公钥的 pin（sha256/base64），方便在诊断信息里直接复制使用。
+pub fn spki_pin(der: &CertificateDer<'_>) -> Option<String> {
+  let (_, cert) = X509Certificate::from_der(der.as_ref()).ok()?;
+  let digest = Sha256::digest(cert.public_key().raw);
+  Some(format!("sha256/{}", general_purpose::STANDARD.encode(digest)))
+}
+
+/// 在正常的证书链校验之后再核对 SPKI pin。
+#[derive(Debug)]
+struct PinnedVerifier {
+  inner: Arc<WebPkiServerVerifier>,
+  pins: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+  fn verify_server_cert(
+    &self,
+    end_entity: &CertificateDer<'_>,
+    intermediates: &[CertificateDer<'_>],
+    server_name: &ServerName<'_>,
+    ocsp_response: &[u8],
+    now: UnixTime,
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    let verified = self
+      .inner
+      .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+
+    let (_, cert) = X509Certificate::from_der(end_entity.as_ref())
+      .map_err(|e| rustls::Error::General(format!("parse server certificate failed: {e}")))?;
+    let digest = Sha256::digest(cert.public_key().raw);
+    if self.pins.iter().any(|pin| pin.as_slice() == digest.as_slice()) {
+      Ok(verified)
+    } else {
+      Err(rustls::Error::General("server certificate does not match pinned key".to_string()))
+    }
+  }
+
+  fn verify_tls12_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    self.inner.verify_tls12_signature(message, cert, dss)
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    self.inner.verify_tls13_signature(message, cert, dss)
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self.inner.supported_verify_schemes()
+  }
+}
+
+fn parse_certificates(pem: &str, what: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+  let certs = CertificateDer::pem_slice_iter(pem.as_bytes())
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("invalid {what} PEM: {e}"))?;
+  if certs.is_empty() {
+    return Err(format!("{what} PEM contains no certificate"));
+  }
+  Ok(certs)
+}
+
+/// 按 TLS 设置构造 rustls 配置。`client_key_pem` 为钥匙串里保存的客户端私钥。
+pub fn rustls_config(settings: &TlsSettings, client_key_pem: Option<&str>) -> Result<rustls::ClientConfig, String> {
+  let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+  let mut roots = rustls::RootCertStore::empty();
+  roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+  if let Some(pem) = settings.ca_pem.as_deref().filter(|p| !p.trim().is_empty()) {
+    for cert in parse_certificates(pem, "CA")? {
+      roots.add(cert).map_err(|e| format!("invalid CA certificate: {e}"))?;
+    }
+  }
+
+  let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+    .with_safe_default_protocol_versions()
+    .map_err(|e| e.to_string())?;
+
+  let builder = if settings.pinned_spki_sha256.is_empty() {
+    builder.with_root_certificates(roots)
+  } else {
+    let pins = settings
+      .pinned_spki_sha256
+      .iter()
+      .map(|p| normalize_pin(p))
+      .collect::<Result<Vec<_>, _>>()?;
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider as Arc<CryptoProvider>)
+      .build()
+      .map_err(|e| e.to_string())?;
+    builder
+      .dangerous()
+      .with_custom_certificate_verifier(Arc::new(PinnedVerifier { inner, pins }))
+  };
+
+  let mut config = match settings.client_cert_pem.as_deref().filter(|p| !p.trim().is_empty()) {
+    Some(cert_pem) => {
+      let certs = parse_certificates(cert_pem, "client certificate")?;
+      let key_pem = client_key_pem
+        .filter(|k| !k.trim().is_empty())
+        .ok_or_else(|| "client certificate is set but its private key is missing".to_string())?;
+      let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes())
+        .map_err(|e| format!("invalid client key PEM: {e}"))?;
+      builder
+        .with_client_auth_cert(certs, key)
+        .map_err(|e| format!("client certificate rejected: {e}"))?
+    }
+    None => builder.with_no_client_auth(),
+  };
+  config.alpn_protocols = vec![b"http/1.1".to_vec()];
+  Ok(config)
+}
+
+/// 出站代理：http://、https://、socks5://（socks5h:// 由代理解析域名）。密码存系统钥匙串。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxySettings {
+  pub url: String,
+  #[serde(default)]
+  pub username: Option<String>,
+  // 不走代理的主机，语法同 NO_PROXY 环境变量（域名后缀、IP、CIDR）
+  #[serde(default)]
+  pub no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+  pub fn validate(&self) -> Result<(), String> {
+    let url = reqwest::Url::parse(self.url.trim()).map_err(|e| format!("invalid proxy url: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+      return Err("proxy url must start with http://, https://, socks5:// or socks5h://".to_string());
+    }
+    if url.host_str().is_none() {
+      return Err("proxy url is missing a host".to_string());
+    }
+    Ok(())
+  }
+
+  fn to_reqwest(&self, password: Option<&str>) -> Result<reqwest::Proxy, String> {
+    self.validate()?;
+    let mut proxy = reqwest::Proxy::all(self.url.trim()).map_err(|e| format!("invalid proxy url: {e}"))?;
+    if let Some(user) = self.username.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+      proxy = proxy.basic_auth(user, password.unwrap_or(""));
+    }
+    let no_proxy = self
+      .no_proxy
+      .iter()
+      .map(|h| h.trim())
+      .filter(|h| !h.is_empty())
+      .collect::<Vec<_>>()
+      .join(",");
+    Ok(proxy.no_proxy(reqwest::NoProxy::from_string(&no_proxy)))
+  }
+}
+
+/// 构造客户端所需的服务器相关设置；私钥与代理密码由调用方从钥匙串取出。
+#[derive(Clone, Default)]
+pub struct ClientOptions {
+  pub tls: TlsSettings,
+  pub client_key_pem: Option<String>,
+  pub proxy: Option<ProxySettings>,
+  pub proxy_password: Option<String>,
+}
+
+fn build_client(options: &ClientOptions, network: &NetworkSettings) -> Result<reqwest::Client, String> {
+  let mut builder = reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(network.connect_timeout_secs))
+    .read_timeout(Duration::from_secs(network.read_timeout_secs))
+    .pool_idle_timeout(Duration::from_secs(network.pool_idle_timeout_secs))
+    .pool_max_idle_per_host(network.pool_max_idle_per_host);
+  if network.tcp_keepalive_secs > 0 {
+    builder = builder.tcp_keepalive(Duration::from_secs(network.tcp_keepalive_secs));
+  }
+  if network.http2 {
+    builder = builder.http2_keep_alive_interval(Duration::from_secs(30)).http2_keep_alive_while_idle(true);
+  } else {
+    builder = builder.http1_only();
+  }
+
+  // 没有自定义 TLS 时沿用 reqwest 内置的 rustls 配置
+  if !options.tls.is_default() {
+    let mut config = rustls_config(&options.tls, options.client_key_pem.as_deref())?;
+    config.alpn_protocols = if network.http2 {
+      vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+      vec![b"http/1.1".to_vec()]
+    };
+    builder = builder.use_preconfigured_tls(config);
+  }
+  // 没有配置代理时 reqwest 仍会读取系统代理环境变量
+  if let Some(proxy) = &options.proxy {
+    builder = builder.proxy(proxy.to_reqwest(options.proxy_password.as_deref())?);
+  }
+  builder
+    .build()
+    .map_err(|e| format!("build http client failed: {e}"))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyTestResult {
+  pub ok: bool,
+  pub target: String,
+  pub status: Option<u16>,
+  pub latency_ms: Option<u64>,
+  pub error: Option<String>,
+}
+
+/// 经代理请求目标服务器的 /health，检验代理是否可用。
+pub async fn test_proxy(client: &reqwest::Client, target: &str) -> ProxyTestResult {
+  let started = std::time::Instant::now();
+  let result = client
+    .get(format!("{}/health", target.trim_end_matches('/')))
+    .timeout(Duration::from_secs(10))
+    .send()
+    .await;
+  let latency_ms = Some(started.elapsed().as_millis() as u64);
+
+  match result {
+    Ok(resp) => ProxyTestResult {
+      ok: resp.status().is_success(),
+      target: target.to_string(),
+      status: Some(resp.status().as_u16()),
+      latency_ms,
+      error: (!resp.status().is_success()).then(|| format!("status {}", resp.status().as_u16())),
+    },
+    // 展开错误链：代理认证失败、CONNECT 被拒等信息在内层
+    Err(e) => ProxyTestResult {
+      ok: false,
+      target: target.to_string(),
+      status: None,
+      latency_ms: None,
+      error: Some(crate::gateway_error::error_chain(&e)),
+    },
+  }
+}
@@ -0,0 +1,78 @@
+use std::future::Future;
+
+use axum::{
+  body::Body,
+  http::{HeaderName, HeaderValue, Request},
+  middleware::Next,
+  response::Response,
+};
+
+// 与后端约定的关联 ID 头：网关转发给后端，并原样写回响应
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// 前端自带的 ID 过长或含奇怪字符时不采用，改为网关生成
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+  static CURRENT: String;
+}
+
+/// 生成新的请求 ID（16 字节随机数，hex 编码）。
+pub fn generate() -> String {
+  let bytes: [u8; 16] = rand::random();
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 当前请求的 ID；只在网关处理请求（或 `scope` 包裹）的任务里有值，用于日志。
+pub fn current() -> Option<String> {
+  CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// 日志前缀：`[req <id>] `，不在请求上下文中时为空。
+pub fn log_prefix() -> String {
+  current().map(|id| format!("[req {id}] ")).unwrap_or_default()
+}
+
+/// 在给定请求 ID 下运行 future，期间 `current()` 返回该 ID。
+pub async fn scope<F: Future>(id: String, fut: F) -> F::Output {
+  CURRENT.scope(id, fut).await
+}
+
+/// 给直接发往后端的请求（上传任务等）带上当前请求 ID。
+pub fn apply(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+  match current() {
+    Some(id) => req.header(REQUEST_ID_HEADER, id),
+    None => req,
+  }
+}
+
+fn is_acceptable(id: &str) -> bool {
+  !id.is_empty()
+    && id.len() <= MAX_REQUEST_ID_LEN
+    && id
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// 网关路由的中间件：沿用前端带来的 X-Request-Id，没有则生成；
+/// 写回请求头（随 copy_request_headers 转发给后端）与响应头。
+pub async fn propagate(mut req: Request<Body>, next: Next) -> Response {
+  let incoming = req
+    .headers()
+    .get(REQUEST_ID_HEADER)
+    .and_then(|v| v.to_str().ok())
+    .map(str::trim)
+    .filter(|v| is_acceptable(v))
+    .map(str::to_string);
+  let id = incoming.unwrap_or_else(generate);
+
+  let Ok(value) = HeaderValue::from_str(&id) else {
+    return next.run(req).await;
+  };
+  let name = HeaderName::from_static(REQUEST_ID_HEADER);
+  req.headers_mut().insert(name.clone(), value.clone());
+
+  let mut response = scope(id, next.run(req)).await;
+  response.headers_mut().insert(name, value);
+  response
+}
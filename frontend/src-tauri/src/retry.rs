@@ -0,0 +1,188 @@
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+
+use crate::net::NetworkSettings;
+
+// 网关响应里报告实际重试了几次（没有重试时不带）
+pub const RETRY_COUNT_HEADER: &str = "x-pdh-retry-count";
+
+/// 幂等请求的重试策略：只在还没收到任何响应字节时重试。
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+  pub max_retries: u32,
+  pub base_delay: Duration,
+  pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+  pub fn from_settings(settings: &NetworkSettings) -> Self {
+    Self {
+      max_retries: settings.retry_max_attempts,
+      base_delay: Duration::from_millis(settings.retry_base_delay_ms),
+      max_delay: Duration::from_millis(settings.retry_max_delay_ms),
+    }
+  }
+
+  pub fn disabled() -> Self {
+    Self {
+      max_retries: 0,
+      base_delay: Duration::ZERO,
+      max_delay: Duration::ZERO,
+    }
+  }
+
+  /// 第 n 次重试前的等待：指数退避 + full jitter。
+  fn backoff(&self, retry: u32) -> Duration {
+    let exp = self
+      .base_delay
+      .saturating_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+      .min(self.max_delay);
+    let millis = exp.as_millis() as u64;
+    if millis == 0 {
+      return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+  }
+}
+
+/// 连接层失败（连不上、连接被重置、连接超时）才重试；读响应超时可能已被服务端处理，不重试。
+pub fn is_retryable_error(err: &reqwest::Error) -> bool {
+  err.is_connect() || (err.is_request() && !err.is_timeout())
+}
+
+/// 解析 Retry-After：秒数或 HTTP 日期。
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+  let raw = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+  if let Ok(secs) = raw.parse::<u64>() {
+    return Some(Duration::from_secs(secs));
+  }
+  let at = httpdate::parse_http_date(raw).ok()?;
+  Some(at.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+}
+
+/// 发送请求并按策略重试。`make` 每次构造一个全新的请求（body 需可重放）。
+/// 429 / 503 只有带 Retry-After 且等待不超过上限时才重试。返回结果与重试次数。
+pub async fn send<F>(policy: RetryPolicy, mut make: F) -> (Result<reqwest::Response, reqwest::Error>, u32)
+where
+  F: FnMut() -> reqwest::RequestBuilder,
+{
+  let mut retries = 0;
+  loop {
+    let result = make().send().await;
+    if retries >= policy.max_retries {
+      return (result, retries);
+    }
+
+    let delay = match &result {
+      Err(e) if is_retryable_error(e) => policy.backoff(retries),
+      Ok(resp)
+        if matches!(
+          resp.status(),
+          reqwest::StatusCode::TOO_MANY_REQUESTS | reqwest::StatusCode::SERVICE_UNAVAILABLE
+        ) =>
+      {
+        match retry_after(resp.headers()) {
+          Some(wait) if wait <= policy.max_delay => wait,
+          _ => return (result, retries),
+        }
+      }
+      _ => return (result, retries),
+    };
+
+    retries += 1;
+    match &result {
+      Err(e) => log::info!(
+        "[gateway] {}retry {}/{} in {:?}: {}",
+        crate::request_id::log_prefix(),
+        retries,
+        policy.max_retries,
+        delay,
+        e
+      ),
+      Ok(resp) => log::info!(
+        "[gateway] {}retry {}/{} in {:?} after {}",
+        crate::request_id::log_prefix(),
+        retries,
+        policy.max_retries,
+        delay,
+        resp.status().as_u16()
+      ),
+    }
+    drop(result);
+    tokio::time::sleep(delay).await;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+  fn headers(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+    headers
+  }
+
+  #[test]
+  fn retry_after_seconds() {
+    assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+    assert_eq!(retry_after(&headers(" 0 ")), Some(Duration::ZERO));
+  }
+
+  #[test]
+  fn retry_after_http_date() {
+    let future = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+    let wait = retry_after(&headers(&future)).unwrap();
+    // HTTP 日期只精确到秒
+    assert!(wait <= Duration::from_secs(60) && wait >= Duration::from_secs(58), "{wait:?}");
+
+    let past = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(60));
+    assert_eq!(retry_after(&headers(&past)), Some(Duration::ZERO));
+  }
+
+  #[test]
+  fn retry_after_missing_or_invalid() {
+    assert_eq!(retry_after(&HeaderMap::new()), None);
+    assert_eq!(retry_after(&headers("soon")), None);
+    assert_eq!(retry_after(&headers("-5")), None);
+  }
+
+  #[test]
+  fn backoff_stays_within_exponential_bound() {
+    let policy = RetryPolicy {
+      max_retries: 10,
+      base_delay: Duration::from_millis(100),
+      max_delay: Duration::from_secs(1),
+    };
+    for retry in 0..8 {
+      let bound = Duration::from_millis(100 << retry).min(policy.max_delay);
+      for _ in 0..50 {
+        assert!(policy.backoff(retry) <= bound);
+      }
+    }
+    // 位移溢出时仍以 max_delay 为上限
+    for retry in [31, 32, 64, u32::MAX] {
+      assert!(policy.backoff(retry) <= policy.max_delay);
+    }
+  }
+
+  #[test]
+  fn backoff_is_jittered() {
+    let policy = RetryPolicy {
+      max_retries: 1,
+      base_delay: Duration::from_secs(10),
+      max_delay: Duration::from_secs(10),
+    };
+    let samples: std::collections::HashSet<Duration> = (0..20).map(|_| policy.backoff(0)).collect();
+    assert!(samples.len() > 1);
+  }
+
+  #[test]
+  fn disabled_policy_never_waits() {
+    let policy = RetryPolicy::disabled();
+    assert_eq!(policy.backoff(0), Duration::ZERO);
+    assert_eq!(policy.backoff(5), Duration::ZERO);
+  }
+}
@@ -0,0 +1,270 @@
+use std::sync::{Arc, RwLock};
+
+use serde_json::json;
+use tauri::Emitter;
+
+use crate::gateway::GatewayConfig;
+use crate::net::HttpClient;
+use crate::token_lifecycle::TokenLifecycle;
+
+pub enum RefreshError {
+  // 没有可用的会话（未设置后端 / 没有 refresh token）：只能重新登录
+  NoSession(String),
+  // 服务端拒绝了 refresh token（401/403）：本地 refresh token 已清理
+  Rejected(String),
+  // 网络错误、5xx 等：会话可能仍然有效，稍后可以再试
+  Transient(String),
+}
+
+impl RefreshError {
+  pub fn is_final(&self) -> bool {
+    !matches!(self, RefreshError::Transient(_))
+  }
+
+  pub fn message(&self) -> &str {
+    match self {
+      RefreshError::NoSession(m) | RefreshError::Rejected(m) | RefreshError::Transient(m) => m,
+    }
+  }
+}
+
+pub struct RefreshedSession {
+  pub token: String,
+  // 后端 /api/auth/refresh 的原始响应；复用他人刚完成的刷新结果时为 null
+  pub body: serde_json::Value,
+}
+
+impl RefreshedSession {
+  /// 新 token 的有效期（原样透传后端的 expiresIn）；token 本身只留在网关里。
+  pub fn expires_in(&self) -> serde_json::Value {
+    self
+      .body
+      .get("data")
+      .and_then(|d| d.get("expiresIn"))
+      .cloned()
+      .unwrap_or(serde_json::Value::Null)
+  }
+}
+
+/// 串行化 access token 刷新：网关并发收到多个 401 时只向后端刷新一次，
+/// 其余请求排队等待并直接复用新 token。每次成功刷新都会重新安排主动刷新。
+pub struct SessionRefresher {
+  lock: tokio::sync::Mutex<()>,
+  lifecycle: Arc<TokenLifecycle>,
+}
+
+impl SessionRefresher {
+  pub fn new(lifecycle: Arc<TokenLifecycle>) -> Self {
+    Self {
+      lock: tokio::sync::Mutex::new(()),
+      lifecycle,
+    }
+  }
+
+  /// `stale_token` 为触发刷新的那次请求所用的 token；
+  /// 排队期间若网关 token 已被别人换掉，则不再重复刷新。传 None 表示强制刷新。
+  pub async fn refresh(
+    &self,
+    http: &HttpClient,
+    config: &Arc<RwLock<GatewayConfig>>,
+    stale_token: Option<&str>,
+  ) -> Result<RefreshedSession, RefreshError> {
+    let _guard = self.lock.lock().await;
+
+    let (backend, account, legacy_owner, upstream, current) = {
+      let cfg = config
+        .read()
+        .map_err(|_| RefreshError::Transient("gateway state poisoned".to_string()))?;
+      (
+        cfg.backend_base_url.clone(),
+        cfg.account.clone(),
+        cfg.legacy_owner.clone(),
+        cfg.upstream_base_url(),
+        cfg.bearer_token.clone(),
+      )
+    };
+
+    if let (Some(stale), Some(current)) = (stale_token, current.as_deref()) {
+      if stale != current {
+        return Ok(RefreshedSession {
+          token: current.to_string(),
+          body: serde_json::Value::Null,
+        });
+      }
+    }
+
+    let backend = backend.ok_or_else(|| RefreshError::NoSession("backend url not set".to_string()))?;
+    let (token, body) = match exchange(http, &backend, upstream, account.as_deref(), legacy_owner.as_deref()).await {
+      Ok(v) => v,
+      Err(e) => {
+        if e.is_final() {
+          self.lifecycle.clear();
+        }
+        return Err(e);
+      }
+    };
+
+    // 刷新期间切换了服务器或账户时，不要把旧会话的 token 写进新配置
+    if let Ok(mut cfg) = config.write() {
+      if cfg.backend_base_url.as_deref() == Some(backend.as_str()) && cfg.account == account {
+        cfg.bearer_token = Some(token.clone());
+        self
+          .lifecycle
+          .schedule(&backend, body.get("data").and_then(|d| d.get("expiresIn")));
+      }
+    }
+
+    Ok(RefreshedSession { token, body })
+  }
+
+  /// 切换后端：等待进行中的刷新结束，再一次性替换后端地址、访问地址列表、账户并清空旧 token。
+  /// 新后端的 refresh token 按 URL + 用户名存在钥匙串里，下一次 refresh 会自动取用。
+  pub async fn switch_backend(
+    &self,
+    config: &Arc<RwLock<GatewayConfig>>,
+    backend: Option<String>,
+    endpoints: Vec<String>,
+    account: Option<String>,
+  ) -> Result<(), String> {
+    let _guard = self.lock.lock().await;
+    let mut cfg = config
+      .write()
+      .map_err(|_| "gateway state poisoned".to_string())?;
+    cfg.backend_base_url = backend;
+    cfg.endpoints = endpoints;
+    cfg.active_endpoint = None;
+    cfg.legacy_owner = account.clone();
+    cfg.account = account;
+    cfg.bearer_token = None;
+    self.lifecycle.clear();
+    Ok(())
+  }
+
+  /// 退出登录：等待进行中的刷新结束（它轮换出的 refresh token 也会被删掉），
+  /// 再删除当前账户的 refresh token 并清空 token、账户与刷新计划。返回被清理的服务器与账户。
+  pub async fn logout(
+    &self,
+    config: &Arc<RwLock<GatewayConfig>>,
+  ) -> Result<(Option<String>, Option<String>), String> {
+    let _guard = self.lock.lock().await;
+    let mut cfg = config
+      .write()
+      .map_err(|_| "gateway state poisoned".to_string())?;
+    let backend = cfg.backend_base_url.clone();
+    let account = cfg.account.take();
+    if let Some(backend) = backend.as_deref() {
+      let _ = crate::store_refresh_token_for_backend(backend, account.as_deref(), None);
+    }
+    cfg.legacy_owner = None;
+    cfg.bearer_token = None;
+    self.lifecycle.clear();
+    Ok((backend, account))
+  }
+
+  /// 切换同一服务器上的账户：先用目标账户的 refresh token 换到新 token，成功后才替换账户与 token；
+  /// 失败时网关配置保持不变，当前会话继续可用。
+  pub async fn switch_account(
+    &self,
+    http: &HttpClient,
+    config: &Arc<RwLock<GatewayConfig>>,
+    account: String,
+  ) -> Result<RefreshedSession, RefreshError> {
+    let _guard = self.lock.lock().await;
+
+    let (backend, legacy_owner, upstream) = {
+      let cfg = config
+        .read()
+        .map_err(|_| RefreshError::Transient("gateway state poisoned".to_string()))?;
+      (cfg.backend_base_url.clone(), cfg.legacy_owner.clone(), cfg.upstream_base_url())
+    };
+    let backend = backend.ok_or_else(|| RefreshError::NoSession("backend url not set".to_string()))?;
+    let (token, body) = exchange(http, &backend, upstream, Some(account.as_str()), legacy_owner.as_deref()).await?;
+
+    let mut cfg = config
+      .write()
+      .map_err(|_| RefreshError::Transient("gateway state poisoned".to_string()))?;
+    if cfg.backend_base_url.as_deref() != Some(backend.as_str()) {
+      return Err(RefreshError::Transient("backend changed during account switch".to_string()));
+    }
+    cfg.account = Some(account);
+    cfg.bearer_token = Some(token.clone());
+    self
+      .lifecycle
+      .schedule(&backend, body.get("data").and_then(|d| d.get("expiresIn")));
+
+    Ok(RefreshedSession { token, body })
+  }
+}
+
+/// 用 `account` 在钥匙串中的 refresh token 向后端换取新的 access token，并保存轮换后的 refresh token。
+/// 只读写钥匙串，不改网关配置；被拒绝（401/403）时清掉该账户的 refresh token。
+async fn exchange(
+  http: &HttpClient,
+  backend: &str,
+  upstream: Option<String>,
+  account: Option<&str>,
+  legacy_owner: Option<&str>,
+) -> Result<(String, serde_json::Value), RefreshError> {
+  let refresh_token = crate::load_refresh_token_for_backend(backend, account, legacy_owner)
+    .map_err(|e| RefreshError::Transient(e.message))?
+    .ok_or_else(|| RefreshError::NoSession("no refresh token".to_string()))?;
+  let url = format!("{}/api/auth/refresh", upstream.unwrap_or_else(|| backend.to_string()));
+
+  let resp = http
+    .with_request_timeout(http.get().post(url))
+    .json(&json!({ "refreshToken": refresh_token }))
+    .send()
+    .await
+    .map_err(|e| RefreshError::Transient(e.to_string()))?;
+
+  let status = resp.status();
+  let body = resp
+    .json::<serde_json::Value>()
+    .await
+    .map_err(|e| RefreshError::Transient(e.to_string()))?;
+
+  if !status.is_success() {
+    let msg = body
+      .get("message")
+      .and_then(|v| v.as_str())
+      .unwrap_or("refresh failed")
+      .to_string();
+    // 401/403：refresh 无效，清理本地 refresh token
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+      let _ = crate::store_refresh_token_for_backend(backend, account, None);
+      return Err(RefreshError::Rejected(msg));
+    }
+    return Err(RefreshError::Transient(msg));
+  }
+
+  let token = body
+    .get("data")
+    .and_then(|d| d.get("token"))
+    .and_then(|v| v.as_str())
+    .unwrap_or("")
+    .trim()
+    .to_string();
+  let next_refresh = body
+    .get("data")
+    .and_then(|d| d.get("refreshToken"))
+    .and_then(|v| v.as_str())
+    .map(|s| s.to_string());
+
+  if let Some(rt) = next_refresh {
+    crate::store_refresh_token_for_backend(backend, account, Some(rt)).map_err(|e| RefreshError::Transient(e.message))?;
+  }
+
+  if token.is_empty() {
+    return Err(RefreshError::Transient("refresh response missing token".to_string()));
+  }
+
+  Ok((token, body))
+}
+
+pub fn emit_auth_state(app: &tauri::AppHandle, state: &str, payload: serde_json::Value) {
+  let mut event = json!({ "state": state });
+  if let (Some(obj), serde_json::Value::Object(extra)) = (event.as_object_mut(), payload) {
+    obj.extend(extra);
+  }
+  let _ = app.emit("pdh-auth-state", event);
+}
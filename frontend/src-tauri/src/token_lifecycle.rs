@@ -0,0 +1,257 @@
+use std::{
+  sync::{Arc, Mutex, RwLock},
+  time::Duration,
+};
+
+use rand::Rng;
+use serde_json::json;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::gateway::GatewayConfig;
+use crate::health::{ConnectionState, HealthMonitor};
+use crate::net::HttpClient;
+use crate::session::{self, SessionRefresher};
+
+// 提前量：有效期的 1/10，限制在 [30s, 10min]；有效期很短时至少留 5s
+const MIN_LEAD: Duration = Duration::from_secs(30);
+const MAX_LEAD: Duration = Duration::from_secs(600);
+const MIN_DELAY: Duration = Duration::from_secs(5);
+// 网络错误后的重试间隔；离线时的检查间隔
+const RETRY_AFTER_ERROR: Duration = Duration::from_secs(30);
+const OFFLINE_POLL: Duration = Duration::from_secs(15);
+// 有效期上限：更长的（或离谱的）expiresIn 按 30 天算，最多提前刷新，不会溢出
+const MAX_LIFETIME: Duration = Duration::from_secs(30 * 86_400);
+
+struct Schedule {
+  // 计划刷新的服务器：切换服务器后旧计划作废
+  backend: String,
+  at: Instant,
+}
+
+/// access token 生命周期：按后端返回的 expiresIn 在过期前（带随机抖动）主动刷新。
+#[derive(Default)]
+pub struct TokenLifecycle {
+  schedule: Mutex<Option<Schedule>>,
+  wake: Notify,
+}
+
+/// 解析 expiresIn：数字为秒；字符串按 jsonwebtoken 的 `ms` 格式（"15m"、"24h"、"7d"，无单位为毫秒）。
+/// "never" 或无法识别时返回 None（不安排刷新）。
+pub fn parse_expires_in(value: &serde_json::Value) -> Option<Duration> {
+  if let Some(secs) = value.as_f64() {
+    return lifetime_from_secs(secs);
+  }
+  let raw = value.as_str()?.trim().to_ascii_lowercase();
+  let split = raw
+    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+    .unwrap_or(raw.len());
+  let (number, unit) = raw.split_at(split);
+  let number = number.parse::<f64>().ok()?;
+  let unit_secs = match unit.trim() {
+    "" | "ms" | "msec" | "msecs" | "millisecond" | "milliseconds" => 0.001,
+    "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+    "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+    "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+    "d" | "day" | "days" => 86_400.0,
+    "w" | "week" | "weeks" => 604_800.0,
+    "y" | "yr" | "yrs" | "year" | "years" => 31_557_600.0,
+    _ => return None,
+  };
+  lifetime_from_secs(number * unit_secs)
+}
+
+// 非正数、NaN 视为无效；无穷大或超出 Duration 范围的按上限处理
+fn lifetime_from_secs(secs: f64) -> Option<Duration> {
+  if secs.is_nan() || secs <= 0.0 {
+    return None;
+  }
+  let lifetime = Duration::try_from_secs_f64(secs).unwrap_or(MAX_LIFETIME);
+  Some(lifetime.min(MAX_LIFETIME))
+}
+
+fn refresh_delay(lifetime: Duration) -> Duration {
+  let lead = (lifetime / 10).clamp(MIN_LEAD, MAX_LEAD);
+  // 多个窗口/设备同时登录时错开刷新时间
+  let jitter_ms = rand::thread_rng().gen_range(0..=(lead.as_millis() as u64 / 2));
+  lifetime
+    .saturating_sub(lead)
+    .saturating_sub(Duration::from_millis(jitter_ms))
+    .max(MIN_DELAY.min(lifetime / 2))
+}
+
+impl TokenLifecycle {
+  fn set(&self, next: Option<Schedule>) {
+    match self.schedule.lock() {
+      Ok(mut guard) => *guard = next,
+      Err(poisoned) => *poisoned.into_inner() = next,
+    }
+    self.wake.notify_one();
+  }
+
+  fn current(&self) -> Option<(String, Instant)> {
+    let guard = match self.schedule.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    guard.as_ref().map(|s| (s.backend.clone(), s.at))
+  }
+
+  /// 拿到新 token 后按它的有效期安排下一次刷新；expiresIn 为 "never" 时取消计划。
+  pub fn schedule(&self, backend: &str, expires_in: Option<&serde_json::Value>) {
+    let next = expires_in
+      .and_then(parse_expires_in)
+      .and_then(|lifetime| Instant::now().checked_add(refresh_delay(lifetime)))
+      .map(|at| Schedule {
+        backend: backend.to_string(),
+        at,
+      });
+    self.set(next);
+  }
+
+  fn retry_later(&self, backend: &str, delay: Duration) {
+    let next = Instant::now().checked_add(delay).map(|at| Schedule {
+      backend: backend.to_string(),
+      at,
+    });
+    self.set(next);
+  }
+
+  /// 退出登录、会话失效或切换服务器时取消计划。
+  pub fn clear(&self) {
+    self.set(None);
+  }
+}
+
+pub fn spawn(
+  app: tauri::AppHandle,
+  config: Arc<RwLock<GatewayConfig>>,
+  http: Arc<HttpClient>,
+  session: Arc<SessionRefresher>,
+  health: Arc<HealthMonitor>,
+  lifecycle: Arc<TokenLifecycle>,
+) {
+  tauri::async_runtime::spawn(async move {
+    loop {
+      let Some((backend, at)) = lifecycle.current() else {
+        lifecycle.wake.notified().await;
+        continue;
+      };
+      if Instant::now() < at {
+        tokio::select! {
+          _ = tokio::time::sleep_until(at) => {}
+          _ = lifecycle.wake.notified() => continue,
+        }
+        // 睡眠期间计划可能已被替换
+        if lifecycle.current().map(|(_, a)| a) != Some(at) {
+          continue;
+        }
+      }
+
+      let (current_backend, token) = config
+        .read()
+        .map(|cfg| (cfg.backend_base_url.clone(), cfg.bearer_token.clone()))
+        .unwrap_or((None, None));
+      if current_backend.as_deref() != Some(backend.as_str()) {
+        lifecycle.clear();
+        continue;
+      }
+
+      // 离线时暂停：等健康检查恢复后再刷新（token 可能已过期，恢复后立即刷新）
+      if health.status(false).state == ConnectionState::Offline {
+        tokio::select! {
+          _ = tokio::time::sleep(OFFLINE_POLL) => {}
+          _ = lifecycle.wake.notified() => {}
+        }
+        continue;
+      }
+
+      match session.refresh(&http, &config, token.as_deref()).await {
+        Ok(refreshed) => {
+          if !refreshed.body.is_null() {
+            log::info!("[auth] access token refreshed before expiry");
+            session::emit_auth_state(&app, "refreshed", json!({ "expiresIn": refreshed.expires_in() }));
+          } else if lifecycle.current().map(|(_, a)| a) == Some(at) {
+            // token 已被别处换掉但没有带来新的有效期：稍后按新 token 再刷新一次
+            lifecycle.retry_later(&backend, RETRY_AFTER_ERROR);
+          }
+        }
+        Err(e) if e.is_final() => {
+          log::warn!("[auth] proactive refresh failed: {}", e.message());
+          lifecycle.clear();
+          session::emit_auth_state(&app, "expired", json!({ "reason": e.message() }));
+        }
+        Err(e) => {
+          log::info!("[auth] proactive refresh deferred: {}", e.message());
+          lifecycle.retry_later(&backend, RETRY_AFTER_ERROR);
+        }
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_expires_in_numbers_are_seconds() {
+    assert_eq!(parse_expires_in(&json!(900)), Some(Duration::from_secs(900)));
+    assert_eq!(parse_expires_in(&json!(1.5)), Some(Duration::from_millis(1500)));
+    assert_eq!(parse_expires_in(&json!(0)), None);
+    assert_eq!(parse_expires_in(&json!(-60)), None);
+  }
+
+  #[test]
+  fn parse_expires_in_ms_strings() {
+    assert_eq!(parse_expires_in(&json!("15m")), Some(Duration::from_secs(900)));
+    assert_eq!(parse_expires_in(&json!("24h")), Some(Duration::from_secs(86_400)));
+    assert_eq!(parse_expires_in(&json!("7d")), Some(Duration::from_secs(604_800)));
+    assert_eq!(parse_expires_in(&json!(" 2 Hours ")), Some(Duration::from_secs(7200)));
+    assert_eq!(parse_expires_in(&json!("30s")), Some(Duration::from_secs(30)));
+    // 无单位为毫秒
+    assert_eq!(parse_expires_in(&json!("1500")), Some(Duration::from_millis(1500)));
+  }
+
+  #[test]
+  fn parse_expires_in_rejects_unknown() {
+    assert_eq!(parse_expires_in(&json!("never")), None);
+    assert_eq!(parse_expires_in(&json!("10 fortnights")), None);
+    assert_eq!(parse_expires_in(&json!("")), None);
+    assert_eq!(parse_expires_in(&json!("0m")), None);
+    assert_eq!(parse_expires_in(&serde_json::Value::Null), None);
+  }
+
+  #[test]
+  fn parse_expires_in_caps_huge_values() {
+    assert_eq!(parse_expires_in(&json!(f64::MAX)), Some(MAX_LIFETIME));
+    assert_eq!(parse_expires_in(&json!(1e300)), Some(MAX_LIFETIME));
+    assert_eq!(parse_expires_in(&json!("99999999999999999999y")), Some(MAX_LIFETIME));
+    assert_eq!(parse_expires_in(&json!("90d")), Some(MAX_LIFETIME));
+    // 超长有效期也能安排刷新，不会在 Instant 相加时溢出
+    let lifecycle = TokenLifecycle::default();
+    lifecycle.schedule("http://backend", Some(&json!(f64::MAX)));
+    assert!(lifecycle.current().is_some());
+  }
+
+  #[test]
+  fn refresh_delay_leaves_lead_time() {
+    // 15 分钟：提前 90s，再减去最多 45s 抖动
+    for _ in 0..50 {
+      let delay = refresh_delay(Duration::from_secs(900));
+      assert!(delay >= Duration::from_secs(765) && delay <= Duration::from_secs(810), "{delay:?}");
+    }
+    // 24 小时：提前量封顶 10 分钟
+    for _ in 0..50 {
+      let delay = refresh_delay(Duration::from_secs(86_400));
+      assert!(delay >= Duration::from_secs(85_500) && delay <= Duration::from_secs(85_800), "{delay:?}");
+    }
+  }
+
+  #[test]
+  fn refresh_delay_short_lifetimes() {
+    // 有效期比最小提前量还短：至少等 5s 或有效期的一半
+    assert_eq!(refresh_delay(Duration::from_secs(20)), Duration::from_secs(5));
+    assert_eq!(refresh_delay(Duration::from_secs(4)), Duration::from_secs(2));
+  }
+}
@@ -1,4 +1,4 @@
-import React, { useState } from 'react';
+import React, { useCallback, useEffect, useState } from 'react';
 import {
   Button,
   CardContent,
@@ -14,11 +14,12 @@ import {
   ListItemText,
   Typography,
 } from '@mui/material';
-import { Logout as LogoutIcon, Person as PersonIcon } from '@mui/icons-material';
+import { Logout as LogoutIcon, Person as PersonIcon, SwitchAccount as SwitchAccountIcon } from '@mui/icons-material';
 import { useDispatch, useSelector } from 'react-redux';
-import { logout, selectAuthLoading, selectIsAuthenticated, selectUser } from '../../../store/authSlice';
+import { checkAuth, logout, selectAuthLoading, selectIsAuthenticated, selectUser } from '../../../store/authSlice';
 import { SettingsCard } from '../components/SettingsShell';
 import { getServerUrl } from '../../../services/serverConfig';
+import { authListAccounts, authSwitchAccount, isTauri } from '../../../services/tauriBridge';
 
 const AccountManagementCard = () => {
   const dispatch = useDispatch();
@@ -26,6 +27,42 @@ const AccountManagementCard = () => {
   const isAuthenticated = useSelector(selectIsAuthenticated);
   const authLoading = useSelector(selectAuthLoading);
   const [logoutConfirmOpen, setLogoutConfirmOpen] = useState(false);
+  const [accounts, setAccounts] = useState([]);
+  const [switching, setSwitching] = useState('');
+  const [switchError, setSwitchError] = useState('');
+
+  const serverUrl = getServerUrl();
+
+  // 同一服务器上登录过的其他账户（桌面端）
+  const reloadAccounts = useCallback(async () => {
+    if (!isTauri() || !serverUrl) {
+      setAccounts([]);
+      return;
+    }
+    const list = await authListAccounts(serverUrl).catch(() => []);
+    setAccounts(list?.[0]?.accounts || []);
+  }, [serverUrl]);
+
+  useEffect(() => {
+    reloadAccounts();
+  }, [reloadAccounts, user?.username]);
+
+  const handleSwitchAccount = async (username) => {
+    setSwitching(username);
+    setSwitchError('');
+    try {
+      const result = await authSwitchAccount(username);
+      if (!result?.authenticated) {
+        setSwitchError(`${username} 的登录已失效，请重新登录`);
+      }
+      await dispatch(checkAuth());
+    } catch (e) {
+      setSwitchError(e?.message || '切换账户失败');
+    } finally {
+      setSwitching('');
+      reloadAccounts();
+    }
+  };
 
   const openLogoutConfirm = () => setLogoutConfirmOpen(true);
   const closeLogoutConfirm = () => setLogoutConfirmOpen(false);
@@ -37,7 +74,7 @@ const AccountManagementCard = () => {
     closeLogoutConfirm();
   };
 
-  const serverUrl = getServerUrl();
+  const otherAccounts = accounts.filter((a) => a.username !== user?.username);
   const who = user?.username || '未登录';
   const statusText = serverUrl ? `${isAuthenticated ? '已登录' : '未登录'}：${who} @ ${serverUrl}` : '未配置服务器';
 
@@ -55,6 +92,33 @@ const AccountManagementCard = () => {
               </ListItemIcon>
               <ListItemText primary="当前状态" secondary={statusText} />
             </ListItem>
+            {otherAccounts.map((account) => (
+              <React.Fragment key={account.username}>
+                <Divider />
+                <ListItem>
+                  <ListItemIcon>
+                    <SwitchAccountIcon />
+                  </ListItemIcon>
+                  <ListItemText
+                    primary={account.username}
+                    secondary={account.hasSession || account.hasSavedPassword ? '可直接切换' : '需要重新登录'}
+                  />
+                  <Button
+                    variant="outlined"
+                    onClick={() => handleSwitchAccount(account.username)}
+                    disabled={authLoading || !!switching}
+                    startIcon={switching === account.username ? <CircularProgress size={16} color="inherit" /> : null}
+                  >
+                    切换
+                  </Button>
+                </ListItem>
+              </React.Fragment>
+            ))}
+            {switchError ? (
+              <ListItem>
+                <ListItemText secondary={switchError} secondaryTypographyProps={{ color: 'error' }} />
+              </ListItem>
+            ) : null}
             <Divider />
             <ListItem>
               <ListItemIcon>
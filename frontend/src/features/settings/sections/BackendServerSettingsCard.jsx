@@ -37,7 +37,7 @@ import {
   updateServer,
   upsertServer,
 } from '../../../services/serverConfig';
-import { isTauri, secretDeletePassword, secretGetPassword, secretSetPassword, setGatewayBackendUrl } from '../../../services/tauriBridge';
+import { isTauri, secretDeletePassword, secretHasPassword, secretSetPassword, setGatewayBackendUrl } from '../../../services/tauriBridge';
 import { ensureDesktopGatewayReady } from '../../../services/desktopGateway';
 import { checkAuth, login, selectAuthLoading, selectIsAuthenticated, selectUser } from '../../../store/authSlice';
 
@@ -194,10 +194,9 @@ const BackendServerSettingsCard = () => {
       if (action?.error) {
         // 桌面端：如果已保存密码，尝试自动登录
         if (isTauri() && next.username) {
-          const saved = await secretGetPassword(next.url, next.username).catch(() => null);
-          const password = typeof saved === 'string' ? saved : null;
-          if (password) {
-            const loginAction = await dispatch(login({ username: next.username, password }));
+          const saved = await secretHasPassword(next.url, next.username).catch(() => false);
+          if (saved) {
+            const loginAction = await dispatch(login({ username: next.username, useSavedPassword: true }));
             if (!loginAction?.error) {
               setStatus({ ok: true, message: `已切换并自动登录：${next.url}` });
               return;
@@ -279,12 +278,10 @@ const BackendServerSettingsCard = () => {
         const username = (form.username || selected.username || '').trim();
         if (!username) throw new Error('请输入用户名');
 
-        let passwordToUse = String(form.password || '').trim();
-        if (!passwordToUse && isTauri()) {
-          const saved = await secretGetPassword(normalized, username).catch(() => null);
-          if (typeof saved === 'string' && saved.trim()) passwordToUse = saved.trim();
-        }
-        if (!passwordToUse) throw new Error('请输入密码（或在桌面端先勾选记住密码）');
+        const passwordToUse = String(form.password || '').trim();
+        const useSavedPassword =
+          !passwordToUse && isTauri() && !!(await secretHasPassword(normalized, username).catch(() => false));
+        if (!passwordToUse && !useSavedPassword) throw new Error('请输入密码（或在桌面端先勾选记住密码）');
 
         // 切换激活服务器，并把用户名写回服务器配置（方便下次直接填充）
         setActiveServerId(selected.id);
@@ -295,7 +292,7 @@ const BackendServerSettingsCard = () => {
           await setGatewayBackendUrl(normalized).catch(() => {});
         }
 
-        const action = await dispatch(login({ username, password: passwordToUse }));
+        const action = await dispatch(login({ username, password: passwordToUse, useSavedPassword }));
         if (action?.error) {
           throw new Error(action.payload || action.error?.message || '登录失败');
         }
@@ -5,7 +5,6 @@
 
 import axios from 'axios';
 import { ensureDesktopGatewayReady } from './desktopGateway';
-import { setAuthToken } from './authToken';
 import { authRefresh, isTauri } from './tauriBridge';
 
 // 创建 axios 实例
@@ -40,14 +39,25 @@ apiClient.interceptors.request.use(
 
 // 响应拦截器 - 处理错误
 apiClient.interceptors.response.use(
-  (response) => {
-    const refreshedToken = response?.headers?.['x-pdh-auth-token'];
-    if (refreshedToken && typeof refreshedToken === 'string') {
-      setAuthToken(refreshedToken);
-    }
-    return response;
-  },
+  (response) => response,
   async (error) => {
+    // 网关为每个请求分配的关联 ID，反馈问题时可据此在后端日志中定位
+    const requestId = error.response?.headers?.['x-request-id'];
+    if (requestId) {
+      error.requestId = requestId;
+    }
+
+    // 网关自身的错误（未配置服务器、DNS/TLS/超时等）：带上结构化信息，便于界面区分提示
+    const gatewayErrorCode = error.response?.headers?.['x-pdh-gateway-error'];
+    if (gatewayErrorCode) {
+      const detail = error.response?.data?.error || {};
+      error.gatewayError = {
+        code: gatewayErrorCode,
+        message: detail.message || error.response?.data?.message || '',
+        cause: detail.cause || null,
+      };
+    }
+
     // 处理 401 未授权错误
     if (error.response?.status === 401) {
       const url = String(error.config?.url || '');
@@ -66,20 +76,14 @@ apiClient.interceptors.response.use(
             const refreshResponse = await refreshInFlight;
             refreshInFlight = null;
 
-            const nextToken = refreshResponse?.data?.token;
-            if (refreshResponse?.success && nextToken) {
-              setAuthToken(nextToken);
-
-              // 确保网关拿到新 token，再重放原请求
-              await ensureDesktopGatewayReady().catch(() => {});
-
+            // 新 token 已由网关接管，直接重放原请求
+            if (refreshResponse?.success) {
               const nextConfig = { ...error.config, __pdhRetried: true };
               return apiClient(nextConfig);
             }
           }
         } catch (_) {
           refreshInFlight = null;
-          setAuthToken('');
         }
       }
 
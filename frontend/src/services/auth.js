@@ -4,7 +4,7 @@
  */
 
 import apiClient from './apiClient';
-import { authLogin, clearRefreshToken, isTauri } from './tauriBridge';
+import { authLogin, authLoginWithSavedCredentials, authLogout, isTauri } from './tauriBridge';
 
 /**
  * 用户登录
@@ -25,6 +25,19 @@ export const login = async (username, password) => {
   }
 };
 
+/**
+ * 使用已保存的密码登录（仅桌面端：密码由 Tauri 侧从凭据库读取）
+ * @param {string} username - 用户名
+ * @returns {Promise<Object>} 登录结果
+ */
+export const loginWithSavedCredentials = async (username) => {
+  try {
+    return await authLoginWithSavedCredentials(username);
+  } catch (error) {
+    throw handleApiError(error);
+  }
+};
+
 /**
  * 获取当前用户信息
  * @returns {Promise<Object>} 用户信息
@@ -40,18 +53,21 @@ export const getCurrentUser = async () => {
 
 /**
  * 用户登出
+ * @param {Object} [options]
+ * @param {string} [options.username] - 当前用户名（忘记密码时需要）
+ * @param {boolean} [options.forgetPassword] - 是否同时删除保存的密码
  * @returns {Promise<Object>} 登出结果
  */
-export const logout = async () => {
+export const logout = async ({ username = null, forgetPassword = false } = {}) => {
   try {
-    try {
-      const response = await apiClient.post('/auth/logout');
-      return response.data;
-    } finally {
-      if (isTauri()) {
-        await clearRefreshToken().catch(() => {});
-      }
+    // 桌面端：由 Tauri 侧通知后端撤销会话并清理本机会话（离线时也能完成本地清理）
+    if (isTauri()) {
+      const result = await authLogout({ username, forgetPassword });
+      return { success: true, data: result, message: '登出成功' };
     }
+
+    const response = await apiClient.post('/auth/logout');
+    return response.data;
   } catch (error) {
     throw handleApiError(error);
   }
@@ -101,6 +117,7 @@ export const clearAuthData = () => {
 
 const authService = {
   login,
+  loginWithSavedCredentials,
   getCurrentUser,
   logout,
   clearAuthData,
@@ -1,6 +1,5 @@
-import { getAuthToken } from './authToken';
-import { getServerUrl, isDesktopTauri } from './serverConfig';
-import { getGatewayUrl, setGatewayBackendUrl, setGatewayToken } from './tauriBridge';
+import { getServerUrl, isDesktopTauri, setServerUrl } from './serverConfig';
+import { getGatewayUrl, listen, setGatewayBackendUrl } from './tauriBridge';
 
 const sleep = (ms) => new Promise((r) => setTimeout(r, ms));
 
@@ -14,13 +13,6 @@ const syncGatewayConfig = async () => {
   } catch (_) {
     // ignore
   }
-
-  try {
-    const token = getAuthToken();
-    await setGatewayToken(token || null);
-  } catch (_) {
-    // ignore
-  }
 };
 
 export const installDesktopGatewaySync = () => {
@@ -33,10 +25,30 @@ export const installDesktopGatewaySync = () => {
     setGatewayBackendUrl(next).catch(() => {});
   });
 
-  window.addEventListener('pdh-auth-token-changed', () => {
-    const token = getAuthToken();
-    setGatewayToken(token || null).catch(() => {});
-  });
+  // 旧版本把 access token 存在 localStorage；现在 token 只留在网关里，顺手清掉
+  try {
+    window.localStorage.removeItem('pdh_auth_token');
+  } catch (_) {
+    // ignore
+  }
+
+  // Tauri 侧切换了服务器配置：前端跟随，避免下次同步时把旧地址推回网关
+  listen('pdh-profile-switched', (event) => {
+    const url = event?.payload?.profile?.url || '';
+    if (url && url !== getServerUrl()) setServerUrl(url);
+  }).catch(() => {});
+
+  // 网关遇到 401 或临近过期会自行刷新 token（token 不经过前端）：这里只处理会话失效
+  listen('pdh-auth-state', (event) => {
+    const payload = event?.payload || {};
+    if (payload.state === 'expired') {
+      window.dispatchEvent(
+        new CustomEvent('auth-required', {
+          detail: { message: '需要重新登录' },
+        })
+      );
+    }
+  }).catch(() => {});
 };
 
 export const ensureDesktopGatewayReady = async ({ timeoutMs = 15000 } = {}) => {
@@ -1,4 +1,5 @@
-import { invoke, isTauri, convertFileSrc } from './tauriBridge';
+import { invoke, isTauri } from './tauriBridge';
+import { ensureDesktopGatewayReady } from './desktopGateway';
 
 const nowIso = () => new Date().toISOString();
 
@@ -28,8 +29,13 @@ const resolveWallpaperUrl = async (url) => {
   if (/^(data:|blob:|https?:)/i.test(trimmed)) return trimmed;
   if (!isTauri()) return trimmed;
 
+  // 壁纸文件都保存在数据目录 themes/wallpapers 下，经本机网关按文件名读取
+  const fileName = trimmed.split(/[\\/]/).pop();
+  if (!fileName) return trimmed;
+
   try {
-    return await convertFileSrc(trimmed);
+    const gateway = await ensureDesktopGatewayReady();
+    return gateway ? `${gateway}/wallpapers/${encodeURIComponent(fileName)}` : trimmed;
   } catch (_) {
     return trimmed;
   }
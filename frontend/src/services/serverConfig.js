@@ -1,4 +1,3 @@
-import { clearAuthToken } from './authToken';
 import { isTauri } from './tauriBridge';
 
 const LEGACY_SERVER_URL_KEY = 'pdh_server_url';
@@ -173,9 +172,6 @@ export const setActiveServerId = (id) => {
     // ignore
   }
 
-  // 切换服务器时避免携带旧 token
-  clearAuthToken();
-
   try {
     window.dispatchEvent(new CustomEvent('pdh-server-changed', { detail: { serverUrl: nextUrl } }));
     window.dispatchEvent(new CustomEvent('pdh-auth-reset', { detail: { reason: 'server-changed' } }));
@@ -267,8 +263,6 @@ export const removeServer = (id) => {
       // ignore
     }
 
-    clearAuthToken();
-
     try {
       window.dispatchEvent(new CustomEvent('pdh-server-changed', { detail: { serverUrl: nextUrl } }));
       window.dispatchEvent(new CustomEvent('pdh-auth-reset', { detail: { reason: 'server-removed' } }));
@@ -292,7 +286,6 @@ export const setServerUrl = (raw) => {
 
     writeServers([]);
     writeActiveServerId('');
-    clearAuthToken();
 
     try {
       window.dispatchEvent(new CustomEvent('pdh-server-changed', { detail: { serverUrl: '' } }));
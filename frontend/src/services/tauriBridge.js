@@ -20,17 +20,28 @@ const getGlobalInvoke = () => {
   return null;
 };
 
-export const invoke = async (command, args) => {
+// 命令失败时后端返回 { code, message, details, retryable }：转成 Error 并保留结构化字段，
+// 界面按 code 本地化提示，不要匹配 message 文本
+const toCommandError = (err) => {
+  if (!err || typeof err !== 'object' || typeof err.code !== 'string') return err;
+  const error = new Error(err.message || err.code);
+  error.code = err.code;
+  error.details = err.details ?? null;
+  error.retryable = !!err.retryable;
+  return error;
+};
+
+const resolveInvoke = async () => {
   const globalInvoke = getGlobalInvoke();
-  if (globalInvoke) return globalInvoke(command, args);
+  if (globalInvoke) return globalInvoke;
 
-  if (cachedModuleInvoke) return cachedModuleInvoke(command, args);
+  if (cachedModuleInvoke) return cachedModuleInvoke;
 
   try {
     const mod = await import('@tauri-apps/api/core');
     if (typeof mod.invoke === 'function') {
       cachedModuleInvoke = mod.invoke;
-      return cachedModuleInvoke(command, args);
+      return cachedModuleInvoke;
     }
   } catch (_) {
     // ignore
@@ -39,6 +50,15 @@ export const invoke = async (command, args) => {
   throw new Error('tauri invoke not available');
 };
 
+export const invoke = async (command, args) => {
+  const fn = await resolveInvoke();
+  try {
+    return await fn(command, args);
+  } catch (err) {
+    throw toCommandError(err);
+  }
+};
+
 const getGlobalConvertFileSrc = () => {
   if (typeof window === 'undefined') return null;
   const candidates = [
@@ -73,8 +93,11 @@ export const convertFileSrc = async (path, protocol) => {
 };
 
 export const getGatewayUrl = async () => invoke('pdh_gateway_url');
+// 后端连通状态（online/degraded/offline）与最近的探测历史；每次探测后另有 pdh-gateway-status 事件
+export const getGatewayStatus = async () => invoke('pdh_gateway_status');
+// 连接诊断：DNS/TCP/TLS/health/auth/时钟偏差，返回可直接贴进 issue 的结构化报告；不传 url 时诊断当前地址
+export const diagnoseGateway = async (url = null) => invoke('pdh_gateway_diagnose', { url });
 export const setGatewayBackendUrl = async (url) => invoke('pdh_gateway_set_backend_url', { url });
-export const setGatewayToken = async (token) => invoke('pdh_gateway_set_token', { token });
 
 let cachedListen = null;
 
@@ -106,13 +129,56 @@ export const listen = async (eventName, handler) => {
 
 // 认证：refresh token 存在 Tauri 侧（OS 凭据库），前端不持有明文
 export const authLogin = async (username, password) => invoke('pdh_auth_login', { username, password });
+// 用“记住密码”保存的凭据登录当前服务器：密码只在 Tauri 侧读取
+export const authLoginWithSavedCredentials = async (username) =>
+  invoke('pdh_auth_login_with_saved_credentials', { username });
 export const authRefresh = async () => invoke('pdh_auth_refresh');
 export const clearRefreshToken = async () => invoke('pdh_auth_clear_refresh_token');
-
-// 本地密钥/凭据（OS 凭据库）：用于“记住密码”
+// 多账户：按服务器列出已知账户；切换账户时用该账户保存的会话（或保存的密码）登录，不需要重新输入密码
+export const authListAccounts = async (backendBaseUrl = null) => invoke('pdh_auth_list_accounts', { backendBaseUrl });
+export const authSwitchAccount = async (username) => invoke('pdh_auth_switch_account', { username });
+// 退出登录：后端撤销会话（尽力）、清理网关与凭据库中的会话、取消上传；forgetPassword 时删除保存的密码
+export const authLogout = async ({ username = null, forgetPassword = false } = {}) =>
+  invoke('pdh_auth_logout', { username, forgetPassword });
+
+// 本地密钥/凭据（OS 凭据库）：用于“记住密码”；前端只能知道是否已保存，拿不到明文
 export const secretSetPassword = async (backendBaseUrl, username, password) =>
   invoke('pdh_secret_set_password', { backendBaseUrl, username, password });
-export const secretGetPassword = async (backendBaseUrl, username) =>
-  invoke('pdh_secret_get_password', { backendBaseUrl, username });
+export const secretHasPassword = async (backendBaseUrl, username) =>
+  invoke('pdh_secret_has_password', { backendBaseUrl, username });
 export const secretDeletePassword = async (backendBaseUrl, username) =>
   invoke('pdh_secret_delete_password', { backendBaseUrl, username });
+
+// 附件本地缓存（网关侧，按容量 LRU 淘汰）
+export const attachmentCacheInfo = async () => invoke('pdh_attachment_cache_info');
+export const attachmentCacheSetCap = async (capBytes) =>
+  invoke('pdh_attachment_cache_set_cap', { capBytes });
+export const attachmentCachePurge = async () => invoke('pdh_attachment_cache_purge');
+
+// 服务器配置（持久化在 Tauri 侧，切换时网关同步切换会话）
+// 网关请求检查器：默认关闭；开启后可监听 'pdh-gateway-inspect' 事件获取实时记录
+export const gatewayInspectSetEnabled = async (enabled) =>
+  invoke('pdh_gateway_inspect_set_enabled', { enabled: !!enabled });
+export const gatewayInspectList = async () => invoke('pdh_gateway_inspect_list');
+export const gatewayInspectClear = async () => invoke('pdh_gateway_inspect_clear');
+export const getGatewayMetrics = async () => invoke('pdh_gateway_metrics');
+export const getNetworkSettings = async () => invoke('pdh_network_settings_get');
+export const setNetworkSettings = async (settings) => invoke('pdh_network_settings_set', { settings });
+export const getLogSettings = async () => invoke('pdh_log_settings_get');
+export const setLogSettings = async (settings) => invoke('pdh_log_settings_set', { settings });
+export const exportDiagnostics = async () => invoke('pdh_export_diagnostics');
+export const profilesList = async () => invoke('pdh_profiles_list');
+export const profilesAdd = async ({ name = '', url, endpoints = [], lastUsername = null, isDefault = false } = {}) =>
+  invoke('pdh_profiles_add', { name, url, endpoints, lastUsername, isDefault });
+export const profilesSetEndpoints = async (id, endpoints) =>
+  invoke('pdh_profiles_set_endpoints', { id, endpoints });
+// clientKeyPem：不传保留已保存的私钥，传空字符串删除
+export const profilesSetTls = async (id, { caPem = null, pinnedSpkiSha256 = [], clientCertPem = null, clientKeyPem } = {}) =>
+  invoke('pdh_profiles_set_tls', { id, caPem, pinnedSpkiSha256, clientCertPem, clientKeyPem: clientKeyPem ?? null });
+// proxy: { url, username, noProxy } 或 null（关闭）；password 不传保留已保存的密码
+export const profilesSetProxy = async (id, proxy, password) =>
+  invoke('pdh_profiles_set_proxy', { id, proxy: proxy ?? null, password: password ?? null });
+export const proxyTest = async (proxy, { password, url } = {}) =>
+  invoke('pdh_proxy_test', { proxy, password: password ?? null, url: url ?? null });
+export const profilesSwitch = async (id) => invoke('pdh_profiles_switch', { id });
+export const profilesRemove = async (id) => invoke('pdh_profiles_remove', { id });
@@ -5,21 +5,17 @@
 
 import { createSlice, createAsyncThunk } from '@reduxjs/toolkit';
 import authService from '../services/auth';
-import { clearAuthToken, setAuthToken } from '../services/authToken';
-import { clearRefreshToken as clearTauriRefreshToken, isTauri } from '../services/tauriBridge';
 
-// 异步 thunk：用户登录
+// 异步 thunk：用户登录（useSavedPassword：桌面端用已保存的密码，前端不经手明文）
 export const login = createAsyncThunk(
   'auth/login',
-  async ({ username, password }, { rejectWithValue }) => {
+  async ({ username, password, useSavedPassword = false }, { rejectWithValue }) => {
     try {
-      const response = await authService.login(username, password);
+      const response = useSavedPassword
+        ? await authService.loginWithSavedCredentials(username)
+        : await authService.login(username, password);
       
       if (response.success && response.data && response.data.user) {
-        const token = response.data.token;
-        if (token) setAuthToken(token);
-        else clearAuthToken();
-
         return {
           user: response.data.user,
           isAuthenticated: true
@@ -61,16 +57,10 @@ export const checkAuth = createAsyncThunk(
 // 异步 thunk：用户登出
 export const logout = createAsyncThunk(
   'auth/logout',
-  async (_, { rejectWithValue }) => {
+  async ({ forgetPassword = false } = {}, { getState, rejectWithValue }) => {
     try {
-      try {
-        await authService.logout();
-      } finally {
-        clearAuthToken();
-        if (isTauri()) {
-          clearTauriRefreshToken().catch(() => {});
-        }
-      }
+      const username = getState()?.auth?.user?.username || null;
+      await authService.logout({ username, forgetPassword });
       return { isAuthenticated: false };
     } catch (error) {
       return rejectWithValue(